@@ -51,8 +51,8 @@ fn main() {
     for [c1, c2] in &colors {
         let mut linear = LinearMixer::new();
         let mut rms = RMSMixer::new();
-        linear.add(&c1).add(&c2);
-        rms.add(&c1).add(&c2);
+        linear.add(c1).add(c2);
+        rms.add(c1).add(c2);
         mixed.push([linear.mix(), rms.mix()]);
     }
 
@@ -0,0 +1,104 @@
+use std::f64::consts::TAU;
+
+use glam::DVec3;
+use image::RgbImage;
+
+use crate::camera::Camera;
+use crate::camera_path::{CameraPath, CameraPose};
+use crate::color::ColorMixer;
+use crate::integrators::Integrator;
+use crate::render_spec::RenderSpec;
+use crate::world::Scene;
+
+// Everything needed to render one frame. Moving objects are placed by the
+// time of each ray rather than evaluated here.
+pub struct Frame<'a> {
+    pub time: f64,
+    pub camera: Camera,
+    pub render_spec: Box<dyn RenderSpec>,
+    pub world: &'a dyn Scene,
+}
+
+pub trait Animation {
+    fn frame_count(&self) -> usize;
+
+    // Evaluates the time-varying camera at the given frame
+    fn frame(&self, index: usize) -> Frame<'_>;
+}
+
+// Renders the frames one after the other, handing each image over as soon as
+// it is done so that long animations need not stay in memory. Stops at the
+// first frame `on_frame` fails on.
+pub fn render_animation<M: ColorMixer>(
+    animation: &dyn Animation,
+    integrator: &dyn Integrator,
+    mut on_frame: impl FnMut(usize, RgbImage) -> Result<(), String>,
+) -> Result<(), String> {
+    for index in 0..animation.frame_count() {
+        let frame = animation.frame(index);
+        let image = frame
+            .camera
+            .render::<M>(&*frame.render_spec, frame.world, integrator);
+        on_frame(index, image)?;
+    }
+    Ok(())
+}
+
+// How the camera moves over an animation
+pub enum CameraMotion {
+    Path(CameraPath),
+    // Orbits around `target` about the world Y axis, `turns` times over the
+    // animation
+    Turntable { target: DVec3, turns: f64 },
+}
+
+impl CameraMotion {
+    // `camera` and `fov` are where a turntable starts from, `duration` how
+    // long the whole animation lasts
+    pub fn pose(&self, camera: &Camera, fov: f64, time: f64, duration: f64) -> CameraPose {
+        match self {
+            CameraMotion::Path(path) => path.sample(time),
+            CameraMotion::Turntable { target, turns } => {
+                let progress = if duration > 0f64 {
+                    time / duration
+                } else {
+                    0f64
+                };
+                CameraPose {
+                    camera: camera.orbit(*target, TAU * turns * progress, 0f64),
+                    fov,
+                }
+            }
+        }
+    }
+
+    pub fn start_time(&self) -> f64 {
+        match self {
+            CameraMotion::Path(path) => path.start_time(),
+            CameraMotion::Turntable { .. } => 0f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_turntable() {
+        let camera = Camera::look_at(DVec3::new(0f64, 1f64, 4f64), DVec3::ZERO, DVec3::Y);
+        let motion = CameraMotion::Turntable {
+            target: DVec3::ZERO,
+            turns: 1f64,
+        };
+        let quarter = motion.pose(&camera, 50f64, 1f64, 4f64);
+        assert!((quarter.camera.position() - DVec3::new(4f64, 1f64, 0f64)).length() < 1e-9);
+        assert_eq!(quarter.fov, 50f64);
+        // still looking at the target
+        let to_target = (DVec3::ZERO - quarter.camera.position()).normalize();
+        assert!((quarter.camera.forward() - to_target).length() < 1e-9);
+
+        let full = motion.pose(&camera, 50f64, 4f64, 4f64);
+        assert!((full.camera.position() - camera.position()).length() < 1e-9);
+    }
+}
@@ -1,20 +1,32 @@
+use glam::DMat3;
 use glam::DQuat;
 use glam::DVec3;
 
 use crate::color::ColorMixer;
 use crate::color::LinearRgbColor;
+use crate::integrators::Integrator;
 use crate::ray::Ray;
 use crate::render_spec::RenderSpec;
-use crate::utils::Interval;
 use crate::world::Scene;
 use image::{ImageBuffer, Rgb};
 use indicatif::{ParallelProgressIterator, ProgressStyle};
 use rayon::prelude::*;
 
+#[derive(Clone, Copy)]
 pub struct Camera {
     rotation: DQuat,
     position: DVec3,
-    max_depth: u32,
+    motion: Option<Motion>,
+}
+
+// Where a moving camera ends up, it moves at a constant speed from its
+// position at `start_time` and stays there after `end_time`
+#[derive(Clone, Copy)]
+struct Motion {
+    start_time: f64,
+    end_time: f64,
+    rotation: DQuat,
+    position: DVec3,
 }
 
 impl Camera {
@@ -22,14 +34,102 @@ impl Camera {
         Self {
             rotation,
             position,
-            max_depth: 10,
+            motion: None,
+        }
+    }
+
+    // Moves to `end` over the given times, blurring the image when the
+    // shutter stays open meanwhile
+    pub fn with_motion(mut self, end: &Camera, start_time: f64, end_time: f64) -> Self {
+        self.motion = (end_time > start_time).then_some(Motion {
+            start_time,
+            end_time,
+            rotation: end.rotation,
+            position: end.position,
+        });
+        self
+    }
+
+    // Looking from `position` towards `target`, with `up` pointing to the top
+    // of the image as much as possible
+    pub fn look_at(position: DVec3, target: DVec3, up: DVec3) -> Self {
+        let forward = (target - position).try_normalize().unwrap_or(DVec3::NEG_Z);
+        // any roll will do when looking straight along `up`
+        let right = forward
+            .cross(up)
+            .try_normalize()
+            .unwrap_or_else(|| forward.any_orthonormal_vector());
+        let up = right.cross(forward);
+        let rotation = DQuat::from_mat3(&DMat3::from_cols(right, up, -forward));
+        Self::new(position, rotation)
+    }
+
+    // Turns the camera around `target`, `yaw` around the world Y axis then
+    // `pitch` around its own X axis, in radians. A camera looking at `target`
+    // keeps looking at it.
+    pub fn orbit(&self, target: DVec3, yaw: f64, pitch: f64) -> Self {
+        let turn =
+            DQuat::from_axis_angle(DVec3::Y, yaw) * DQuat::from_axis_angle(self.right(), pitch);
+        Self::new(
+            target + turn * (self.position - target),
+            (turn * self.rotation).normalize(),
+        )
+    }
+
+    // Moves the camera forward, or backward for negative distances
+    pub fn dolly(&self, distance: f64) -> Self {
+        Self::new(self.position + self.forward() * distance, self.rotation)
+    }
+
+    pub fn forward(&self) -> DVec3 {
+        self.rotation * DVec3::NEG_Z
+    }
+
+    pub fn up(&self) -> DVec3 {
+        self.rotation * DVec3::Y
+    }
+
+    pub fn right(&self) -> DVec3 {
+        self.rotation * DVec3::X
+    }
+
+    // Of the camera at rest, or when a moving one starts
+    pub fn position(&self) -> DVec3 {
+        self.position
+    }
+
+    pub fn rotation(&self) -> DQuat {
+        self.rotation
+    }
+
+    pub fn pose_at(&self, time: f64) -> (DVec3, DQuat) {
+        let Some(motion) = &self.motion else {
+            return (self.position, self.rotation);
+        };
+        let t =
+            ((time - motion.start_time) / (motion.end_time - motion.start_time)).clamp(0f64, 1f64);
+        (
+            self.position.lerp(motion.position, t),
+            self.rotation.slerp(motion.rotation, t),
+        )
+    }
+
+    // Moves a ray from camera space, looking down -Z, to the world, where the
+    // camera is at the time of the ray
+    pub fn to_world(&self, ray: &Ray) -> Ray {
+        let (position, rotation) = self.pose_at(ray.time);
+        Ray {
+            direction: rotation * ray.direction,
+            origin: position + rotation * ray.origin,
+            time: ray.time,
         }
     }
 
     pub fn render<M: ColorMixer>(
         &self,
-        render_spec: &impl RenderSpec,
-        world: &impl Scene,
+        render_spec: &dyn RenderSpec,
+        world: &dyn Scene,
+        integrator: &dyn Integrator,
     ) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
         let size = render_spec.image_size();
         let width = size.width as usize;
@@ -49,9 +149,16 @@ impl Camera {
             .progress_with_style(style)
             .enumerate()
             .for_each(|(y, row)| {
-                for x in 0..width {
-                    row[x] = self
-                        .render_pixel(&mut M::new(), render_spec, world, x as u32, y as u32)
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    *pixel = self
+                        .render_pixel(
+                            &mut M::new(),
+                            render_spec,
+                            world,
+                            integrator,
+                            x as u32,
+                            y as u32,
+                        )
                         .into();
                 }
             });
@@ -67,41 +174,88 @@ impl Camera {
     fn render_pixel(
         &self,
         mixer: &mut impl ColorMixer,
-        render_spec: &impl RenderSpec,
-        world: &impl Scene,
+        render_spec: &dyn RenderSpec,
+        world: &dyn Scene,
+        integrator: &dyn Integrator,
         x: u32,
         y: u32,
     ) -> LinearRgbColor {
         let rays = render_spec.ray_for_pixel(x, y);
 
         for ray in rays {
-            let rotated_ray = Ray {
-                direction: self.rotation * ray.direction,
-                origin: self.position + ray.origin,
-            };
-            let color = Self::ray_color(&rotated_ray, world, self.max_depth);
+            let color = integrator.radiance(&self.to_world(&ray), world);
             mixer.add(&color);
         }
 
         mixer.mix()
     }
+}
 
-    fn ray_color<W: Scene>(ray: &Ray, world: &W, depth: u32) -> LinearRgbColor {
-        if depth <= 0 {
-            // too many reflections, no light remaining
-            return LinearRgbColor::from_hex(0x000000);
-        }
-        // add a small eps to fix shadow acne
-        let eps = 0.001;
-        if let Some(hit_rec) = world.hit(ray, &Interval::greater_than(eps)) {
-            if let Some(scatter_rec) = hit_rec.mat.scatter(ray, &hit_rec) {
-                return Self::ray_color(&scatter_rec.scattered, world, depth - 1)
-                    .attenute(scatter_rec.attenuation_factor);
-            }
-            // error color
-            return LinearRgbColor::from_hex(0x6000a0);
-        }
-        // miss, background color
-        world.miss(ray)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn assert_close(a: DVec3, b: DVec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let camera = Camera::look_at(DVec3::new(0f64, 0f64, 5f64), DVec3::ZERO, DVec3::Y);
+        assert_close(camera.forward(), DVec3::NEG_Z);
+        assert_close(camera.up(), DVec3::Y);
+
+        let camera = Camera::look_at(DVec3::new(3f64, 3f64, 0f64), DVec3::ZERO, DVec3::Y);
+        assert_close(camera.forward(), DVec3::new(-1f64, -1f64, 0f64).normalize());
+        assert_close(camera.right(), DVec3::NEG_Z);
+        assert!(camera.up().y > 0f64);
+
+        // straight down, the roll is arbitrary but the camera is still valid
+        let camera = Camera::look_at(DVec3::Y, DVec3::ZERO, DVec3::Y);
+        assert_close(camera.forward(), DVec3::NEG_Y);
+        assert!((camera.up().length() - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_orbit_and_dolly() {
+        let target = DVec3::new(1f64, 0f64, 0f64);
+        let camera = Camera::look_at(DVec3::new(1f64, 0f64, 4f64), target, DVec3::Y);
+
+        let turned = camera.orbit(target, FRAC_PI_2, 0f64);
+        assert_close(turned.position(), DVec3::new(5f64, 0f64, 0f64));
+        assert_close(turned.forward(), DVec3::NEG_X);
+
+        let raised = camera.orbit(target, 0f64, -FRAC_PI_2 / 2f64);
+        assert!(raised.position().y > 0f64);
+        assert!(((raised.position() - target).length() - 4f64).abs() < 1e-9);
+        assert_close(raised.forward(), (target - raised.position()).normalize());
+
+        let closer = camera.dolly(1.5);
+        assert_close(closer.position(), DVec3::new(1f64, 0f64, 2.5));
+        assert_close(closer.forward(), camera.forward());
+    }
+
+    #[test]
+    fn test_motion() {
+        let start = Camera::new(DVec3::ZERO, DQuat::IDENTITY);
+        let end = Camera::new(
+            DVec3::new(2f64, 0f64, 0f64),
+            DQuat::from_rotation_y(FRAC_PI_2),
+        );
+        let camera = start.with_motion(&end, 1f64, 2f64);
+
+        let ray = camera.to_world(&Ray::new(DVec3::ZERO, DVec3::NEG_Z).with_time(1.5));
+        assert_close(ray.origin, DVec3::new(1f64, 0f64, 0f64));
+        assert_close(
+            ray.direction,
+            DQuat::from_rotation_y(FRAC_PI_2 / 2f64) * DVec3::NEG_Z,
+        );
+        assert_eq!(ray.time, 1.5);
+
+        // at rest before and after the motion
+        assert_close(camera.pose_at(0f64).0, DVec3::ZERO);
+        assert_close(camera.pose_at(3f64).0, DVec3::new(2f64, 0f64, 0f64));
+        assert_close(start.pose_at(1.5).0, DVec3::ZERO);
     }
 }
@@ -0,0 +1,225 @@
+use std::ops::{Add, Mul, Sub};
+
+use glam::DVec3;
+
+use crate::camera::Camera;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    // in seconds
+    pub time: f64,
+    pub position: DVec3,
+    pub target: DVec3,
+    // diagonal field of view, in degrees
+    pub fov: f64,
+}
+
+// Where the camera is at a given time, and how wide it looks
+pub struct CameraPose {
+    pub camera: Camera,
+    pub fov: f64,
+}
+
+// Smooth camera motion through keyframes. Positions, targets and fields of
+// view are interpolated independently with Catmull-Rom splines, so the camera
+// passes through every keyframe and keeps looking at the interpolated target.
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    up: DVec3,
+}
+
+impl CameraPath {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "a camera path needs keyframes");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            keyframes,
+            up: DVec3::Y,
+        }
+    }
+
+    pub fn with_up(mut self, up: DVec3) -> Self {
+        self.up = up;
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.keyframes[0].time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.end_time() - self.start_time()
+    }
+
+    // Holds the first and last keyframes outside of the path
+    pub fn sample(&self, time: f64) -> CameraPose {
+        let keys = &self.keyframes;
+        let last = keys.len() - 1;
+        // first keyframe after `time`, the segment ends there
+        let next = keys.partition_point(|k| k.time <= time);
+        let key = if next == 0 {
+            keys[0]
+        } else if next > last {
+            keys[last]
+        } else {
+            let (a, b) = (next - 1, next);
+            let span = keys[b].time - keys[a].time;
+            if span <= 0f64 {
+                keys[b]
+            } else {
+                let t = (time - keys[a].time) / span;
+                let spline = |f: fn(&Keyframe) -> DVec3| {
+                    hermite(
+                        f(&keys[a]),
+                        f(&keys[b]),
+                        self.tangent(a, f) * span,
+                        self.tangent(b, f) * span,
+                        t,
+                    )
+                };
+                let fov = hermite(
+                    keys[a].fov,
+                    keys[b].fov,
+                    self.tangent(a, |k| k.fov) * span,
+                    self.tangent(b, |k| k.fov) * span,
+                    t,
+                );
+                Keyframe {
+                    time,
+                    position: spline(|k| k.position),
+                    target: spline(|k| k.target),
+                    fov,
+                }
+            }
+        };
+        CameraPose {
+            camera: Camera::look_at(key.position, key.target, self.up),
+            fov: key.fov,
+        }
+    }
+
+    // `frame` out of `frame_count` frames spread evenly from the first
+    // keyframe to the last one, both included
+    pub fn sample_frame(&self, frame: usize, frame_count: usize) -> CameraPose {
+        if frame_count <= 1 {
+            return self.sample(self.start_time());
+        }
+        let t = frame as f64 / (frame_count - 1) as f64;
+        self.sample(self.start_time() + t * self.duration())
+    }
+
+    // Rate of change of a keyframe value around keyframe `i`, in units per
+    // second. It follows the neighbouring keyframes, one-sided at the ends.
+    fn tangent<T>(&self, i: usize, f: impl Fn(&Keyframe) -> T) -> T
+    where
+        T: Copy + Sub<Output = T> + Mul<f64, Output = T>,
+    {
+        let keys = &self.keyframes;
+        let a = i.saturating_sub(1);
+        let b = (i + 1).min(keys.len() - 1);
+        let span = keys[b].time - keys[a].time;
+        if span <= 0f64 {
+            return f(&keys[i]) * 0f64;
+        }
+        (f(&keys[b]) - f(&keys[a])) * (1f64 / span)
+    }
+}
+
+// Cubic Hermite interpolation from `p0` to `p1` with the given tangents,
+// already scaled to the length of the segment
+fn hermite<T>(p0: T, p1: T, m0: T, m1: T, t: f64) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f64, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p0 * (2f64 * t3 - 3f64 * t2 + 1f64)
+        + m0 * (t3 - 2f64 * t2 + t)
+        + p1 * (-2f64 * t3 + 3f64 * t2)
+        + m1 * (t3 - t2)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keyframe(time: f64, x: f64, fov: f64) -> Keyframe {
+        Keyframe {
+            time,
+            position: DVec3::new(x, 0f64, 5f64),
+            target: DVec3::new(x, 0f64, 0f64),
+            fov,
+        }
+    }
+
+    #[test]
+    fn test_passes_through_keyframes() {
+        let path = CameraPath::new(vec![
+            keyframe(2f64, 4f64, 60f64),
+            keyframe(0f64, 0f64, 90f64),
+            keyframe(1f64, 1f64, 80f64),
+        ]);
+        assert_eq!(path.start_time(), 0f64);
+        assert_eq!(path.duration(), 2f64);
+        for key in path.keyframes() {
+            let pose = path.sample(key.time);
+            assert!((pose.camera.position() - key.position).length() < 1e-9);
+            assert!((pose.fov - key.fov).abs() < 1e-9);
+            assert!((pose.camera.forward() - DVec3::NEG_Z).length() < 1e-9);
+        }
+
+        // held before the start and after the end
+        assert_eq!(path.sample(-1f64).camera.position().x, 0f64);
+        assert_eq!(path.sample(3f64).camera.position().x, 4f64);
+    }
+
+    #[test]
+    fn test_smooth_motion() {
+        // evenly spaced keyframes on a line are followed at constant speed
+        let path = CameraPath::new(vec![
+            keyframe(0f64, 0f64, 90f64),
+            keyframe(1f64, 1f64, 90f64),
+            keyframe(2f64, 2f64, 90f64),
+            keyframe(3f64, 3f64, 90f64),
+        ]);
+        for i in 0..=30 {
+            let time = i as f64 * 0.1;
+            let x = path.sample(time).camera.position().x;
+            if (0.9..=2.1).contains(&time) {
+                assert!((x - time).abs() < 1e-9, "{} at {}", x, time);
+            }
+        }
+
+        // the velocity is continuous across keyframes
+        let path = CameraPath::new(vec![
+            keyframe(0f64, 0f64, 90f64),
+            keyframe(1f64, 2f64, 90f64),
+            keyframe(3f64, 3f64, 90f64),
+        ]);
+        let x = |time| path.sample(time).camera.position().x;
+        let h = 1e-5;
+        let before = (x(1f64) - x(1f64 - h)) / h;
+        let after = (x(1f64 + h) - x(1f64)) / h;
+        assert!((before - after).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sample_frame() {
+        let path = CameraPath::new(vec![
+            keyframe(1f64, 0f64, 90f64),
+            keyframe(3f64, 2f64, 40f64),
+        ]);
+        assert_eq!(path.sample_frame(0, 5).fov, 90f64);
+        assert_eq!(path.sample_frame(4, 5).fov, 40f64);
+        assert!((path.sample_frame(2, 5).camera.position().x - 1f64).abs() < 1e-9);
+        assert_eq!(path.sample_frame(0, 1).fov, 90f64);
+    }
+}
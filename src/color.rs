@@ -2,6 +2,7 @@ use glam::DVec3;
 use image::Rgb;
 use std::default::Default;
 use std::fmt::Display;
+use std::ops::{Add, AddAssign, Mul};
 
 #[derive(Clone, Copy)]
 pub struct LinearRgbColor {
@@ -23,8 +24,13 @@ impl LinearRgbColor {
     }
 
     pub fn from_vec(v: &DVec3) -> LinearRgbColor {
-        LinearRgbColor { color: v.clone() }
+        LinearRgbColor { color: *v }
     }
+
+    pub fn to_vec(&self) -> DVec3 {
+        self.color
+    }
+
     pub fn r(&self) -> f64 {
         self.color[0]
     }
@@ -35,12 +41,17 @@ impl LinearRgbColor {
         self.color[2]
     }
 
+    // Perceived brightness, with the Rec. 709 weights
+    pub fn luminance(&self) -> f64 {
+        self.color.dot(DVec3::new(0.2126, 0.7152, 0.0722))
+    }
+
     pub fn attenute_mut(&mut self, scale: DVec3) {
         self.color *= scale;
     }
 
     pub fn attenute(&self, scale: DVec3) -> Self {
-        let mut copy = self.clone();
+        let mut copy = *self;
         copy.attenute_mut(scale);
         copy
     }
@@ -53,6 +64,32 @@ impl LinearRgbColor {
     }
 }
 
+impl Add for LinearRgbColor {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            color: self.color + rhs.color,
+        }
+    }
+}
+
+impl AddAssign for LinearRgbColor {
+    fn add_assign(&mut self, rhs: Self) {
+        self.color += rhs.color;
+    }
+}
+
+impl Mul<f64> for LinearRgbColor {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self {
+            color: self.color * rhs,
+        }
+    }
+}
+
 impl Display for LinearRgbColor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ri = (255.999 * f64::sqrt(self.r())).trunc() as i64;
@@ -90,6 +127,10 @@ impl ColorMixer for LinearMixer {
     }
 
     fn mix(&mut self) -> LinearRgbColor {
+        if self.total_color == 0 {
+            // e.g. pixels outside of the image circle of a fisheye
+            return LinearRgbColor::default();
+        }
         for i in 0..3 {
             self.color[i] /= self.total_color as f64;
         }
@@ -122,6 +163,9 @@ impl ColorMixer for RMSMixer {
     }
 
     fn mix(&mut self) -> LinearRgbColor {
+        if self.total_color == 0 {
+            return LinearRgbColor::default();
+        }
         for i in 0..3 {
             self.color[i] = (self.color[i] / self.total_color as f64).sqrt();
         }
@@ -139,13 +183,13 @@ impl Default for LinearRgbColor {
     }
 }
 
-impl Into<image::Rgb<u8>> for LinearRgbColor {
-    fn into(self) -> image::Rgb<u8> {
+impl From<LinearRgbColor> for image::Rgb<u8> {
+    fn from(color: LinearRgbColor) -> Self {
         // image::Rgb assumes sRGB color, use gamma = 2 here.
         Rgb([
-            (f64::sqrt(self.r()) * 255f64).trunc() as u8,
-            (f64::sqrt(self.g()) * 255f64).trunc() as u8,
-            (f64::sqrt(self.b()) * 255f64).trunc() as u8,
+            (f64::sqrt(color.r()) * 255f64).trunc() as u8,
+            (f64::sqrt(color.g()) * 255f64).trunc() as u8,
+            (f64::sqrt(color.b()) * 255f64).trunc() as u8,
         ])
     }
 }
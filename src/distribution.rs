@@ -0,0 +1,150 @@
+use glam::DVec2;
+
+// Piecewise-constant density over [0, 1), proportional to a tabulated
+// non-negative function. Sampled by inverting its cumulative distribution.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        assert!(n > 0, "cannot build a distribution over nothing");
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0f64);
+        for value in &func {
+            cdf.push(cdf.last().unwrap() + value.max(0f64) / n as f64);
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0f64 {
+                *c / integral
+            } else {
+                // nothing to go by, fall back to uniform
+                i as f64 / n as f64
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    // Average of the function over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Maps a uniform `u` in [0, 1) to (x, pdf, index of the segment of x)
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // last segment whose cdf starts at or below u
+        let index = self.cdf.partition_point(|&c| c <= u).clamp(1, self.len()) - 1;
+        let start = self.cdf[index];
+        let width = self.cdf[index + 1] - start;
+        let offset = if width > 0f64 {
+            (u - start) / width
+        } else {
+            0f64
+        };
+        let x = (index as f64 + offset) / self.len() as f64;
+        (x, self.segment_pdf(index), index)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        self.segment_pdf(self.index_of(x))
+    }
+
+    fn index_of(&self, x: f64) -> usize {
+        ((x * self.len() as f64) as usize).min(self.len() - 1)
+    }
+
+    fn segment_pdf(&self, index: usize) -> f64 {
+        if self.integral > 0f64 {
+            self.func[index].max(0f64) / self.integral
+        } else {
+            1f64
+        }
+    }
+}
+
+// Piecewise-constant density over [0, 1)^2, from a row-major table of
+// `width` x `height` values. Picks a row from the marginal density first,
+// then a column inside of it.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let rows: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+        Self { rows, marginal }
+    }
+
+    // Maps a uniform point in [0, 1)^2 to a point and its density
+    pub fn sample(&self, u: DVec2) -> (DVec2, f64) {
+        let (y, row_pdf, row) = self.marginal.sample(u.y);
+        let (x, column_pdf, _) = self.rows[row].sample(u.x);
+        (DVec2::new(x, y), row_pdf * column_pdf)
+    }
+
+    pub fn pdf(&self, p: DVec2) -> f64 {
+        let row = self.marginal.index_of(p.y);
+        self.marginal.segment_pdf(row) * self.rows[row].pdf(p.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_1d() {
+        let dist = Distribution1D::new(vec![1f64, 3f64, 0f64, 4f64]);
+        assert_eq!(dist.integral(), 2f64);
+        // the cdf is [0, 1/8, 1/2, 1/2, 1]
+        let (x, pdf, index) = dist.sample(0.0625);
+        assert_eq!((x, pdf, index), (0.125, 0.5, 0));
+        let (x, pdf, index) = dist.sample(0.3125);
+        assert_eq!((x, pdf, index), (0.375, 1.5, 1));
+        // the empty segment is skipped
+        let (x, pdf, index) = dist.sample(0.5);
+        assert_eq!((x, pdf, index), (0.75, 2f64, 3));
+        assert_eq!(dist.pdf(0.6), 0f64);
+    }
+
+    #[test]
+    fn test_all_zero_is_uniform() {
+        let dist = Distribution1D::new(vec![0f64; 4]);
+        let (x, pdf, _) = dist.sample(0.3);
+        assert!((x - 0.3).abs() < 1e-12);
+        assert_eq!(pdf, 1f64);
+    }
+
+    #[test]
+    fn test_sample_2d() {
+        // all the mass is in the bottom right cell
+        let dist = Distribution2D::new(&[0f64, 0f64, 0f64, 1f64], 2, 2);
+        let (p, pdf) = dist.sample(DVec2::new(0.5, 0.5));
+        assert_eq!(p, DVec2::new(0.75, 0.75));
+        assert_eq!(pdf, 4f64);
+        assert_eq!(dist.pdf(p), pdf);
+        assert_eq!(dist.pdf(DVec2::new(0.25, 0.75)), 0f64);
+    }
+}
@@ -0,0 +1,157 @@
+// Integrators visualizing a single property of the scene instead of its
+// lighting, for inspecting geometry and materials quickly.
+use glam::DVec3;
+
+use super::lights::EPS;
+use super::Integrator;
+use crate::color::LinearRgbColor;
+use crate::ray::Ray;
+use crate::utils::Interval;
+use crate::world::Scene;
+
+// Surface normals facing the camera, mapped from [-1, 1] to [0, 1]
+#[derive(Default)]
+pub struct NormalIntegrator {}
+
+impl NormalIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for NormalIntegrator {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        match world.hit(ray, &Interval::greater_than(EPS)) {
+            Some(hit) => LinearRgbColor::from_vec(&((hit.normal + DVec3::ONE) * 0.5)),
+            None => LinearRgbColor::default(),
+        }
+    }
+}
+
+// Distance to the first hit, white at the camera fading to black at
+// `max_distance` and beyond
+pub struct DepthIntegrator {
+    max_distance: f64,
+}
+
+impl DepthIntegrator {
+    pub fn new(max_distance: f64) -> Self {
+        Self { max_distance }
+    }
+}
+
+impl Integrator for DepthIntegrator {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        match world.hit(ray, &Interval::greater_than(EPS)) {
+            Some(hit) => {
+                let distance = hit.t * ray.direction.length();
+                let shade = Interval::new(0f64, 1f64).clamp(1f64 - distance / self.max_distance);
+                LinearRgbColor::from_vec(&DVec3::splat(shade))
+            }
+            None => LinearRgbColor::default(),
+        }
+    }
+}
+
+// Color of the first surface hit: the attenuation of its scattered ray, or
+// its emission for lights
+#[derive(Default)]
+pub struct AlbedoIntegrator {}
+
+impl AlbedoIntegrator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Integrator for AlbedoIntegrator {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        let Some(hit) = world.hit(ray, &Interval::greater_than(EPS)) else {
+            return world.miss(ray);
+        };
+        if hit.mat.is_emissive() {
+            return hit.mat.emitted(&hit);
+        }
+        match hit.mat.scatter(ray, &hit) {
+            Some(scatter) => LinearRgbColor::from_vec(&scatter.attenuation_factor),
+            None => LinearRgbColor::default(),
+        }
+    }
+}
+
+// Number of surfaces the ray passes through, white once it reaches `max_hits`
+pub struct HitCountIntegrator {
+    max_hits: u32,
+}
+
+impl HitCountIntegrator {
+    pub fn new(max_hits: u32) -> Self {
+        Self { max_hits }
+    }
+
+    pub fn count(&self, ray: &Ray, world: &dyn Scene) -> u32 {
+        let mut count = 0;
+        let mut t = 0f64;
+        while count < self.max_hits {
+            match world.hit(ray, &Interval::greater_than(t + EPS)) {
+                Some(hit) => {
+                    count += 1;
+                    t = hit.t;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+}
+
+impl Default for HitCountIntegrator {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl Integrator for HitCountIntegrator {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        let shade = self.count(ray, world) as f64 / self.max_hits as f64;
+        LinearRgbColor::from_vec(&DVec3::splat(shade))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::world::{Intersectable, LerpScene, Sphere, VecContainer};
+
+    fn two_spheres() -> LerpScene<VecContainer> {
+        let material = DummyMaterial::new_shared();
+        let container: VecContainer = vec![
+            Sphere::new(DVec3::new(0f64, 0f64, -3f64), 1f64, &material).into_box(),
+            Sphere::new(DVec3::new(0f64, 0f64, -6f64), 1f64, &material).into_box(),
+        ];
+        let black = LinearRgbColor::default();
+        LerpScene::new(container, black, black)
+    }
+
+    #[test]
+    fn test_hit_count() {
+        let world = two_spheres();
+        let ray = Ray::new(DVec3::ZERO, DVec3::NEG_Z);
+        assert_eq!(HitCountIntegrator::default().count(&ray, &world), 4);
+        assert_eq!(HitCountIntegrator::new(3).count(&ray, &world), 3);
+        let ray = Ray::new(DVec3::ZERO, DVec3::Y);
+        assert_eq!(HitCountIntegrator::default().count(&ray, &world), 0);
+    }
+
+    #[test]
+    fn test_depth_and_normal() {
+        let world = two_spheres();
+        // the ray direction does not need to be normalized
+        let ray = Ray::new(DVec3::ZERO, DVec3::new(0f64, 0f64, -2f64));
+        let depth = DepthIntegrator::new(4f64).radiance(&ray, &world);
+        assert_eq!(depth.to_vec(), DVec3::splat(0.5));
+        let normal = NormalIntegrator::new().radiance(&ray, &world);
+        assert_eq!(normal.to_vec(), DVec3::new(0.5, 0.5, 1f64));
+    }
+}
@@ -0,0 +1,8 @@
+use crate::color::LinearRgbColor;
+use crate::ray::Ray;
+use crate::world::Scene;
+
+// Light transport algorithm, computing the color seen along camera rays
+pub trait Integrator: Sync {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor;
+}
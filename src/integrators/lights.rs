@@ -0,0 +1,135 @@
+use glam::DVec3;
+
+use crate::color::LinearRgbColor;
+use crate::ray::Ray;
+use crate::utils::Interval;
+use crate::world::{EnvironmentLight, IntersectRecord, LightSample, Scene, SharedLight};
+
+// add a small eps to fix shadow acne
+pub const EPS: f64 = 0.001;
+
+pub struct DirectSample {
+    // emitted radiance times the BSDF (including the cosine term)
+    pub radiance: LinearRgbColor,
+    // densities of the sampled direction, for the sampled light alone and for
+    // the material scattering `ray`
+    pub light_pdf: f64,
+    pub bsdf_pdf: f64,
+}
+
+// Everything that can be sampled directly: the emissive shapes, and the
+// environment when there is one
+pub fn light_count(world: &dyn Scene) -> usize {
+    world.lights().len() + usize::from(world.environment_light().is_some())
+}
+
+// Samples one of the `light_count` lights. The environment comes last.
+pub fn sample_light_at(
+    world: &dyn Scene,
+    ray: &Ray,
+    hit: &IntersectRecord,
+    index: usize,
+) -> Option<DirectSample> {
+    match world.lights().get(index) {
+        Some(light) => sample_light(world, ray, hit, light),
+        None => sample_environment(world, ray, hit, world.environment_light()?),
+    }
+}
+
+// Samples the light arriving at `hit` straight from `light`, through a shadow
+// ray. None if the sample is occluded or carries no light.
+pub fn sample_light(
+    world: &dyn Scene,
+    ray: &Ray,
+    hit: &IntersectRecord,
+    light: &SharedLight,
+) -> Option<DirectSample> {
+    let sample = light.sample(hit.point)?;
+    let bsdf = eval_towards(ray, hit, &sample)?;
+    let shadow_ray = Ray::new(hit.point, sample.direction).with_time(ray.time);
+    let light_rec = light.hit(&shadow_ray, &Interval::greater_than(EPS))?;
+    if world
+        .hit(&shadow_ray, &Interval::new(EPS, light_rec.t - EPS))
+        .is_some()
+    {
+        // occluded
+        return None;
+    }
+    Some(DirectSample {
+        radiance: light_rec.mat.emitted(&light_rec).attenute(bsdf),
+        light_pdf: sample.pdf,
+        bsdf_pdf: hit.mat.pdf(ray, hit, sample.direction),
+    })
+}
+
+// Same as `sample_light`, for the light coming from the environment, which
+// reaches `hit` only if nothing at all is in the way
+pub fn sample_environment(
+    world: &dyn Scene,
+    ray: &Ray,
+    hit: &IntersectRecord,
+    environment: &dyn EnvironmentLight,
+) -> Option<DirectSample> {
+    let sample = environment.sample()?;
+    let bsdf = eval_towards(ray, hit, &sample)?;
+    let shadow_ray = Ray::new(hit.point, sample.direction).with_time(ray.time);
+    if world
+        .hit(&shadow_ray, &Interval::greater_than(EPS))
+        .is_some()
+    {
+        return None;
+    }
+    Some(DirectSample {
+        radiance: environment.radiance(sample.direction).attenute(bsdf),
+        light_pdf: sample.pdf,
+        bsdf_pdf: hit.mat.pdf(ray, hit, sample.direction),
+    })
+}
+
+// The BSDF of `hit` for light arriving along the sample, None when the
+// surface does not reflect any of it. Points in a volume have no surface to
+// be behind of.
+fn eval_towards(ray: &Ray, hit: &IntersectRecord, sample: &LightSample) -> Option<DVec3> {
+    let behind = !hit.mat.is_volumetric() && hit.normal.dot(sample.direction) <= 0f64;
+    if behind || sample.pdf <= 0f64 {
+        return None;
+    }
+    let bsdf = hit.mat.eval(ray, hit, sample.direction);
+    (bsdf != DVec3::ZERO).then_some(bsdf)
+}
+
+// Density of picking one of the lights uniformly and sampling `ray` from it,
+// given the ray hits the scene first at `t`. Zero if the surface there is not
+// one of the lights.
+pub fn light_pdf(world: &dyn Scene, ray: &Ray, t: f64) -> f64 {
+    let range = Interval::new(EPS, t + EPS);
+    world
+        .lights()
+        .iter()
+        .find(|light| {
+            light
+                .hit(ray, &range)
+                .is_some_and(|rec| (rec.t - t).abs() <= EPS)
+        })
+        .map_or(0f64, |light| {
+            light.pdf(ray.origin, ray.direction) / light_count(world) as f64
+        })
+}
+
+// Same as `light_pdf`, for a ray escaping the scene
+pub fn environment_pdf(world: &dyn Scene, ray: &Ray) -> f64 {
+    world.environment_light().map_or(0f64, |environment| {
+        environment.pdf(ray.direction) / light_count(world) as f64
+    })
+}
+
+// Weight of a sample drawn with density `pdf` when the other strategy would
+// have drawn it with `other_pdf`
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a.is_infinite() {
+        1f64
+    } else {
+        a / (a + b)
+    }
+}
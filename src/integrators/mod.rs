@@ -0,0 +1,9 @@
+pub mod debug;
+pub mod integrator;
+mod lights;
+pub mod path;
+pub mod whitted;
+pub use debug::{AlbedoIntegrator, DepthIntegrator, HitCountIntegrator, NormalIntegrator};
+pub use integrator::Integrator;
+pub use path::PathTracer;
+pub use whitted::WhittedTracer;
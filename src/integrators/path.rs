@@ -0,0 +1,314 @@
+use glam::DVec3;
+use rand::{random, thread_rng, Rng};
+
+use super::lights::{
+    environment_pdf, light_count, light_pdf, power_heuristic, sample_light_at, EPS,
+};
+use super::Integrator;
+use crate::color::LinearRgbColor;
+use crate::ray::Ray;
+use crate::utils::Interval;
+use crate::world::{IntersectRecord, Scene};
+
+// Unbiased Monte Carlo path tracer. Surfaces that are not perfectly specular
+// also sample the lights, combined with the BSDF samples by multiple
+// importance sampling. Past `min_depth` bounces, paths are terminated by
+// Russian roulette, with a probability following their throughput.
+pub struct PathTracer {
+    min_depth: u32,
+    // hard limit on the bounces, biased as the light of longer paths is lost
+    max_depth: Option<u32>,
+}
+
+impl PathTracer {
+    pub fn new() -> Self {
+        Self {
+            min_depth: 3,
+            max_depth: None,
+        }
+    }
+
+    pub fn with_min_depth(mut self, min_depth: u32) -> Self {
+        self.min_depth = min_depth;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    // Next-event estimation: light arriving at the surface straight from one
+    // randomly chosen light
+    fn direct_light(world: &dyn Scene, ray: &Ray, hit: &IntersectRecord) -> LinearRgbColor {
+        let count = light_count(world);
+        match sample_light_at(world, ray, hit, thread_rng().gen_range(0..count)) {
+            Some(sample) => {
+                // one light out of N was sampled
+                let pdf = sample.light_pdf / count as f64;
+                sample.radiance * (power_heuristic(pdf, sample.bsdf_pdf) / pdf)
+            }
+            None => LinearRgbColor::default(),
+        }
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Russian roulette never lets a path survive with certainty, so that paths
+// trapped without losing energy (e.g. inside of glass) still end
+const MAX_SURVIVAL: f64 = 0.95;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        let mut color = LinearRgbColor::default();
+        let mut ray = Ray::new(ray.origin, ray.direction).with_time(ray.time);
+        // fraction of the light arriving along `ray` that reaches the camera
+        let mut throughput = DVec3::ONE;
+        // density the previous bounce sampled `ray` with, when it also sampled
+        // the lights. Light hit by such a ray is weighted against the light
+        // sample by multiple importance sampling.
+        let mut bsdf_pdf: Option<f64> = None;
+        let mut depth = 0;
+        while self.max_depth.is_none_or(|max_depth| depth < max_depth) {
+            let Some(hit_rec) = world.hit(&ray, &Interval::greater_than(EPS)) else {
+                // miss, background color
+                let mut background = world.miss(&ray);
+                if let Some(bsdf_pdf) = bsdf_pdf {
+                    background =
+                        background * power_heuristic(bsdf_pdf, environment_pdf(world, &ray));
+                }
+                color += background.attenute(throughput);
+                break;
+            };
+            let mut emitted = hit_rec.mat.emitted(&hit_rec);
+            if let Some(bsdf_pdf) = bsdf_pdf.filter(|_| hit_rec.mat.is_emissive()) {
+                emitted = emitted * power_heuristic(bsdf_pdf, light_pdf(world, &ray, hit_rec.t));
+            }
+            color += emitted.attenute(throughput);
+
+            let Some(scatter_rec) = hit_rec.mat.scatter(&ray, &hit_rec) else {
+                // absorbed
+                break;
+            };
+            let direction = scatter_rec.scattered.direction.normalize();
+            let pdf = hit_rec.mat.pdf(&ray, &hit_rec, direction);
+            let sample_lights = pdf > 0f64 && light_count(world) > 0;
+            if sample_lights {
+                color += Self::direct_light(world, &ray, &hit_rec).attenute(throughput);
+            }
+            throughput *= scatter_rec.attenuation_factor;
+            depth += 1;
+
+            if depth >= self.min_depth {
+                let survival = throughput.max_element().min(MAX_SURVIVAL);
+                if random::<f64>() >= survival {
+                    break;
+                }
+                // the surviving paths make up for the terminated ones
+                throughput /= survival;
+            }
+            bsdf_pdf = sample_lights.then_some(pdf);
+            ray = scatter_rec.scattered;
+        }
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{
+        DiffuseLight, HenyeyGreensteinPhase, IsotropicPhase, LambertianMaterial, Material,
+    };
+    use crate::world::{
+        ConstantMedium, EnvMapScene, EnvironmentMap, Intersectable, LerpScene, Rectangle, Sphere,
+        VecContainer,
+    };
+    use glam::DQuat;
+    use image::{Rgb, Rgb32FImage};
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_emission_reaches_camera() {
+        let light = DiffuseLight::make_shared(DiffuseLight::new(
+            LinearRgbColor::new(1f64, 0.5, 0.25),
+            2f64,
+        ));
+        // the camera sits inside of a glowing sphere
+        let container: VecContainer = vec![Sphere::new(DVec3::ZERO, 10f64, &light).into_box()];
+        let black = LinearRgbColor::default();
+        let world = LerpScene::new(container, black, black);
+
+        let ray = Ray::new(DVec3::ZERO, DVec3::new(0.3, -0.2, 1f64));
+        let color = PathTracer::default().radiance(&ray, &world);
+        assert_eq!(color.to_vec(), DVec3::new(2f64, 1f64, 0.5));
+    }
+
+    #[test]
+    fn test_mis_under_uniform_light() {
+        let light = DiffuseLight::make_shared(DiffuseLight::new(
+            LinearRgbColor::new(1f64, 1f64, 1f64),
+            1f64,
+        ));
+        let white = LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::splat(0.5)));
+        // a diffuse card inside of a glowing sphere reflects its albedo
+        let sphere = Arc::new(Sphere::new(DVec3::ZERO, 10f64, &light));
+        let card = Rectangle::new(DVec3::ZERO, DQuat::IDENTITY, 1f64, 1f64, &white);
+        let container: VecContainer = vec![sphere.clone().into_box(), card.into_box()];
+        let black = LinearRgbColor::default();
+        let world = LerpScene::new(container, black, black).with_lights(vec![sphere]);
+
+        let ray = Ray::new(DVec3::Y, DVec3::NEG_Y);
+        mean_radiance(&PathTracer::new().with_max_depth(2), &ray, &world)
+            .assert_near(DVec3::splat(0.5));
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        let light = DiffuseLight::make_shared(DiffuseLight::new(
+            LinearRgbColor::new(1f64, 1f64, 1f64),
+            1f64,
+        ));
+        let white = LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::splat(0.5)));
+        // light bouncing between two facing cards, open to a glowing sphere
+        let sphere = Arc::new(Sphere::new(DVec3::ZERO, 10f64, &light));
+        let bottom = Rectangle::new(DVec3::ZERO, DQuat::IDENTITY, 1f64, 1f64, &white);
+        let top = Rectangle::new(
+            DVec3::new(0f64, 0.2, 0f64),
+            DQuat::from_rotation_x(PI),
+            1f64,
+            1f64,
+            &white,
+        );
+        let container: VecContainer =
+            vec![sphere.clone().into_box(), bottom.into_box(), top.into_box()];
+        let black = LinearRgbColor::default();
+        let world = LerpScene::new(container, black, black).with_lights(vec![sphere]);
+
+        let ray = Ray::new(DVec3::new(0f64, 0.1, 0f64), DVec3::new(0.1, -1f64, 0f64));
+        // no path is cut short, every bounce is subject to the roulette
+        let reference = mean_radiance(&PathTracer::new().with_min_depth(64), &ray, &world);
+        let roulette = mean_radiance(&PathTracer::new().with_min_depth(0), &ray, &world);
+        roulette.assert_near_estimate(&reference);
+    }
+
+    #[test]
+    fn test_medium_furnace() {
+        // Inside of a glowing sphere, a medium that scatters without
+        // absorbing anything changes where the light comes from but not how
+        // much of it there is, whichever way it scatters. Light sampled from
+        // the volume has to get through the rest of it.
+        let light = DiffuseLight::make_shared(DiffuseLight::new(
+            LinearRgbColor::new(1f64, 1f64, 1f64),
+            1f64,
+        ));
+        let sphere = Arc::new(Sphere::new(DVec3::ZERO, 10f64, &light));
+        let black = LinearRgbColor::default();
+        let ray = Ray::new(DVec3::new(0f64, 0f64, -5f64), DVec3::Z);
+        let phases = [
+            IsotropicPhase::make_shared(IsotropicPhase::new(DVec3::ONE)),
+            HenyeyGreensteinPhase::make_shared(HenyeyGreensteinPhase::new(DVec3::ONE, 0.7)),
+        ];
+        for phase in phases {
+            let boundary = Sphere::new(DVec3::ZERO, 2f64, &phase);
+            let fog = ConstantMedium::new(boundary, 1f64, &phase);
+            let container: VecContainer = vec![sphere.clone().into_box(), fog.into_box()];
+            let world = LerpScene::new(container, black, black).with_lights(vec![sphere.clone()]);
+            mean_radiance(&PathTracer::new(), &ray, &world).assert_near(DVec3::ONE);
+        }
+    }
+
+    #[test]
+    fn test_medium_absorbs() {
+        // black smoke lets through only the light that never scatters
+        let light = DiffuseLight::make_shared(DiffuseLight::new(
+            LinearRgbColor::new(1f64, 1f64, 1f64),
+            1f64,
+        ));
+        let smoke = IsotropicPhase::make_shared(IsotropicPhase::new(DVec3::ZERO));
+        let sphere = Arc::new(Sphere::new(DVec3::ZERO, 10f64, &light));
+        let boundary = Sphere::new(DVec3::ZERO, 1f64, &smoke);
+        let container: VecContainer = vec![
+            sphere.clone().into_box(),
+            ConstantMedium::new(boundary, 0.5, &smoke).into_box(),
+        ];
+        let black = LinearRgbColor::default();
+        let world = LerpScene::new(container, black, black).with_lights(vec![sphere]);
+
+        let ray = Ray::new(DVec3::new(0f64, 0f64, -5f64), DVec3::Z);
+        let expected = (-0.5f64 * 2f64).exp();
+        mean_radiance(&PathTracer::new(), &ray, &world).assert_near(DVec3::splat(expected));
+    }
+
+    // Mean of many samples of the radiance, together with its standard error
+    struct Estimate {
+        mean: DVec3,
+        error: DVec3,
+    }
+
+    // Standard errors a correct estimate may be off by. The samples are not
+    // seeded, and a fixed bound fails once in a while, however loose.
+    const MAX_ERRORS: f64 = 5f64;
+
+    impl Estimate {
+        fn assert_near(&self, expected: DVec3) {
+            let off = (self.mean - expected).abs();
+            assert!(
+                off.cmple(self.error * MAX_ERRORS).all(),
+                "{} != {expected}, error {}",
+                self.mean,
+                self.error
+            );
+        }
+
+        fn assert_near_estimate(&self, other: &Estimate) {
+            let off = (self.mean - other.mean).abs();
+            let error = (self.error * self.error + other.error * other.error).powf(0.5);
+            assert!(
+                off.cmple(error * MAX_ERRORS).all(),
+                "{} != {}, error {}",
+                self.mean,
+                other.mean,
+                error
+            );
+        }
+    }
+
+    fn mean_radiance(integrator: &PathTracer, ray: &Ray, world: &dyn Scene) -> Estimate {
+        let n = 50000;
+        let mut total = DVec3::ZERO;
+        let mut total_squared = DVec3::ZERO;
+        for _ in 0..n {
+            let sample = integrator.radiance(ray, world).to_vec();
+            total += sample;
+            total_squared += sample * sample;
+        }
+        let mean = total / n as f64;
+        let variance = (total_squared / n as f64 - mean * mean).max(DVec3::ZERO);
+        Estimate {
+            mean,
+            // plus a little for estimates without any noise
+            error: (variance / n as f64).powf(0.5) + DVec3::splat(1e-9),
+        }
+    }
+
+    #[test]
+    fn test_environment_lighting() {
+        let white = LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::splat(0.5)));
+        let card = Rectangle::new(DVec3::ZERO, DQuat::IDENTITY, 1f64, 1f64, &white);
+        let container: VecContainer = vec![card.into_box()];
+        // uniformly white all around
+        let image = Rgb32FImage::from_pixel(8, 4, Rgb([1f32, 1f32, 1f32]));
+        let world = EnvMapScene::new(container, EnvironmentMap::new(image));
+
+        let ray = Ray::new(DVec3::Y, DVec3::NEG_Y);
+        mean_radiance(&PathTracer::new().with_max_depth(2), &ray, &world)
+            .assert_near(DVec3::splat(0.5));
+    }
+}
@@ -0,0 +1,56 @@
+use super::lights::{light_count, sample_light_at, EPS};
+use super::Integrator;
+use crate::color::LinearRgbColor;
+use crate::ray::Ray;
+use crate::utils::Interval;
+use crate::world::Scene;
+
+// Classic recursive ray tracing: perfectly specular surfaces (mirrors, glass)
+// are followed, every other surface only gets the direct light of the scene's
+// lights and environment, one shadow ray per light. Much faster to converge
+// than path tracing, but without any indirect lighting.
+pub struct WhittedTracer {
+    max_depth: u32,
+}
+
+impl WhittedTracer {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+
+    fn ray_color(ray: &Ray, world: &dyn Scene, depth: u32) -> LinearRgbColor {
+        if depth == 0 {
+            return LinearRgbColor::default();
+        }
+        let Some(hit_rec) = world.hit(ray, &Interval::greater_than(EPS)) else {
+            return world.miss(ray);
+        };
+        let mut color = hit_rec.mat.emitted(&hit_rec);
+        if let Some(scatter_rec) = hit_rec.mat.scatter(ray, &hit_rec) {
+            let direction = scatter_rec.scattered.direction.normalize();
+            if hit_rec.mat.pdf(ray, &hit_rec, direction) > 0f64 {
+                for index in 0..light_count(world) {
+                    if let Some(sample) = sample_light_at(world, ray, &hit_rec, index) {
+                        color += sample.radiance * (1f64 / sample.light_pdf);
+                    }
+                }
+            } else {
+                color += Self::ray_color(&scatter_rec.scattered, world, depth - 1)
+                    .attenute(scatter_rec.attenuation_factor);
+            }
+        }
+        color
+    }
+}
+
+impl Default for WhittedTracer {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl Integrator for WhittedTracer {
+    fn radiance(&self, ray: &Ray, world: &dyn Scene) -> LinearRgbColor {
+        Self::ray_color(ray, world, self.max_depth)
+    }
+}
@@ -1,10 +1,15 @@
 #![allow(dead_code)]
+pub mod animation;
 pub mod camera;
+pub mod camera_path;
 pub mod color;
-mod materials;
+pub mod distribution;
+pub mod integrators;
+pub mod materials;
 pub mod output;
 pub mod ray;
 pub mod render_spec;
+pub mod scene_file;
 #[cfg(test)]
 pub mod test_utils;
 pub mod utils;
@@ -1,90 +1,637 @@
-#![allow(dead_code)]
-mod camera;
-mod color;
-mod materials;
-mod output;
-mod ray;
-mod render_spec;
-#[cfg(test)]
-mod test_utils;
-mod utils;
-mod world;
-
-use crate::camera::Camera;
-use crate::color::LinearMixer;
-use crate::materials::{
-    DielectricMaterial, LambertianMaterial, Material, MetalMaterial, SimpleDiffuseMaterial,
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use glam::EulerRot;
+use image::{ImageBuffer, ImageFormat, Rgb, RgbImage};
+use raytrace_cli::animation::render_animation;
+use raytrace_cli::color::{ColorMixer, LinearMixer, RMSMixer};
+use raytrace_cli::integrators::{
+    AlbedoIntegrator, DepthIntegrator, HitCountIntegrator, Integrator, NormalIntegrator,
+    PathTracer, WhittedTracer,
 };
-use crate::output::{ImageFormatsSaver, ImageSaver};
-use crate::render_spec::{ImageSize, PinHoleSpec};
-use crate::world::{InfinitePlane, Intersectable, Rectangle, Sphere, VecContainer};
-use color::LinearRgbColor;
-use glam::{DQuat, DVec3, EulerRot};
-use world::LerpScene;
-
-fn main() {
-    let spp = 500;
-    let fov = 135f64;
-
-    let spec = PinHoleSpec::new(
-        spp,
-        fov,
-        ImageSize {
-            width: 300,
-            height: 200,
-        },
-    );
+use raytrace_cli::output::{
+    frame_path, AnimationFormat, AnimationSaver, AsciiArtSaver, ImageFormatsSaver, ImageSaver,
+};
+use raytrace_cli::scene_file::description::ProjectionDescription;
+use raytrace_cli::scene_file::{load_scene, validate_render, LoadedScene};
 
-    let camera = Camera::new(
-        DVec3::Z * 0.3,
-        DQuat::from_euler(glam::EulerRot::XYZ, 15f64.to_radians(), 0f64, 0f64),
-    );
+#[derive(Parser)]
+#[command(version, about = "Ray-tracing, but in CLI.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    // materials
-    let simple = SimpleDiffuseMaterial::make_shared(SimpleDiffuseMaterial::new());
-    let lambertian =
-        LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::new(0.2, 0.8, 0.1)));
-    let metal = MetalMaterial::make_shared(MetalMaterial::new(DVec3::splat(0.9), 0f64));
-    let metal_fuzz = MetalMaterial::make_shared(MetalMaterial::new(DVec3::new(0.8, 0.6, 0.2), 0.6));
-    let dielectric = DielectricMaterial::make_shared(DielectricMaterial::new(1.5));
-
-    // objects
-    let s1 = Sphere::new(DVec3::new(0f64, 0f64, -1f64), 0.5, &lambertian);
-    let s2 = Sphere::new(DVec3::new(-1f64, 0f64, -1f64), 0.5, &dielectric);
-    let s3 = Sphere::new(DVec3::new(1f64, 0f64, -1f64), 0.5, &metal_fuzz);
-    let s4 = Sphere::new(DVec3::new(0f64, 1f64, -1f64), 0.5, &metal);
-    //let gnd = Sphere::new(DVec3::new(0f64, -100.5, -1f64), 100f64, &simple);
-    let gnd = InfinitePlane::new(
-        DVec3::new(0f64, -1.5, 0f64),
-        DVec3::new(0f64, 1f64, 0f64),
-        &simple,
-    );
+#[derive(Subcommand)]
+enum Command {
+    /// Render a scene to an image file
+    Render {
+        #[command(flatten)]
+        render: RenderArgs,
 
-    let mirror = Rectangle::new(
-        DVec3::new(-1f64, 1.5, -1f64),
-        DQuat::from_euler(EulerRot::XYZ, 0f64, 0f64, -135f64.to_radians()),
-        2.5f64,
-        2.5f64,
-        &simple,
-    );
+        /// Output image path
+        #[arg(short, long, default_value = "output.png")]
+        output: PathBuf,
+
+        /// Output image format, deduced from the output extension if omitted
+        #[arg(long, value_parser = parse_image_format)]
+        format: Option<ImageFormat>,
+    },
+    /// Render a scene to a text file as ASCII art
+    Ascii {
+        #[command(flatten)]
+        render: RenderArgs,
+
+        /// Output text file path
+        #[arg(short, long, default_value = "output.txt")]
+        output: PathBuf,
+
+        /// Font used to match image blocks with characters
+        #[arg(long)]
+        font: PathBuf,
+    },
+    /// Render the frames of an animated scene, and optionally encode them
+    /// into an animated GIF or PNG
+    Animate {
+        #[command(flatten)]
+        render: RenderArgs,
+
+        /// Frame paths, the last run of `#` is replaced by the frame number
+        #[arg(short, long, default_value = "frames/frame_####.png")]
+        output: PathBuf,
+
+        /// Animated GIF or PNG to also encode the frames into
+        #[arg(long)]
+        animation: Option<PathBuf>,
+
+        /// Number of frames to render
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        frames: Option<u32>,
+
+        /// Frames per second
+        #[arg(long, value_parser = parse_positive)]
+        fps: Option<f64>,
+    },
+    /// Print a summary of a scene file without rendering it
+    Info {
+        /// Scene description file
+        scene: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Scene description file
+    scene: PathBuf,
+
+    /// Image resolution, e.g. 300x200
+    #[arg(short, long)]
+    resolution: Option<Resolution>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+
+    /// Diagonal field of view, in degrees
+    #[arg(long, value_parser = parse_fov)]
+    fov: Option<f64>,
+
+    /// Camera projection, with default settings unless the scene file
+    /// already uses the same one
+    #[arg(long, value_enum)]
+    projection: Option<Projection>,
+
+    /// Lens radius, enables depth of field when positive
+    #[arg(long, value_parser = parse_non_negative)]
+    aperture: Option<f64>,
+
+    /// Distance to the plane in focus
+    #[arg(long, value_parser = parse_positive, conflicts_with = "autofocus")]
+    focus_distance: Option<f64>,
 
-    // world
-    let container = VecContainer::from_iter(vec![
-        s1.into_box(),
-        s2.into_box(),
-        s3.into_box(),
-        s4.into_box(),
-        gnd.into_box(),
-        mirror.into_box(),
-    ]);
-
-    let world = LerpScene::new(
-        container,
-        LinearRgbColor::new(1f64, 1f64, 1f64),
-        LinearRgbColor::new(0.5f64, 0.7f64, 1.0f64),
+    /// Focus on what is seen through the given pixel, e.g. 150,100
+    #[arg(long)]
+    autofocus: Option<Pixel>,
+
+    /// How long the shutter stays open, in seconds, for motion blur
+    #[arg(long, value_parser = parse_non_negative)]
+    shutter: Option<f64>,
+
+    /// Light transport algorithm
+    #[arg(long, value_enum, default_value_t = IntegratorKind::Path)]
+    integrator: IntegratorKind,
+
+    /// Bounces before the path integrator starts terminating paths randomly.
+    /// Only valid with the path integrator.
+    #[arg(long)]
+    min_depth: Option<u32>,
+
+    /// Maximum number of bounces per ray, or of surfaces counted by hit-count.
+    /// Unlimited for the path integrator unless given.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_depth: Option<u32>,
+
+    /// How the samples of a pixel are combined
+    #[arg(long, value_enum, default_value_t = Mixer::Linear)]
+    mixer: Mixer,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mixer {
+    /// Arithmetic mean of the samples
+    Linear,
+    /// Root mean square of the samples
+    Rms,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Projection {
+    Perspective,
+    Orthographic,
+    /// 360 degrees panorama
+    Equirectangular,
+    Fisheye,
+}
+
+impl Projection {
+    fn of(description: &ProjectionDescription) -> Self {
+        match description {
+            ProjectionDescription::Perspective => Self::Perspective,
+            ProjectionDescription::Orthographic { .. } => Self::Orthographic,
+            ProjectionDescription::Equirectangular => Self::Equirectangular,
+            ProjectionDescription::Fisheye { .. } => Self::Fisheye,
+        }
+    }
+
+    fn description(self) -> ProjectionDescription {
+        match self {
+            Self::Perspective => ProjectionDescription::Perspective,
+            Self::Orthographic => ProjectionDescription::orthographic(),
+            Self::Equirectangular => ProjectionDescription::Equirectangular,
+            Self::Fisheye => ProjectionDescription::fisheye(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IntegratorKind {
+    /// Path tracing with global illumination
+    Path,
+    /// Direct light and perfect reflections/refractions only
+    Whitted,
+    /// Surface normals of the first hit
+    Normals,
+    /// Distance to the first hit
+    Depth,
+    /// Color of the first surface hit
+    Albedo,
+    /// Number of surfaces along the camera rays
+    HitCount,
+}
+
+#[derive(Clone, Copy)]
+struct Resolution {
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{}`", s))?;
+        let parse = |v: &str| match v.trim().parse::<u32>() {
+            Ok(v) if v > 0 => Ok(v),
+            _ => Err(format!("`{}` is not a positive integer", v)),
+        };
+        Ok(Self {
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Pixel {
+    x: u32,
+    y: u32,
+}
+
+impl FromStr for Pixel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected X,Y, got `{}`", s))?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("`{}` is not a pixel coordinate", v))
+        };
+        Ok(Self {
+            x: parse(x)?,
+            y: parse(y)?,
+        })
+    }
+}
+
+fn parse_non_negative(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v >= 0f64 => Ok(v),
+        _ => Err(format!("`{}` is not a non-negative number", s)),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0f64 => Ok(v),
+        _ => Err(format!("`{}` is not a positive number", s)),
+    }
+}
+
+fn parse_fov(s: &str) -> Result<f64, String> {
+    let fov: f64 = s.parse().map_err(|_| format!("`{}` is not a number", s))?;
+    if fov > 0f64 && fov < 180f64 {
+        Ok(fov)
+    } else {
+        Err("field of view must be between 0 and 180 degrees".to_string())
+    }
+}
+
+fn parse_image_format(s: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_extension(s).ok_or_else(|| format!("unsupported image format `{}`", s))
+}
+
+fn load(path: &Path) -> Result<LoadedScene, String> {
+    load_scene(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Run before rendering, so that an output that cannot be written fails early
+fn create_parent(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent)
+            .map_err(|e| format!("cannot create {}: {}", parent.display(), e)),
+        None => Ok(()),
+    }
+}
+
+fn cannot_save(path: &Path) -> impl FnOnce(String) -> String + '_ {
+    move |e| format!("cannot save {}: {}", path.display(), e)
+}
+
+impl RenderArgs {
+    // Combinations of arguments clap cannot catch on its own
+    fn validate(&self) -> Result<(), String> {
+        if self.min_depth.is_some() && !matches!(self.integrator, IntegratorKind::Path) {
+            return Err("--min-depth only applies to the path integrator".to_string());
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<LoadedScene, String> {
+        self.validate()?;
+        let mut scene = load(&self.scene)?;
+        if let Some(resolution) = self.resolution {
+            scene.render.width = resolution.width;
+            scene.render.height = resolution.height;
+        }
+        if let Some(spp) = self.spp {
+            scene.render.samples_per_pixel = spp as usize;
+        }
+        if let Some(fov) = self.fov {
+            scene.render.fov = fov;
+        }
+        if let Some(projection) = self.projection {
+            if Projection::of(&scene.render.projection) != projection {
+                scene.render.projection = projection.description();
+            }
+        }
+        if let Some(aperture) = self.aperture {
+            scene.render.aperture = aperture;
+        }
+        if let Some(shutter) = self.shutter {
+            scene.render.shutter = shutter;
+        }
+        if let Some(focus_distance) = self.focus_distance {
+            scene.render.focus_distance = Some(focus_distance);
+            scene.render.autofocus = None;
+        }
+        if let Some(pixel) = self.autofocus {
+            scene.render.focus_distance = None;
+            scene.render.autofocus = Some([pixel.x, pixel.y]);
+        }
+        validate_render(&scene.render).map_err(|e| e.to_string())?;
+        Ok(scene)
+    }
+
+    fn integrator(&self, scene: &LoadedScene) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Path => {
+                let mut integrator = PathTracer::new();
+                if let Some(min_depth) = self.min_depth {
+                    integrator = integrator.with_min_depth(min_depth);
+                }
+                if let Some(max_depth) = self.max_depth {
+                    integrator = integrator.with_max_depth(max_depth);
+                }
+                Box::new(integrator)
+            }
+            IntegratorKind::Whitted => Box::new(
+                self.max_depth
+                    .map_or_else(WhittedTracer::default, WhittedTracer::new),
+            ),
+            IntegratorKind::Normals => Box::new(NormalIntegrator::new()),
+            IntegratorKind::Depth => Box::new(DepthIntegrator::new(scene_depth(scene))),
+            IntegratorKind::Albedo => Box::new(AlbedoIntegrator::new()),
+            IntegratorKind::HitCount => Box::new(
+                self.max_depth
+                    .map_or_else(HitCountIntegrator::default, HitCountIntegrator::new),
+            ),
+        }
+    }
+
+    fn render(&self) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, String> {
+        let scene = self.load()?;
+        let integrator = self.integrator(&scene);
+        Ok(match self.mixer {
+            Mixer::Linear => render_with::<LinearMixer>(&scene, &*integrator),
+            Mixer::Rms => render_with::<RMSMixer>(&scene, &*integrator),
+        })
+    }
+
+    fn animate(
+        &self,
+        scene: &LoadedScene,
+        on_frame: impl FnMut(usize, RgbImage) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let integrator = self.integrator(scene);
+        match self.mixer {
+            Mixer::Linear => render_animation::<LinearMixer>(scene, &*integrator, on_frame),
+            Mixer::Rms => render_animation::<RMSMixer>(scene, &*integrator, on_frame),
+        }
+    }
+}
+
+// Distance from the camera to the farthest corner of the scene, used to scale
+// the depth integrator
+fn scene_depth(scene: &LoadedScene) -> f64 {
+    const UNBOUNDED_DEPTH: f64 = 100f64;
+    match scene.world.bounding_box() {
+        Some(aabb) if !aabb.is_empty() => {
+            let position = scene.camera.position();
+            (aabb.min - position)
+                .abs()
+                .max((aabb.max - position).abs())
+                .length()
+        }
+        _ => UNBOUNDED_DEPTH,
+    }
+}
+
+fn render_with<M: ColorMixer>(
+    scene: &LoadedScene,
+    integrator: &dyn Integrator,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    scene
+        .camera
+        .render::<M>(&*scene.render_spec(), &*scene.world, integrator)
+}
+
+fn print_info(scene: &LoadedScene) {
+    let render = &scene.render;
+    println!("resolution:        {}x{}", render.width, render.height);
+    println!("samples per pixel: {}", render.samples_per_pixel);
+    println!("field of view:     {}", render.fov);
+    println!("camera position:   {}", scene.camera.position());
+    let (x, y, z) = scene.camera.rotation().to_euler(EulerRot::XYZ);
+    println!(
+        "camera rotation:   [{}, {}, {}] (XYZ euler, degrees)",
+        x.to_degrees(),
+        y.to_degrees(),
+        z.to_degrees()
     );
+    let mut names: Vec<&String> = scene.materials.keys().collect();
+    names.sort();
+    println!("materials ({}):", names.len());
+    for name in names {
+        println!("  {}", name);
+    }
+    println!("objects:           {}", scene.object_count);
+    println!("bvh:               {}", scene.bvh_stats);
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Render {
+            render,
+            output,
+            format,
+        } => {
+            let format = match format {
+                Some(format) => format,
+                None => ImageFormat::from_path(&output).map_err(|_| {
+                    format!(
+                        "cannot deduce image format from `{}`, use --format",
+                        output.display()
+                    )
+                })?,
+            };
+            create_parent(&output)?;
+            let buffer = render.render()?;
+            ImageFormatsSaver::with_format(format)
+                .save_to(&buffer, &output)
+                .map_err(cannot_save(&output))?;
+        }
+        Command::Ascii {
+            render,
+            output,
+            font,
+        } => {
+            // load the font first so a bad path fails before the long render
+            let saver = AsciiArtSaver::try_new(&font)
+                .map_err(|e| format!("cannot load font {}: {}", font.display(), e))?;
+            create_parent(&output)?;
+            let buffer = render.render()?;
+            saver
+                .save_to(&buffer, &output)
+                .map_err(cannot_save(&output))?;
+        }
+        Command::Animate {
+            render,
+            output,
+            animation,
+            frames,
+            fps,
+        } => {
+            ImageFormat::from_path(&output)
+                .map_err(|_| format!("cannot deduce image format from `{}`", output.display()))?;
+            let animation = match animation {
+                Some(path) => {
+                    let format = AnimationFormat::from_path(&path).ok_or_else(|| {
+                        format!("`{}` is neither a .gif nor a .png file", path.display())
+                    })?;
+                    Some((path, format))
+                }
+                None => None,
+            };
+            let mut scene = render.load()?;
+            let Some(settings) = &mut scene.animation else {
+                return Err(format!(
+                    "{} has no [animation] section",
+                    render.scene.display()
+                ));
+            };
+            if let Some(frames) = frames {
+                settings.frames = frames as usize;
+            }
+            if let Some(fps) = fps {
+                settings.fps = fps;
+            }
+            let (frame_count, fps) = (settings.frames, settings.fps);
+            create_parent(&output)?;
+            if let Some((path, _)) = &animation {
+                create_parent(path)?;
+            }
+
+            let saver = ImageFormatsSaver::new();
+            let mut images = vec![];
+            render.animate(&scene, |index, image| {
+                let path = frame_path(&output, index);
+                saver.save_to(&image, &path).map_err(cannot_save(&path))?;
+                println!("frame {}/{}: {}", index + 1, frame_count, path.display());
+                if animation.is_some() {
+                    images.push(image);
+                }
+                Ok(())
+            })?;
+            if let Some((path, format)) = animation {
+                AnimationSaver::new(format, fps)
+                    .save_to(&images, &path)
+                    .map_err(cannot_save(&path))?;
+            }
+        }
+        Command::Info { scene } => print_info(&load(&scene)?),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_resolution() {
+        let resolution: Resolution = "300x200".parse().unwrap();
+        assert_eq!((resolution.width, resolution.height), (300, 200));
+        let resolution: Resolution = "64 X 48".parse().unwrap();
+        assert_eq!((resolution.width, resolution.height), (64, 48));
+        assert!("300".parse::<Resolution>().is_err());
+        assert!("0x200".parse::<Resolution>().is_err());
+        assert!("300x-2".parse::<Resolution>().is_err());
+        assert!("axb".parse::<Resolution>().is_err());
+    }
+
+    #[test]
+    fn test_pixel() {
+        let pixel: Pixel = "150, 100".parse().unwrap();
+        assert_eq!((pixel.x, pixel.y), (150, 100));
+        let pixel: Pixel = "0,0".parse().unwrap();
+        assert_eq!((pixel.x, pixel.y), (0, 0));
+        assert!("150".parse::<Pixel>().is_err());
+        assert!("-1,2".parse::<Pixel>().is_err());
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(parse_non_negative("0"), Ok(0f64));
+        assert_eq!(parse_non_negative("0.5"), Ok(0.5));
+        assert!(parse_non_negative("-0.5").is_err());
+        assert!(parse_non_negative("NaN").is_err());
+        assert_eq!(parse_positive("2"), Ok(2f64));
+        assert!(parse_positive("0").is_err());
+        assert!(parse_positive("fast").is_err());
+    }
+
+    #[test]
+    fn test_fov() {
+        assert_eq!(parse_fov("90"), Ok(90f64));
+        assert!(parse_fov("0").is_err());
+        assert!(parse_fov("180").is_err());
+        assert!(parse_fov("wide").is_err());
+    }
+
+    #[test]
+    fn test_image_format() {
+        assert_eq!(parse_image_format("png"), Ok(ImageFormat::Png));
+        assert_eq!(parse_image_format("JPG"), Ok(ImageFormat::Jpeg));
+        assert!(parse_image_format("txt").is_err());
+    }
+
+    #[test]
+    fn test_arguments() {
+        let cli = Cli::try_parse_from(["raytrace_cli", "render", "a.toml", "-r", "30x20"]).unwrap();
+        let Command::Render { render, output, .. } = cli.command else {
+            panic!("not a render command");
+        };
+        assert_eq!(render.resolution.map(|r| r.width), Some(30));
+        assert_eq!(output, PathBuf::from("output.png"));
+
+        let args = ["raytrace_cli", "render", "a.toml", "--fov", "200"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = ["raytrace_cli", "render", "a.toml", "--spp", "0"];
+        assert!(Cli::try_parse_from(args).is_err());
+        let args = [
+            "raytrace_cli",
+            "render",
+            "a.toml",
+            "--focus-distance",
+            "2",
+            "--autofocus",
+            "1,1",
+        ];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
 
-    let buffer = camera.render::<LinearMixer>(&spec, &world);
-    let saver = ImageFormatsSaver::new();
-    saver.save_to(&buffer, "test_saver.png");
+    #[test]
+    fn test_min_depth_needs_path_integrator() {
+        let render_args = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["raytrace_cli", "render", "a.toml"].iter().chain(args))
+                .unwrap();
+            let Command::Render { render, .. } = cli.command else {
+                panic!("not a render command");
+            };
+            render
+        };
+        assert!(render_args(&["--min-depth", "3"]).validate().is_ok());
+        assert!(render_args(&["--integrator", "path", "--min-depth", "3"])
+            .validate()
+            .is_ok());
+        assert!(render_args(&["--integrator", "whitted"]).validate().is_ok());
+        let whitted = render_args(&["--integrator", "whitted", "--min-depth", "3"]);
+        assert!(whitted.validate().is_err());
+        // before the scene is even read
+        assert!(matches!(whitted.load(), Err(e) if e.contains("--min-depth")));
+    }
 }
@@ -47,7 +47,7 @@ impl Material for DielectricMaterial {
         };
 
         Some(ScatterRecord {
-            scattered: Ray::new(rec.point, direction),
+            scattered: Ray::new(rec.point, direction).with_time(ray.time),
             attenuation_factor: attenuation,
         })
     }
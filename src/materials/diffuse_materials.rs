@@ -3,8 +3,9 @@ use crate::utils::random_unit_vector;
 use crate::utils::random_unit_vector_on_hemisphere;
 use crate::{ray::Ray, world::intersectable::IntersectRecord};
 use glam::DVec3;
+use std::f64::consts::PI;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SimpleDiffuseMaterial {}
 
 impl SimpleDiffuseMaterial {
@@ -14,17 +15,31 @@ impl SimpleDiffuseMaterial {
 }
 
 impl Material for SimpleDiffuseMaterial {
-    fn scatter(&self, _ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
         let attenuation_factor = DVec3::splat(0.5);
         let scattered = Ray {
             origin: hit.point,
             direction: random_unit_vector_on_hemisphere(hit.normal),
+            time: ray.time,
         };
         Some(ScatterRecord {
             attenuation_factor,
             scattered,
         })
     }
+
+    fn eval(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> DVec3 {
+        DVec3::splat(0.5) * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> f64 {
+        // uniform over the hemisphere
+        if hit.normal.dot(direction) > 0f64 {
+            1f64 / (2f64 * PI)
+        } else {
+            0f64
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,7 +54,7 @@ impl LambertianMaterial {
 }
 
 impl Material for LambertianMaterial {
-    fn scatter(&self, _ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
         let attenuation_factor = self.albedo;
         let mut random_dir = hit.normal + random_unit_vector();
         let epsilon = 1e-7;
@@ -49,10 +64,20 @@ impl Material for LambertianMaterial {
         let scattered = Ray {
             origin: hit.point,
             direction: random_dir,
+            time: ray.time,
         };
         Some(ScatterRecord {
             attenuation_factor,
             scattered,
         })
     }
+
+    fn eval(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> DVec3 {
+        self.albedo * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> f64 {
+        // the normal offset by a random unit vector is cosine distributed
+        hit.normal.dot(direction).max(0f64) / PI
+    }
 }
@@ -0,0 +1,31 @@
+use crate::color::LinearRgbColor;
+use crate::materials::{Material, ScatterRecord};
+use crate::{ray::Ray, world::IntersectRecord};
+
+// Emits the same radiance in every direction from both sides of the surface,
+// and does not reflect any incoming light.
+pub struct DiffuseLight {
+    radiance: LinearRgbColor,
+}
+
+impl DiffuseLight {
+    pub fn new(color: LinearRgbColor, intensity: f64) -> Self {
+        Self {
+            radiance: color * intensity,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &IntersectRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _hit: &IntersectRecord) -> LinearRgbColor {
+        self.radiance
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
@@ -1,6 +1,7 @@
 use glam::DVec3;
 use std::sync::Arc;
 
+use crate::color::LinearRgbColor;
 use crate::ray::Ray;
 use crate::world::intersectable::IntersectRecord;
 
@@ -12,6 +13,35 @@ pub struct ScatterRecord {
 pub trait Material: Sync + Send {
     fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord>;
 
+    // Fraction of the light arriving from `direction` (unit) that is scattered
+    // along the incoming ray back to its origin, per unit solid angle and
+    // including the cosine term. For directions `scatter` samples,
+    // `eval / pdf` equals its `attenuation_factor`.
+    fn eval(&self, _ray: &Ray, _hit: &IntersectRecord, _direction: DVec3) -> DVec3 {
+        DVec3::ZERO
+    }
+
+    // Solid angle density of `scatter` picking `direction`. Perfectly specular
+    // materials keep the default zero, so the lights are never sampled for them.
+    fn pdf(&self, _ray: &Ray, _hit: &IntersectRecord, _direction: DVec3) -> f64 {
+        0f64
+    }
+
+    // Light given off by the surface itself, black for everything but lights
+    fn emitted(&self, _hit: &IntersectRecord) -> LinearRgbColor {
+        LinearRgbColor::default()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Scatters inside of a volume rather than off a surface (see
+    // `materials::phase`), in any direction regardless of the normal
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
     fn make_shared<Mat: Material + 'static>(material: Mat) -> SharedMaterial
     where
         Self: Sized,
@@ -1,4 +1,5 @@
 use glam::DVec3;
+use std::f64::consts::PI;
 
 use crate::{
     ray::Ray,
@@ -18,16 +19,82 @@ impl MetalMaterial {
         let fuzz = Interval::new(0f64, 1f64).clamp(fuzz.abs());
         Self { albedo, fuzz }
     }
+
+    fn reflect(ray: &Ray, hit: &IntersectRecord) -> DVec3 {
+        let unit_dir = ray.direction.normalize();
+        unit_dir - 2f64 * unit_dir.dot(hit.normal) * hit.normal
+    }
 }
 
 impl Material for MetalMaterial {
     fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
-        let unit_dir = ray.direction.normalize();
-        let reflect = unit_dir - 2f64 * unit_dir.dot(hit.normal) * hit.normal;
+        let reflect = Self::reflect(ray, hit);
         let fuzz_reflect = reflect + self.fuzz * random_unit_vector();
         Some(ScatterRecord {
             attenuation_factor: self.albedo,
-            scattered: Ray::new(hit.point, fuzz_reflect),
+            scattered: Ray::new(hit.point, fuzz_reflect).with_time(ray.time),
         })
     }
+
+    fn eval(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> DVec3 {
+        self.albedo * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> f64 {
+        if self.fuzz == 0f64 {
+            // perfect mirror
+            return 0f64;
+        }
+        // `scatter` picks a uniform point on the sphere of radius `fuzz`
+        // around the unit reflection. Along `direction`, the ray enters and
+        // leaves that sphere at t = c -+ s, each point contributing
+        // t^2 / (cos * 4 PI fuzz^2) with cos = s / fuzz.
+        let c = direction.normalize().dot(Self::reflect(ray, hit));
+        let disc = c * c - 1f64 + self.fuzz * self.fuzz;
+        if c <= 0f64 || disc <= 0f64 {
+            return 0f64;
+        }
+        let s = disc.sqrt();
+        (c * c + s * s) / (2f64 * PI * self.fuzz * s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    #[test]
+    fn test_pdf_integrates_to_one() {
+        let ray = Ray::new(DVec3::new(-1f64, 1f64, 0f64), DVec3::new(1f64, -1f64, 0f64));
+        let hit = IntersectRecord::new(&ray, DVec3::Y, 1f64, DummyMaterial::new_shared());
+
+        for fuzz in [0.1, 0.5, 1f64] {
+            let metal = MetalMaterial::new(DVec3::ONE, fuzz);
+            // midpoint rule over the sphere, uniform in z and the azimuth
+            let n = 1000;
+            let mut total = 0f64;
+            for i in 0..n {
+                let z = -1f64 + (i as f64 + 0.5) * 2f64 / n as f64;
+                let r = (1f64 - z * z).sqrt();
+                for j in 0..n {
+                    let phi = (j as f64 + 0.5) * 2f64 * PI / n as f64;
+                    let direction = DVec3::new(r * phi.cos(), r * phi.sin(), z);
+                    total += metal.pdf(&ray, &hit, direction);
+                }
+            }
+            let integral = total * 4f64 * PI / (n * n) as f64;
+            assert!((integral - 1f64).abs() < 0.02, "fuzz {fuzz}: {integral}");
+        }
+    }
+
+    #[test]
+    fn test_mirror_is_specular() {
+        let ray = Ray::new(DVec3::new(-1f64, 1f64, 0f64), DVec3::new(1f64, -1f64, 0f64));
+        let hit = IntersectRecord::new(&ray, DVec3::Y, 1f64, DummyMaterial::new_shared());
+        let metal = MetalMaterial::new(DVec3::ONE, 0f64);
+        let reflected = DVec3::new(1f64, 1f64, 0f64).normalize();
+        assert_eq!(metal.pdf(&ray, &hit, reflected), 0f64);
+        assert_eq!(metal.eval(&ray, &hit, reflected), DVec3::ZERO);
+    }
 }
@@ -1,8 +1,12 @@
 pub mod dielectric;
 pub mod diffuse_materials;
+pub mod light;
 pub mod material;
 pub mod metal;
+pub mod phase;
 pub use dielectric::DielectricMaterial;
 pub use diffuse_materials::{LambertianMaterial, SimpleDiffuseMaterial};
+pub use light::DiffuseLight;
 pub use material::{Material, ScatterRecord, SharedMaterial};
 pub use metal::MetalMaterial;
+pub use phase::{HenyeyGreensteinPhase, IsotropicPhase};
@@ -0,0 +1,166 @@
+use glam::DVec3;
+use rand::{thread_rng, Rng};
+use std::f64::consts::PI;
+
+use crate::{ray::Ray, utils::random_unit_vector, world::IntersectRecord};
+
+use super::{Material, ScatterRecord};
+
+// Phase functions, the materials of participating media. They scatter light
+// at points inside of a volume rather than on a surface, so there is no
+// normal and no cosine term, and `eval` is the phase function times the
+// albedo.
+
+// Scatters equally in every direction
+pub struct IsotropicPhase {
+    albedo: DVec3,
+}
+
+impl IsotropicPhase {
+    pub fn new(albedo: DVec3) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for IsotropicPhase {
+    fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation_factor: self.albedo,
+            scattered: Ray::new(hit.point, random_unit_vector()).with_time(ray.time),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> DVec3 {
+        self.albedo * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, _hit: &IntersectRecord, _direction: DVec3) -> f64 {
+        1f64 / (4f64 * PI)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+// Scatters mostly forwards for a positive asymmetry `g`, mostly backwards for
+// a negative one, e.g. 0.8 for clouds and fog. Zero is isotropic.
+pub struct HenyeyGreensteinPhase {
+    albedo: DVec3,
+    g: f64,
+}
+
+impl HenyeyGreensteinPhase {
+    pub fn new(albedo: DVec3, g: f64) -> Self {
+        // at +-1 all of the light goes straight through or back
+        let g = g.clamp(-0.99, 0.99);
+        Self { albedo, g }
+    }
+
+    // Density over the sphere of turning by an angle of the given cosine
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1f64 + g * g - 2f64 * g * cos_theta;
+        (1f64 - g * g) / (4f64 * PI * denominator * denominator.sqrt())
+    }
+
+    // Inverts the cumulative distribution of `phase`
+    fn sample_cos_theta(&self, u: f64) -> f64 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return 1f64 - 2f64 * u;
+        }
+        let s = (1f64 - g * g) / (1f64 - g + 2f64 * g * u);
+        ((1f64 + g * g - s * s) / (2f64 * g)).clamp(-1f64, 1f64)
+    }
+}
+
+impl Material for HenyeyGreensteinPhase {
+    fn scatter(&self, ray: &Ray, hit: &IntersectRecord) -> Option<ScatterRecord> {
+        let mut rng = thread_rng();
+        let forward = ray.direction.normalize();
+        let cos_theta = self.sample_cos_theta(rng.gen());
+        let sin_theta = (1f64 - cos_theta * cos_theta).max(0f64).sqrt();
+        let phi = rng.gen_range(0f64..2f64 * PI);
+        let (u, v) = forward.any_orthonormal_pair();
+        let direction = (u * phi.cos() + v * phi.sin()) * sin_theta + forward * cos_theta;
+        Some(ScatterRecord {
+            attenuation_factor: self.albedo,
+            scattered: Ray::new(hit.point, direction).with_time(ray.time),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &IntersectRecord, direction: DVec3) -> DVec3 {
+        self.albedo * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, _hit: &IntersectRecord, direction: DVec3) -> f64 {
+        self.phase(ray.direction.normalize().dot(direction.normalize()))
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn integrate(pdf: impl Fn(DVec3) -> f64) -> f64 {
+        // midpoint rule over the sphere, uniform in z and the azimuth
+        let n = 400;
+        let mut total = 0f64;
+        for i in 0..n {
+            let z = -1f64 + (i as f64 + 0.5) * 2f64 / n as f64;
+            let r = (1f64 - z * z).sqrt();
+            for j in 0..n {
+                let phi = (j as f64 + 0.5) * 2f64 * PI / n as f64;
+                total += pdf(DVec3::new(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        total * 4f64 * PI / (n * n) as f64
+    }
+
+    #[test]
+    fn test_pdf_integrates_to_one() {
+        let ray = Ray::new(DVec3::ZERO, DVec3::new(0f64, 0f64, 2f64));
+        let hit = IntersectRecord::new(&ray, DVec3::NEG_Z, 1f64, DummyMaterial::new_shared());
+        let isotropic = IsotropicPhase::new(DVec3::ONE);
+        let integral = integrate(|d| isotropic.pdf(&ray, &hit, d));
+        assert!((integral - 1f64).abs() < 1e-9);
+        for g in [-0.5, 0f64, 0.3, 0.8] {
+            let hg = HenyeyGreensteinPhase::new(DVec3::ONE, g);
+            let integral = integrate(|d| hg.pdf(&ray, &hit, d));
+            assert!((integral - 1f64).abs() < 0.01, "g {g}: {integral}");
+        }
+    }
+
+    #[test]
+    fn test_sampling_matches_asymmetry() {
+        // the mean cosine of the scattering angle is g
+        let ray = Ray::new(DVec3::ZERO, DVec3::new(1f64, 1f64, 0f64));
+        let hit = IntersectRecord::new(&ray, DVec3::NEG_X, 1f64, DummyMaterial::new_shared());
+        let forward = ray.direction.normalize();
+        for g in [-0.6, 0f64, 0.7] {
+            let hg = HenyeyGreensteinPhase::new(DVec3::ONE, g);
+            let n = 100000;
+            let mean = (0..n)
+                .map(|_| {
+                    let scattered = hg.scatter(&ray, &hit).unwrap().scattered;
+                    assert_eq!(scattered.origin, hit.point);
+                    scattered.direction.normalize().dot(forward)
+                })
+                .sum::<f64>()
+                / n as f64;
+            assert!((mean - g).abs() < 0.01, "g {g}: {mean}");
+        }
+    }
+
+    #[test]
+    fn test_volumetric() {
+        assert!(IsotropicPhase::new(DVec3::ONE).is_volumetric());
+        assert!(!DummyMaterial::new_shared().is_volumetric());
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, RgbImage};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnimationFormat {
+    Gif,
+    // animated PNG, shown as its first frame by viewers without APNG support
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Self::Gif),
+            "png" | "apng" => Some(Self::Apng),
+            _ => None,
+        }
+    }
+}
+
+// Encodes rendered frames into a single looping animation file
+pub struct AnimationSaver {
+    format: AnimationFormat,
+    fps: f64,
+}
+
+// Trades a little color accuracy for a much faster GIF quantization, 1 being
+// the slowest and best
+const GIF_SPEED: i32 = 10;
+
+impl AnimationSaver {
+    pub fn new(format: AnimationFormat, fps: f64) -> Self {
+        Self { format, fps }
+    }
+
+    pub fn save_to(&self, frames: &[RgbImage], path: impl AsRef<Path>) -> Result<(), String> {
+        let Some(first) = frames.first() else {
+            return Err("no frames to save".to_string());
+        };
+        if frames.iter().any(|f| f.dimensions() != first.dimensions()) {
+            return Err("frames have different sizes".to_string());
+        }
+        let file = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        match self.format {
+            AnimationFormat::Gif => self.save_gif(frames, file),
+            AnimationFormat::Apng => self.save_apng(frames, file),
+        }
+    }
+
+    fn frame_millis(&self) -> u32 {
+        (1000f64 / self.fps).round().max(1f64) as u32
+    }
+
+    fn save_gif(&self, frames: &[RgbImage], file: BufWriter<File>) -> Result<(), String> {
+        let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        let delay = Delay::from_numer_denom_ms(self.frame_millis(), 1);
+        encoder
+            .encode_frames(frames.iter().map(|frame| {
+                let rgba = image::DynamicImage::ImageRgb8(frame.clone()).into_rgba8();
+                image::Frame::from_parts(rgba, 0, 0, delay)
+            }))
+            .map_err(|e| e.to_string())
+    }
+
+    fn save_apng(&self, frames: &[RgbImage], file: BufWriter<File>) -> Result<(), String> {
+        let (width, height) = frames[0].dimensions();
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        // zero plays means looping forever
+        encoder
+            .set_animated(frames.len() as u32, 0)
+            .map_err(|e| e.to_string())?;
+        let millis = self.frame_millis().min(u16::MAX as u32) as u16;
+        encoder
+            .set_frame_delay(millis, 1000)
+            .map_err(|e| e.to_string())?;
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        for frame in frames {
+            writer
+                .write_image_data(frame.as_raw())
+                .map_err(|e| e.to_string())?;
+        }
+        writer.finish().map_err(|e| e.to_string())
+    }
+}
+
+// Path of a frame in a numbered sequence. The last run of `#` in `pattern` is
+// replaced by the zero-padded frame number, e.g. `frame_###.png` gives
+// `frame_007.png`. Without any, the number is appended to the file name.
+pub fn frame_path(pattern: impl AsRef<Path>, index: usize) -> PathBuf {
+    let pattern = pattern.as_ref();
+    let name = pattern
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match name.rfind('#') {
+        Some(end) => {
+            let start = name[..end].rfind(|c| c != '#').map_or(0, |i| i + 1);
+            let width = end + 1 - start;
+            format!(
+                "{}{:0width$}{}",
+                &name[..start],
+                index,
+                &name[end + 1..],
+                width = width
+            )
+        }
+        None => {
+            let stem = pattern
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            match pattern.extension() {
+                Some(extension) => {
+                    format!("{}_{:04}.{}", stem, index, extension.to_string_lossy())
+                }
+                None => format!("{}_{:04}", stem, index),
+            }
+        }
+    };
+    pattern.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_path() {
+        assert_eq!(
+            frame_path("out/frame_###.png", 7),
+            PathBuf::from("out/frame_007.png")
+        );
+        assert_eq!(frame_path("#.png", 12), PathBuf::from("12.png"));
+        assert_eq!(frame_path("a#b##.png", 3), PathBuf::from("a#b03.png"));
+        assert_eq!(
+            frame_path("out/frame.png", 3),
+            PathBuf::from("out/frame_0003.png")
+        );
+        assert_eq!(frame_path("frame", 3), PathBuf::from("frame_0003"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            AnimationFormat::from_path("a.GIF"),
+            Some(AnimationFormat::Gif)
+        );
+        assert_eq!(
+            AnimationFormat::from_path("a.png"),
+            Some(AnimationFormat::Apng)
+        );
+        assert_eq!(
+            AnimationFormat::from_path("a.apng"),
+            Some(AnimationFormat::Apng)
+        );
+        assert_eq!(AnimationFormat::from_path("a.jpg"), None);
+    }
+
+    #[test]
+    fn test_save_apng() {
+        let frames: Vec<RgbImage> = (0..3u8)
+            .map(|i| RgbImage::from_pixel(4, 2, image::Rgb([i * 100, 0, 0])))
+            .collect();
+        let path = std::env::temp_dir().join(format!("anim_test_{}.png", std::process::id()));
+        AnimationSaver::new(AnimationFormat::Apng, 10f64)
+            .save_to(&frames, &path)
+            .unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!(control.num_frames, 3);
+        assert_eq!(control.num_plays, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
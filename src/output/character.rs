@@ -17,10 +17,14 @@ pub struct GlyphPalette {
 
 impl GlyphPalette {
     pub fn from_font<P: AsRef<Path>>(filepath: P) -> GlyphPalette {
-        let font = Self::load_font(filepath).unwrap();
-        GlyphPalette {
+        Self::try_from_font(filepath).unwrap()
+    }
+
+    pub fn try_from_font<P: AsRef<Path>>(filepath: P) -> Result<GlyphPalette, String> {
+        let font = Self::load_font(filepath)?;
+        Ok(GlyphPalette {
             glyph_to_char: Self::font_to_glyph_pixels(&font),
-        }
+        })
     }
 
     fn load_font<P: AsRef<Path>>(filename: P) -> Result<Font, String> {
@@ -1,33 +1,55 @@
 use std::{fs::File, io::Write, ops::Deref, path::Path};
 
-use image::{imageops, EncodableLayout, GenericImageView, ImageBuffer, Pixel, PixelWithColorType};
+use image::{
+    imageops, EncodableLayout, GenericImageView, ImageBuffer, ImageFormat, Pixel,
+    PixelWithColorType,
+};
 
 use super::character::{GlyphPalette, GlyphPixel, BLURRED_SHAPE, BLURRED_SHAPE_SIZE};
 
 pub trait ImageSaver {
-    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>)
+    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>) -> Result<(), String>
     where
         [P::Subpixel]: EncodableLayout,
         C: Deref<Target = [P::Subpixel]>,
         P: PixelWithColorType + 'static;
 }
 
-pub struct ImageFormatsSaver {}
+pub struct ImageFormatsSaver {
+    // When not set, the format is deduced from the file extension
+    format: Option<ImageFormat>,
+}
 
 impl ImageFormatsSaver {
     pub fn new() -> Self {
-        Self {}
+        Self { format: None }
+    }
+
+    pub fn with_format(format: ImageFormat) -> Self {
+        Self {
+            format: Some(format),
+        }
+    }
+}
+
+impl Default for ImageFormatsSaver {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl ImageSaver for ImageFormatsSaver {
-    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>)
+    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>) -> Result<(), String>
     where
         [P::Subpixel]: EncodableLayout,
         C: Deref<Target = [P::Subpixel]>,
         P: PixelWithColorType,
     {
-        buff.save(path).unwrap()
+        match self.format {
+            Some(format) => buff.save_with_format(path, format),
+            None => buff.save(path),
+        }
+        .map_err(|e| e.to_string())
     }
 }
 
@@ -41,6 +63,12 @@ impl AsciiArtSaver {
             palette: GlyphPalette::from_font(font_path),
         }
     }
+
+    pub fn try_new<P: AsRef<Path>>(font_path: P) -> Result<Self, String> {
+        Ok(Self {
+            palette: GlyphPalette::try_from_font(font_path)?,
+        })
+    }
 }
 
 fn pad_image<P, C>(
@@ -56,8 +84,8 @@ where
     let height = img.height();
 
     // Calculate the new width and height that are multiples of character size
-    let new_width = (width + width_align - 1) / width_align * width_align;
-    let new_height = (height + height_align - 1) / height_align * height_align;
+    let new_width = width.div_ceil(width_align) * width_align;
+    let new_height = height.div_ceil(height_align) * height_align;
 
     // Pad the image to the new dimensions
     imageops::resize(img, new_width, new_height, imageops::Nearest)
@@ -70,14 +98,14 @@ fn subpixel_to_u8<T: image::Primitive>(subpixel: T) -> u8 {
 }
 
 impl ImageSaver for AsciiArtSaver {
-    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>)
+    fn save_to<P, C>(&self, buff: &ImageBuffer<P, C>, path: impl AsRef<Path>) -> Result<(), String>
     where
         [P::Subpixel]: EncodableLayout,
         C: Deref<Target = [P::Subpixel]>,
         P: PixelWithColorType + 'static,
     {
         // Pad the image so its dimensions are multiples of character size
-        let padded_img = pad_image(&buff, BLURRED_SHAPE.0, BLURRED_SHAPE.1);
+        let padded_img = pad_image(buff, BLURRED_SHAPE.0, BLURRED_SHAPE.1);
 
         // Calculate the number of blocks that can fit horizontally and vertically
         let blocks_horizontal = padded_img.width() / BLURRED_SHAPE.0;
@@ -88,7 +116,7 @@ impl ImageSaver for AsciiArtSaver {
             vec![vec![' '; blocks_horizontal as usize]; blocks_vertical as usize];
 
         // Process each block in parallel
-        (0..blocks_vertical).into_iter().for_each(|i| {
+        (0..blocks_vertical).for_each(|i| {
             for j in 0..blocks_horizontal {
                 // Define the top-left corner of the current block
                 let x = j * BLURRED_SHAPE.0;
@@ -121,7 +149,7 @@ impl ImageSaver for AsciiArtSaver {
         });
 
         // Open a file in write mode
-        let mut file = File::create(path).unwrap();
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
 
         // Iterate over the matrix and write each row as a line in the file
         for row in results {
@@ -129,7 +157,26 @@ impl ImageSaver for AsciiArtSaver {
             let line: String = row.iter().collect();
 
             // Write the line to the file, add a newline character to separate rows
-            writeln!(file, "{}", line).unwrap();
+            writeln!(file, "{}", line).map_err(|e| e.to_string())?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn test_save_error() {
+        let image = RgbImage::new(2, 2);
+        let path = std::env::temp_dir()
+            .join(format!("missing_{}", std::process::id()))
+            .join("out.png");
+        assert!(ImageFormatsSaver::new().save_to(&image, &path).is_err());
+        // no format to deduce from
+        let path = std::env::temp_dir().join("out.unknown");
+        assert!(ImageFormatsSaver::new().save_to(&image, path).is_err());
     }
 }
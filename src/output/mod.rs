@@ -1,6 +1,8 @@
+pub mod animation_saver;
 pub mod character;
 pub mod image_saver;
 pub mod render_target;
 
+pub use animation_saver::{frame_path, AnimationFormat, AnimationSaver};
 pub use image_saver::{AsciiArtSaver, ImageFormatsSaver, ImageSaver};
 pub use render_target::{ImageTarget, RenderTarget};
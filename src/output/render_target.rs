@@ -1,8 +1,5 @@
 use glam::DVec2;
-use std::{
-    fmt::{Display, Formatter, Result},
-    usize,
-};
+use std::fmt::{Display, Formatter, Result};
 
 use crate::color::LinearRgbColor;
 
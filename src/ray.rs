@@ -3,11 +3,22 @@ use glam::DVec3;
 pub struct Ray {
     pub origin: DVec3,
     pub direction: DVec3,
+    // when the ray is traced, in seconds, for moving objects and cameras
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: DVec3, direction: DVec3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0f64,
+        }
+    }
+
+    pub fn with_time(mut self, time: f64) -> Ray {
+        self.time = time;
+        self
     }
 
     pub fn at(&self, t: f64) -> DVec3 {
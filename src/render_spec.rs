@@ -1,7 +1,11 @@
 use std::iter::repeat_with;
 
+use crate::camera::Camera;
 use crate::ray::Ray;
-use glam::DVec3;
+use crate::utils::{random_in_unit_disk, Interval};
+use crate::world::environment::uv_to_direction;
+use crate::world::Scene;
+use glam::{DVec2, DVec3};
 use rand::random;
 
 #[derive(Copy, Clone)]
@@ -38,6 +42,16 @@ impl PinHoleSpec {
             resolution,
         }
     }
+
+    // Direction through the center of the pixel, on the z = -1 plane
+    pub fn pixel_center(&self, x: u32, y: u32) -> DVec3 {
+        DVec3::new(
+            x as f64 - self.resolution.width as f64 / 2f64 + 0.5f64,
+            -(y as f64 - self.resolution.height as f64 / 2f64 + 0.5f64),
+            0f64,
+        ) * self.pixel_tangent
+            + DVec3::new(0f64, 0f64, -1f64)
+    }
 }
 
 impl RenderSpec for PinHoleSpec {
@@ -48,29 +62,262 @@ impl RenderSpec for PinHoleSpec {
     fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
         // Generate and return an iterator over Rays for the given pixel
         // This is where you implement your ray generation logic
-        let base_vector = DVec3::new(
-            x as f64 - self.resolution.width as f64 / 2f64 + 0.5f64,
-            -(y as f64 - self.resolution.height as f64 / 2f64 + 0.5f64),
-            0f64,
-        ) * self.pixel_tangent
-            + DVec3::new(0f64, 0f64, -1f64);
+        let base_vector = self.pixel_center(x, y);
         let origin = DVec3::ZERO;
 
         Box::new(
-            repeat_with(move || Ray {
-                origin,
-                direction: base_vector
-                    + self.pixel_tangent
-                        * DVec3::new(random::<f64>() - 0.5, random::<f64>() - 0.5, 0f64),
+            repeat_with(move || {
+                Ray::new(
+                    origin,
+                    base_vector
+                        + self.pixel_tangent
+                            * DVec3::new(random::<f64>() - 0.5, random::<f64>() - 0.5, 0f64),
+                )
+            })
+            .take(self.sample_per_pixel),
+        )
+    }
+}
+
+// Pin hole rays refocused through a thin lens: everything at
+// `focus_distance` in front of the camera stays sharp, the rest is blurred
+// more the wider the aperture.
+pub struct ThinLensSpec {
+    pinhole: PinHoleSpec,
+    aperture_radius: f64,
+    focus_distance: f64,
+}
+
+impl ThinLensSpec {
+    pub fn new(pinhole: PinHoleSpec, aperture_radius: f64, focus_distance: f64) -> Self {
+        Self {
+            pinhole,
+            aperture_radius,
+            focus_distance,
+        }
+    }
+
+    // Focuses on whatever `camera` sees through the center of the pixel. The
+    // focus distance is left as is when there is nothing there.
+    pub fn with_autofocus(mut self, camera: &Camera, world: &dyn Scene, x: u32, y: u32) -> Self {
+        let probe = camera.to_world(&Ray::new(DVec3::ZERO, self.pinhole.pixel_center(x, y)));
+        if let Some(hit) = world.hit(&probe, &Interval::greater_than(0f64)) {
+            // the probe direction is one unit deep, t is the depth of the hit
+            self.focus_distance = hit.t;
+        }
+        self
+    }
+
+    pub fn aperture_radius(&self) -> f64 {
+        self.aperture_radius
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+    }
+}
+
+impl RenderSpec for ThinLensSpec {
+    fn image_size(&self) -> ImageSize {
+        self.pinhole.image_size()
+    }
+
+    fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
+        Box::new(self.pinhole.ray_for_pixel(x, y).map(|ray| {
+            // the pin hole direction is one unit deep, scale it onto the
+            // focus plane and aim at it from a random point of the lens
+            let focus_point = ray.direction * self.focus_distance;
+            let origin = random_in_unit_disk() * self.aperture_radius;
+            Ray::new(origin, focus_point - origin)
+        }))
+    }
+}
+
+// Parallel rays along -Z, for drawings without perspective such as
+// architectural elevations. `view_height` is the height of the image in world
+// units.
+pub struct OrthographicSpec {
+    sample_per_pixel: usize,
+    pixel_size: f64,
+    resolution: ImageSize,
+}
+
+impl OrthographicSpec {
+    pub fn new(sample_per_pixel: usize, view_height: f64, resolution: ImageSize) -> Self {
+        Self {
+            sample_per_pixel,
+            pixel_size: view_height / resolution.height as f64,
+            resolution,
+        }
+    }
+}
+
+impl RenderSpec for OrthographicSpec {
+    fn image_size(&self) -> ImageSize {
+        self.resolution
+    }
+
+    fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
+        let (half_width, half_height) = (
+            self.resolution.width as f64 / 2f64,
+            self.resolution.height as f64 / 2f64,
+        );
+        Box::new(
+            repeat_with(move || {
+                let origin = DVec3::new(
+                    x as f64 + random::<f64>() - half_width,
+                    -(y as f64 + random::<f64>() - half_height),
+                    0f64,
+                ) * self.pixel_size;
+                Ray::new(origin, DVec3::NEG_Z)
             })
             .take(self.sample_per_pixel),
         )
     }
 }
 
+// Full 360 by 180 degrees panorama, laid out like the environment maps: the
+// center of the image looks down -Z.
+pub struct EquirectangularSpec {
+    sample_per_pixel: usize,
+    resolution: ImageSize,
+}
+
+impl EquirectangularSpec {
+    pub fn new(sample_per_pixel: usize, resolution: ImageSize) -> Self {
+        Self {
+            sample_per_pixel,
+            resolution,
+        }
+    }
+}
+
+impl RenderSpec for EquirectangularSpec {
+    fn image_size(&self) -> ImageSize {
+        self.resolution
+    }
+
+    fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
+        Box::new(
+            repeat_with(move || {
+                let uv = DVec2::new(
+                    (x as f64 + random::<f64>()) / self.resolution.width as f64,
+                    (y as f64 + random::<f64>()) / self.resolution.height as f64,
+                );
+                Ray::new(DVec3::ZERO, uv_to_direction(uv))
+            })
+            .take(self.sample_per_pixel),
+        )
+    }
+}
+
+// How the angle from the view axis maps to the distance from the image center
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    // proportional to the angle
+    Equidistant,
+    // preserving areas, r ~ sin(angle / 2)
+    Equisolid,
+}
+
+// Circular fisheye, the image circle fits in the shorter side of the image
+// and spans `fov` degrees, up to 360. Pixels outside of it get no rays.
+pub struct FisheyeSpec {
+    sample_per_pixel: usize,
+    half_fov: f64,
+    mapping: FisheyeMapping,
+    resolution: ImageSize,
+}
+
+impl FisheyeSpec {
+    pub fn new(
+        sample_per_pixel: usize,
+        fov_in_degree: f64,
+        mapping: FisheyeMapping,
+        resolution: ImageSize,
+    ) -> Self {
+        Self {
+            sample_per_pixel,
+            half_fov: (fov_in_degree / 2f64).to_radians(),
+            mapping,
+            resolution,
+        }
+    }
+
+    // Direction for a point of the image, in units of the image circle
+    // radius from its center. None outside of the circle.
+    fn direction(&self, offset: DVec2) -> Option<DVec3> {
+        let r = offset.length();
+        if r > 1f64 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2f64 * (r * (self.half_fov / 2f64).sin()).asin(),
+        };
+        let phi = offset.y.atan2(offset.x);
+        Some(DVec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos(),
+        ))
+    }
+}
+
+impl RenderSpec for FisheyeSpec {
+    fn image_size(&self) -> ImageSize {
+        self.resolution
+    }
+
+    fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
+        let (width, height) = (self.resolution.width as f64, self.resolution.height as f64);
+        let radius = width.min(height) / 2f64;
+        Box::new(
+            repeat_with(move || {
+                DVec2::new(
+                    x as f64 + random::<f64>() - width / 2f64,
+                    -(y as f64 + random::<f64>() - height / 2f64),
+                ) / radius
+            })
+            .take(self.sample_per_pixel)
+            .filter_map(|offset| self.direction(offset))
+            .map(|direction| Ray::new(DVec3::ZERO, direction)),
+        )
+    }
+}
+
+// Keeps the shutter of another spec open from `open` to `close`, in seconds.
+// Each ray is traced at a random time in between, so whatever moves meanwhile
+// is motion blurred.
+pub struct ShutterSpec {
+    inner: Box<dyn RenderSpec>,
+    open: f64,
+    close: f64,
+}
+
+impl ShutterSpec {
+    pub fn new(inner: Box<dyn RenderSpec>, open: f64, close: f64) -> Self {
+        Self { inner, open, close }
+    }
+}
+
+impl RenderSpec for ShutterSpec {
+    fn image_size(&self) -> ImageSize {
+        self.inner.image_size()
+    }
+
+    fn ray_for_pixel(&self, x: u32, y: u32) -> Box<dyn Iterator<Item = Ray> + '_> {
+        Box::new(self.inner.ray_for_pixel(x, y).map(|ray| {
+            let time = self.open + random::<f64>() * (self.close - self.open);
+            ray.with_time(time)
+        }))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::f64::consts::PI;
 
     #[test]
     fn test_pinhole() {
@@ -98,4 +345,124 @@ mod test {
         }
         assert_eq!(num_rays, 10);
     }
+
+    #[test]
+    fn test_thin_lens_focus() {
+        let size = ImageSize {
+            width: 3,
+            height: 3,
+        };
+        let spec = ThinLensSpec::new(PinHoleSpec::new(10, 90f64, size), 0.5, 4f64);
+        let pinhole = PinHoleSpec::new(10, 90f64, size);
+        for (x, y) in [(0, 0), (1, 1), (2, 1)] {
+            let target = pinhole.pixel_center(x, y) * 4f64;
+            for ray in spec.ray_for_pixel(x, y) {
+                assert_eq!(ray.origin.z, 0f64);
+                assert!(ray.origin.length() <= 0.5);
+                // every ray crosses the focus plane within the pixel footprint
+                let on_plane = ray.at(4f64 / -ray.direction.z);
+                assert!((on_plane - target).abs().max_element() <= 4f64 * pinhole.pixel_tangent);
+            }
+        }
+    }
+
+    #[test]
+    fn test_autofocus() {
+        use crate::color::LinearRgbColor;
+        use crate::test_utils::DummyMaterial;
+        use crate::world::{Intersectable, LerpScene, Sphere, VecContainer};
+        use glam::DQuat;
+
+        let material = DummyMaterial::new_shared();
+        let container: VecContainer =
+            vec![Sphere::new(DVec3::new(0f64, 0f64, -3f64), 1f64, &material).into_box()];
+        let black = LinearRgbColor::default();
+        let world = LerpScene::new(container, black, black);
+        let size = ImageSize {
+            width: 3,
+            height: 3,
+        };
+        let lens = |camera: &Camera, x, y| {
+            ThinLensSpec::new(PinHoleSpec::new(1, 90f64, size), 0.1, 10f64)
+                .with_autofocus(camera, &world, x, y)
+                .focus_distance()
+        };
+
+        let camera = Camera::new(DVec3::ZERO, DQuat::IDENTITY);
+        assert!((lens(&camera, 1, 1) - 2f64).abs() < 1e-9);
+        // nothing to focus on in the corner
+        assert_eq!(lens(&camera, 0, 0), 10f64);
+        // the probe follows the camera around
+        let camera = Camera::new(DVec3::new(0f64, 0f64, -6f64), DQuat::from_rotation_y(PI));
+        assert!((lens(&camera, 1, 1) - 2f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let size = ImageSize {
+            width: 4,
+            height: 2,
+        };
+        let spec = OrthographicSpec::new(10, 1f64, size);
+        for ray in spec.ray_for_pixel(3, 0) {
+            assert_eq!(ray.direction, DVec3::NEG_Z);
+            // top right quarter of a 2 by 1 view
+            assert!((0.5..=1f64).contains(&ray.origin.x));
+            assert!((0f64..=0.5).contains(&ray.origin.y));
+        }
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let size = ImageSize {
+            width: 400,
+            height: 200,
+        };
+        let spec = EquirectangularSpec::new(10, size);
+        for ray in spec.ray_for_pixel(200, 100) {
+            assert!(ray.direction.abs_diff_eq(DVec3::NEG_Z, 0.05));
+        }
+        for ray in spec.ray_for_pixel(300, 0) {
+            assert!(ray.direction.y > 0.99);
+        }
+        for ray in spec.ray_for_pixel(300, 100) {
+            assert!(ray.direction.abs_diff_eq(DVec3::X, 0.05));
+        }
+    }
+
+    #[test]
+    fn test_fisheye() {
+        let size = ImageSize {
+            width: 20,
+            height: 10,
+        };
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let spec = FisheyeSpec::new(10, 180f64, mapping, size);
+            assert_eq!(spec.direction(DVec2::ZERO), Some(DVec3::NEG_Z));
+            // the rim of the circle looks sideways
+            let rim = spec.direction(DVec2::new(0f64, 1f64)).unwrap();
+            assert!(rim.abs_diff_eq(DVec3::Y, 1e-9), "{rim}");
+            assert_eq!(spec.direction(DVec2::new(0.8, 0.8)), None);
+            assert_eq!(spec.ray_for_pixel(0, 0).count(), 0);
+            assert_eq!(spec.ray_for_pixel(10, 5).count(), 10);
+        }
+        // half way out, the equidistant angle is half of the rim's
+        let spec = FisheyeSpec::new(1, 180f64, FisheyeMapping::Equidistant, size);
+        let half = spec.direction(DVec2::new(0.5, 0f64)).unwrap();
+        assert!((half.dot(DVec3::NEG_Z) - (PI / 4f64).cos()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shutter() {
+        let size = ImageSize {
+            width: 2,
+            height: 2,
+        };
+        let spec = ShutterSpec::new(Box::new(PinHoleSpec::new(100, 90f64, size)), 1f64, 1.5);
+        let times: Vec<f64> = spec.ray_for_pixel(0, 1).map(|ray| ray.time).collect();
+        assert_eq!(times.len(), 100);
+        assert!(times.iter().all(|t| (1f64..=1.5).contains(t)));
+        // spread over the whole exposure
+        assert!(times.iter().any(|&t| t < 1.1) && times.iter().any(|&t| t > 1.4));
+    }
 }
@@ -0,0 +1,569 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+// Raw, serde-facing layout of a scene file. Vectors and colors are plain
+// arrays so the file stays readable without knowing anything about glam.
+pub type Vec3 = [f64; 3];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub camera: CameraDescription,
+    pub render: RenderDescription,
+    #[serde(default)]
+    pub background: BackgroundDescription,
+    // either replaces the background gradient when present
+    pub environment: Option<Spanned<EnvironmentDescription>>,
+    pub sky: Option<Spanned<SkyDescription>>,
+    pub animation: Option<Spanned<AnimationDescription>>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, Spanned<MaterialDescription>>,
+    #[serde(default)]
+    pub objects: Vec<Spanned<ObjectDescription>>,
+    // objects described once and placed by `instance` objects
+    #[serde(default)]
+    pub prototypes: BTreeMap<String, Spanned<ObjectDescription>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    #[serde(default)]
+    pub position: Vec3,
+    // XYZ euler angles, in degrees
+    pub rotation: Option<Vec3>,
+    // point to look at, instead of giving a rotation
+    pub look_at: Option<Vec3>,
+    #[serde(default = "default_up")]
+    pub up: Vec3,
+}
+
+fn default_up() -> Vec3 {
+    [0f64, 1f64, 0f64]
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            position: Vec3::default(),
+            rotation: None,
+            look_at: None,
+            up: default_up(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RenderDescription {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_samples_per_pixel")]
+    pub samples_per_pixel: usize,
+    // diagonal field of view, in degrees
+    #[serde(default = "default_fov")]
+    pub fov: f64,
+    // lens radius, zero for a pin hole camera with everything in focus
+    #[serde(default)]
+    pub aperture: f64,
+    // distance to the sharp plane, defaults to focusing on `autofocus`
+    pub focus_distance: Option<f64>,
+    // [x, y] of the pixel to focus on, defaults to the center of the image
+    pub autofocus: Option<[u32; 2]>,
+    #[serde(default)]
+    pub projection: ProjectionDescription,
+    // how long the shutter stays open, in seconds, zero for no motion blur
+    #[serde(default)]
+    pub shutter: f64,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ProjectionDescription {
+    // the only one using the fov, aperture and focus settings of the render
+    #[default]
+    Perspective,
+    Orthographic {
+        // height of the view, in world units
+        #[serde(default = "default_view_height")]
+        height: f64,
+    },
+    Equirectangular,
+    Fisheye {
+        // diagonal of the image circle, in degrees
+        #[serde(default = "default_fisheye_fov")]
+        fov: f64,
+        #[serde(default)]
+        mapping: FisheyeMappingDescription,
+    },
+}
+
+impl ProjectionDescription {
+    // With the same defaults as in a scene file
+    pub fn orthographic() -> Self {
+        Self::Orthographic {
+            height: default_view_height(),
+        }
+    }
+
+    pub fn fisheye() -> Self {
+        Self::Fisheye {
+            fov: default_fisheye_fov(),
+            mapping: FisheyeMappingDescription::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FisheyeMappingDescription {
+    #[default]
+    Equidistant,
+    Equisolid,
+}
+
+fn default_view_height() -> f64 {
+    2f64
+}
+
+fn default_fisheye_fov() -> f64 {
+    180f64
+}
+
+fn default_samples_per_pixel() -> usize {
+    100
+}
+
+fn default_fov() -> f64 {
+    90f64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationDescription {
+    pub frames: usize,
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    // the camera either follows keyframes or turns around a target, and
+    // stays still otherwise
+    #[serde(default)]
+    pub keyframes: Vec<KeyframeDescription>,
+    pub turntable: Option<TurntableDescription>,
+}
+
+fn default_fps() -> f64 {
+    24f64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDescription {
+    // in seconds
+    pub time: f64,
+    pub position: Vec3,
+    pub look_at: Vec3,
+    // defaults to the fov of the render
+    pub fov: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TurntableDescription {
+    #[serde(default)]
+    pub target: Vec3,
+    // full turns over the whole animation, negative ones go clockwise seen
+    // from above
+    #[serde(default = "default_turns")]
+    pub turns: f64,
+}
+
+fn default_turns() -> f64 {
+    1f64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackgroundDescription {
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+impl Default for BackgroundDescription {
+    fn default() -> Self {
+        Self {
+            start: [1f64, 1f64, 1f64],
+            end: [0.5, 0.7, 1f64],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDescription {
+    // equirectangular image, relative to the scene file
+    pub path: String,
+    // XYZ euler angles, in degrees
+    #[serde(default)]
+    pub rotation: Vec3,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkyDescription {
+    // angle of the sun above the horizon, in degrees
+    pub sun_elevation: f64,
+    // clockwise angle of the sun from -Z (straight ahead of an unrotated
+    // camera) seen from above, in degrees
+    #[serde(default)]
+    pub sun_azimuth: f64,
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f64,
+    #[serde(default = "default_ground_albedo")]
+    pub ground_albedo: Vec3,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+fn default_turbidity() -> f64 {
+    3f64
+}
+
+fn default_ground_albedo() -> Vec3 {
+    [0.3, 0.3, 0.3]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    SimpleDiffuse,
+    Lambertian {
+        albedo: Vec3,
+    },
+    Metal {
+        albedo: Vec3,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        index_of_refraction: f64,
+    },
+    DiffuseLight {
+        color: Vec3,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+    // Phase functions, only for media. `albedo` is the fraction of the light
+    // scattered rather than absorbed.
+    Isotropic {
+        albedo: Vec3,
+    },
+    // forward scattering for a positive `g`, backward for a negative one
+    HenyeyGreenstein {
+        albedo: Vec3,
+        g: f64,
+    },
+}
+
+fn default_end_time() -> f64 {
+    1f64
+}
+
+fn default_intensity() -> f64 {
+    1f64
+}
+
+// Objects without a material use the one of their group, or a light gray
+// diffuse at the top level
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        // where the sphere is at time zero
+        center: Vec3,
+        radius: f64,
+        material: Option<String>,
+        // where it moves to at `end_time` seconds, for motion blur
+        end_center: Option<Vec3>,
+        #[serde(default = "default_end_time")]
+        end_time: f64,
+    },
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        material: Option<String>,
+    },
+    Rectangle {
+        position: Vec3,
+        // XYZ euler angles, in degrees
+        #[serde(default)]
+        rotation: Vec3,
+        width: f64,
+        height: f64,
+        material: Option<String>,
+    },
+    Triangle {
+        vertices: [Vec3; 3],
+        material: Option<String>,
+    },
+    // axis-aligned box between two opposite corners
+    Cuboid {
+        min: Vec3,
+        max: Vec3,
+        material: Option<String>,
+    },
+    // round and flat, seen from both sides
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+        material: Option<String>,
+    },
+    // closed at both ends
+    Cylinder {
+        bottom: Vec3,
+        top: Vec3,
+        radius: f64,
+        material: Option<String>,
+    },
+    // closed at its base
+    Cone {
+        base: Vec3,
+        apex: Vec3,
+        radius: f64,
+        material: Option<String>,
+    },
+    // ring around `axis`, `major_radius` from the center to the middle of
+    // its tube
+    Torus {
+        center: Vec3,
+        #[serde(default = "default_up")]
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Option<String>,
+    },
+    // Constructive solid geometry, e.g. a lens as the intersection of two
+    // spheres. `a` and `b` are spheres, cuboids, cylinders, cones, tori or
+    // other CSG objects, and take `material` unless they have their own.
+    Union {
+        a: Box<ObjectDescription>,
+        b: Box<ObjectDescription>,
+        material: Option<String>,
+    },
+    Intersection {
+        a: Box<ObjectDescription>,
+        b: Box<ObjectDescription>,
+        material: Option<String>,
+    },
+    // `a` without `b`
+    Difference {
+        a: Box<ObjectDescription>,
+        b: Box<ObjectDescription>,
+        material: Option<String>,
+    },
+    // Surface of a signed distance field, found by sphere tracing. Slower
+    // than the analytic shapes, but can blend shapes together and render
+    // fractals.
+    Sdf {
+        shape: SdfDescription,
+        material: Option<String>,
+        // below one when the tracing misses thin parts
+        #[serde(default = "default_step_scale")]
+        step_scale: f64,
+    },
+    // Surface where a polynomial in x, y and z is zero, e.g. rings, pipes and
+    // other algebraic surfaces, within the box between `min` and `max`.
+    // Negative values are inside.
+    Implicit {
+        terms: Vec<TermDescription>,
+        min: Vec3,
+        max: Vec3,
+        material: Option<String>,
+    },
+    // Smoke or fog filling `boundary`, which is a sphere, cuboid, cylinder,
+    // cone, torus or CSG object. `density` is the chance of light scattering
+    // per unit of length, `material` an isotropic or henyey_greenstein one.
+    // Without one of those, e.g. inheriting a surface material from a group,
+    // it scatters all light equally in every direction.
+    Medium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+        material: Option<String>,
+    },
+    Mesh {
+        vertices: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        material: Option<String>,
+    },
+    // Wavefront OBJ model, relative to the scene file. Faces without an MTL
+    // material use `material`. Placing the same model several times shares
+    // its geometry.
+    Obj {
+        path: String,
+        material: Option<String>,
+        #[serde(default)]
+        position: Vec3,
+        // XYZ euler angles, in degrees
+        #[serde(default)]
+        rotation: Vec3,
+        #[serde(default)]
+        scale: ScaleDescription,
+    },
+    // Objects placed together, e.g. the parts of a table. Children without a
+    // material use the one of the group.
+    Group {
+        name: Option<String>,
+        material: Option<String>,
+        #[serde(default)]
+        position: Vec3,
+        // XYZ euler angles, in degrees
+        #[serde(default)]
+        rotation: Vec3,
+        #[serde(default)]
+        scale: ScaleDescription,
+        // errors in them are reported at the enclosing top-level object, as
+        // spans are lost inside of tagged enums
+        #[serde(default)]
+        children: Vec<ObjectDescription>,
+    },
+    // One of the `prototypes`, placed like a group
+    Instance {
+        prototype: String,
+        name: Option<String>,
+        material: Option<String>,
+        #[serde(default)]
+        position: Vec3,
+        // XYZ euler angles, in degrees
+        #[serde(default)]
+        rotation: Vec3,
+        #[serde(default)]
+        scale: ScaleDescription,
+    },
+}
+
+impl ObjectDescription {
+    pub fn material(&self) -> &Option<String> {
+        match self {
+            Self::Sphere { material, .. }
+            | Self::Plane { material, .. }
+            | Self::Rectangle { material, .. }
+            | Self::Triangle { material, .. }
+            | Self::Cuboid { material, .. }
+            | Self::Disk { material, .. }
+            | Self::Cylinder { material, .. }
+            | Self::Cone { material, .. }
+            | Self::Torus { material, .. }
+            | Self::Union { material, .. }
+            | Self::Intersection { material, .. }
+            | Self::Difference { material, .. }
+            | Self::Sdf { material, .. }
+            | Self::Implicit { material, .. }
+            | Self::Medium { material, .. }
+            | Self::Mesh { material, .. }
+            | Self::Obj { material, .. }
+            | Self::Group { material, .. }
+            | Self::Instance { material, .. } => material,
+        }
+    }
+}
+
+// `coefficient * x^x * y^y * z^z`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TermDescription {
+    pub coefficient: f64,
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    #[serde(default)]
+    pub z: u32,
+}
+
+fn default_step_scale() -> f64 {
+    1f64
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SdfDescription {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+    },
+    // axis-aligned
+    Box {
+        center: Vec3,
+        size: Vec3,
+        // radius of the edges
+        #[serde(default)]
+        rounding: f64,
+    },
+    // ring around the Y axis
+    Torus {
+        center: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f64,
+    },
+    Mandelbulb {
+        center: Vec3,
+        scale: f64,
+        #[serde(default = "default_mandelbulb_power")]
+        power: f64,
+        #[serde(default = "default_mandelbulb_iterations")]
+        iterations: usize,
+    },
+    // `a` and `b` blended where they are closer than `smoothness`
+    SmoothUnion {
+        a: Box<SdfDescription>,
+        b: Box<SdfDescription>,
+        smoothness: f64,
+    },
+    // Copies every `spacing` along each axis, not along the axes where it is
+    // zero. Endless unless `count` copies are given for each side.
+    Repeat {
+        shape: Box<SdfDescription>,
+        spacing: Vec3,
+        count: Option<[u32; 3]>,
+    },
+}
+
+fn default_mandelbulb_power() -> f64 {
+    8f64
+}
+
+fn default_mandelbulb_iterations() -> usize {
+    10
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(untagged)]
+pub enum ScaleDescription {
+    Uniform(f64),
+    PerAxis(Vec3),
+}
+
+impl ScaleDescription {
+    pub fn to_array(self) -> Vec3 {
+        match self {
+            Self::Uniform(s) => [s, s, s],
+            Self::PerAxis(v) => v,
+        }
+    }
+}
+
+impl Default for ScaleDescription {
+    fn default() -> Self {
+        Self::Uniform(1f64)
+    }
+}
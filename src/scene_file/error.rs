@@ -0,0 +1,94 @@
+use std::fmt::{Display, Formatter, Result};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    // both are 1-based, as shown by text editors
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let offset = offset.min(source.len());
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = source[line_start..offset].chars().count() + 1;
+        Self { line, column }
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    message: String,
+    location: Option<Location>,
+}
+
+impl SceneError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    pub fn at(source: &str, span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            location: Some(Location::from_offset(source, span.start)),
+        }
+    }
+
+    pub fn from_toml(source: &str, err: &toml::de::Error) -> Self {
+        Self {
+            message: err.message().trim_end().to_string(),
+            location: err
+                .span()
+                .map(|span| Location::from_offset(source, span.start)),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.location {
+            Some(Location { line, column }) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_from_offset() {
+        let source = "a = 1\nbb = 2\n";
+        assert_eq!(
+            Location::from_offset(source, 0),
+            Location { line: 1, column: 1 }
+        );
+        assert_eq!(
+            Location::from_offset(source, 6),
+            Location { line: 2, column: 1 }
+        );
+        assert_eq!(
+            Location::from_offset(source, 11),
+            Location { line: 2, column: 6 }
+        );
+    }
+}
@@ -0,0 +1,1619 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::{DAffine3, DQuat, DVec3, EulerRot, IVec3};
+use toml::Spanned;
+
+use crate::animation::{Animation, CameraMotion, Frame};
+use crate::camera::Camera;
+use crate::camera_path::{CameraPath, Keyframe};
+use crate::color::LinearRgbColor;
+use crate::materials::{
+    DielectricMaterial, DiffuseLight, HenyeyGreensteinPhase, IsotropicPhase, LambertianMaterial,
+    Material, MetalMaterial, SharedMaterial, SimpleDiffuseMaterial,
+};
+use crate::render_spec::{
+    EquirectangularSpec, FisheyeMapping, FisheyeSpec, ImageSize, OrthographicSpec, PinHoleSpec,
+    RenderSpec, ShutterSpec, ThinLensSpec,
+};
+use crate::world::{
+    load_obj, sdf, Aabb, BvhContainer, BvhStats, ConstantMedium, CsgOperation, Disk, EnvMapScene,
+    EnvironmentMap, FlatScene, ImplicitSurface, InfinitePlane, IntersectContainer, Intersectable,
+    LerpScene, PhysicalSky, PolynomialFunction, Scene, SceneGraph, SceneNode, SdfShape, SharedSdf,
+    SharedShape, SkyScene, Triangle, TriangleMesh,
+};
+
+use super::description::{
+    AnimationDescription, CameraDescription, EnvironmentDescription, FisheyeMappingDescription,
+    MaterialDescription, ObjectDescription, ProjectionDescription, RenderDescription,
+    ScaleDescription, SceneDescription, SdfDescription, SkyDescription, Vec3,
+};
+use super::error::SceneError;
+use super::shapes::{
+    MeshShape, PrimitiveShape, RectangleShape, SolidKind, SolidShape, SphereShape,
+};
+
+pub struct LoadedScene {
+    pub materials: HashMap<String, SharedMaterial>,
+    pub world: Box<dyn Scene>,
+    pub object_count: usize,
+    pub bvh_stats: BvhStats,
+    pub camera: Camera,
+    // Kept as plain settings so that callers can override them before
+    // building the actual render spec
+    pub render: RenderDescription,
+    pub animation: Option<SceneAnimation>,
+    // the objects as described, for querying. The world is built from it.
+    pub graph: SceneGraph,
+}
+
+pub struct SceneAnimation {
+    pub frames: usize,
+    pub fps: f64,
+    // None for a still camera
+    pub camera: Option<CameraMotion>,
+}
+
+impl SceneAnimation {
+    pub fn duration(&self) -> f64 {
+        self.frames as f64 / self.fps
+    }
+}
+
+impl LoadedScene {
+    pub fn render_spec(&self) -> Box<dyn RenderSpec> {
+        self.render_spec_for(&self.camera, self.render.fov, 0f64)
+    }
+
+    // With the camera and fov of an animation frame instead of the scene
+    // ones, and the shutter opening at `time`. Rays are stamped with the time
+    // even when the shutter closes at once, so moving objects are seen where
+    // they are at each frame.
+    pub fn render_spec_for(&self, camera: &Camera, fov: f64, time: f64) -> Box<dyn RenderSpec> {
+        let spec = self.projection_spec(camera, fov);
+        Box::new(ShutterSpec::new(spec, time, time + self.render.shutter))
+    }
+
+    fn projection_spec(&self, camera: &Camera, fov: f64) -> Box<dyn RenderSpec> {
+        let render = &self.render;
+        let size = ImageSize {
+            width: render.width,
+            height: render.height,
+        };
+        let spp = render.samples_per_pixel;
+        match render.projection {
+            ProjectionDescription::Perspective => {}
+            ProjectionDescription::Orthographic { height } => {
+                return Box::new(OrthographicSpec::new(spp, height, size));
+            }
+            ProjectionDescription::Equirectangular => {
+                return Box::new(EquirectangularSpec::new(spp, size));
+            }
+            ProjectionDescription::Fisheye { fov, mapping } => {
+                let mapping = match mapping {
+                    FisheyeMappingDescription::Equidistant => FisheyeMapping::Equidistant,
+                    FisheyeMappingDescription::Equisolid => FisheyeMapping::Equisolid,
+                };
+                return Box::new(FisheyeSpec::new(spp, fov, mapping, size));
+            }
+        }
+        let pinhole = PinHoleSpec::new(spp, fov, size);
+        if render.aperture <= 0f64 {
+            return Box::new(pinhole);
+        }
+        match render.focus_distance {
+            Some(focus_distance) => {
+                Box::new(ThinLensSpec::new(pinhole, render.aperture, focus_distance))
+            }
+            None => {
+                let [x, y] = render
+                    .autofocus
+                    .unwrap_or([render.width / 2, render.height / 2]);
+                Box::new(
+                    ThinLensSpec::new(pinhole, render.aperture, DEFAULT_FOCUS_DISTANCE)
+                        .with_autofocus(camera, &*self.world, x, y),
+                )
+            }
+        }
+    }
+}
+
+// A scene without an [animation] section is a single still frame
+impl Animation for LoadedScene {
+    fn frame_count(&self) -> usize {
+        self.animation.as_ref().map_or(1, |a| a.frames)
+    }
+
+    fn frame(&self, index: usize) -> Frame<'_> {
+        let mut time = 0f64;
+        let mut camera = self.camera;
+        let mut fov = self.render.fov;
+        if let Some(animation) = &self.animation {
+            time = index as f64 / animation.fps;
+            if let Some(motion) = &animation.camera {
+                time += motion.start_time();
+                let duration = animation.duration();
+                let pose = motion.pose(&self.camera, fov, time, duration);
+                camera = pose.camera;
+                fov = pose.fov;
+                let shutter = self.render.shutter;
+                if shutter > 0f64 {
+                    // blurred by its own motion while the shutter is open
+                    let end = motion.pose(&self.camera, fov, time + shutter, duration);
+                    camera = camera.with_motion(&end.camera, time, time + shutter);
+                }
+            }
+        }
+        Frame {
+            time,
+            render_spec: self.render_spec_for(&camera, fov, time),
+            camera,
+            world: &*self.world,
+        }
+    }
+}
+
+// Used when autofocus finds nothing to focus on
+const DEFAULT_FOCUS_DISTANCE: f64 = 1f64;
+
+pub fn load_scene(path: impl AsRef<Path>) -> Result<LoadedScene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|e| SceneError::new(format!("cannot read {}: {}", path.display(), e)))?;
+    parse_scene_in(&source, path.parent().unwrap_or(Path::new(".")))
+}
+
+pub fn parse_scene(source: &str) -> Result<LoadedScene, SceneError> {
+    parse_scene_in(source, Path::new("."))
+}
+
+// Relative paths in the scene (e.g. OBJ models) are resolved against `base_dir`
+pub fn parse_scene_in(source: &str, base_dir: &Path) -> Result<LoadedScene, SceneError> {
+    let description: SceneDescription =
+        toml::from_str(source).map_err(|e| SceneError::from_toml(source, &e))?;
+    SceneBuilder {
+        source,
+        base_dir,
+        default_material: LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::splat(
+            0.8,
+        ))),
+        models: RefCell::new(HashMap::new()),
+        prototypes: RefCell::new(HashMap::new()),
+        building: RefCell::new(vec![]),
+    }
+    .build(description)
+}
+
+fn to_dvec3(v: Vec3) -> DVec3 {
+    DVec3::from_array(v)
+}
+
+fn to_color(v: Vec3) -> LinearRgbColor {
+    LinearRgbColor::new(v[0], v[1], v[2])
+}
+
+fn euler_degrees_to_quat(v: Vec3) -> DQuat {
+    DQuat::from_euler(
+        EulerRot::XYZ,
+        v[0].to_radians(),
+        v[1].to_radians(),
+        v[2].to_radians(),
+    )
+}
+
+fn build_camera(camera: &CameraDescription) -> Result<Camera, SceneError> {
+    let position = to_dvec3(camera.position);
+    match (camera.rotation, camera.look_at) {
+        (Some(_), Some(_)) => Err(SceneError::new(
+            "camera has either a rotation or a look_at point",
+        )),
+        (_, Some(target)) => {
+            let target = to_dvec3(target);
+            if target == position {
+                return Err(SceneError::new("camera look_at is the camera position"));
+            }
+            Ok(Camera::look_at(position, target, to_dvec3(camera.up)))
+        }
+        (rotation, None) => Ok(Camera::new(
+            position,
+            euler_degrees_to_quat(rotation.unwrap_or_default()),
+        )),
+    }
+}
+
+// Also run by callers overriding the render settings of a loaded scene
+pub fn validate_render(render: &RenderDescription) -> Result<(), SceneError> {
+    if !(render.fov > 0f64 && render.fov < 180f64) {
+        return Err(SceneError::new(
+            "render fov must be between 0 and 180 degrees",
+        ));
+    }
+    match render.projection {
+        ProjectionDescription::Orthographic { height } if height.is_nan() || height <= 0f64 => {
+            return Err(SceneError::new("orthographic height must be positive"));
+        }
+        ProjectionDescription::Fisheye { fov, .. } if !(fov > 0f64 && fov <= 360f64) => {
+            return Err(SceneError::new(
+                "fisheye fov must be between 0 and 360 degrees",
+            ));
+        }
+        _ => {}
+    }
+    if render.aperture.is_nan() || render.aperture < 0f64 {
+        return Err(SceneError::new("render aperture cannot be negative"));
+    }
+    if render
+        .focus_distance
+        .is_some_and(|d| d.is_nan() || d <= 0f64)
+    {
+        return Err(SceneError::new("render focus_distance must be positive"));
+    }
+    if render
+        .autofocus
+        .is_some_and(|[x, y]| x >= render.width || y >= render.height)
+    {
+        return Err(SceneError::new(
+            "render autofocus pixel lies outside of the image",
+        ));
+    }
+    if render.shutter.is_nan() || render.shutter < 0f64 {
+        return Err(SceneError::new("render shutter must not be negative"));
+    }
+    Ok(())
+}
+
+// Keeps the source text around so that every semantic error can be reported
+// with the position of the offending entry.
+struct SceneBuilder<'a> {
+    source: &'a str,
+    base_dir: &'a Path,
+    // for objects without a material, here or in any of their groups
+    default_material: SharedMaterial,
+    models: RefCell<HashMap<PathBuf, SharedShape>>,
+    prototypes: RefCell<HashMap<String, SceneNode>>,
+    // prototypes being built, to catch the ones placing themselves
+    building: RefCell<Vec<String>>,
+}
+
+impl<'a> SceneBuilder<'a> {
+    fn error_at<T>(&self, spanned: &Spanned<T>, message: impl Into<String>) -> SceneError {
+        SceneError::at(self.source, spanned.span(), message)
+    }
+
+    fn build(&self, description: SceneDescription) -> Result<LoadedScene, SceneError> {
+        let render = &description.render;
+        if render.width == 0 || render.height == 0 {
+            return Err(SceneError::new("render width and height must be positive"));
+        }
+        if render.samples_per_pixel == 0 {
+            return Err(SceneError::new("render samples_per_pixel must be positive"));
+        }
+        validate_render(render)?;
+        let camera = build_camera(&description.camera)?;
+        let animation = match &description.animation {
+            Some(animation) => Some(self.build_animation(animation, render)?),
+            None => None,
+        };
+
+        let mut materials = HashMap::new();
+        for (name, material) in &description.materials {
+            materials.insert(name.clone(), self.build_material(material)?);
+        }
+
+        // unused prototypes are still checked for errors
+        for (name, prototype) in &description.prototypes {
+            self.prototype(prototype, name, &materials, &description.prototypes)?;
+        }
+        let mut nodes = vec![];
+        for object in &description.objects {
+            nodes.push(self.build_node(
+                object,
+                object.get_ref(),
+                &materials,
+                &description.prototypes,
+            )?);
+        }
+        let graph = SceneGraph::new(SceneNode::group(nodes), &self.default_material);
+
+        let FlatScene { objects, lights } = graph.flatten();
+        let container = BvhContainer::from_iter(objects);
+        let object_count = container.len();
+        let bvh_stats = container.stats();
+        let world: Box<dyn Scene> = match (&description.environment, &description.sky) {
+            (Some(_), Some(sky)) => {
+                return Err(self.error_at(sky, "a scene has either an environment or a sky"));
+            }
+            (Some(environment), None) => Box::new(
+                EnvMapScene::new(container, self.build_environment(environment)?)
+                    .with_lights(lights),
+            ),
+            (None, Some(sky)) => {
+                Box::new(SkyScene::new(container, self.build_sky(sky)?).with_lights(lights))
+            }
+            (None, None) => Box::new(
+                LerpScene::new(
+                    container,
+                    to_color(description.background.start),
+                    to_color(description.background.end),
+                )
+                .with_lights(lights),
+            ),
+        };
+
+        Ok(LoadedScene {
+            materials,
+            world,
+            object_count,
+            bvh_stats,
+            camera,
+            render: description.render,
+            animation,
+            graph,
+        })
+    }
+
+    fn build_animation(
+        &self,
+        animation: &Spanned<AnimationDescription>,
+        render: &RenderDescription,
+    ) -> Result<SceneAnimation, SceneError> {
+        let description = animation.get_ref();
+        if description.frames == 0 {
+            return Err(self.error_at(animation, "animation frames must be positive"));
+        }
+        if description.fps.is_nan() || description.fps <= 0f64 {
+            return Err(self.error_at(animation, "animation fps must be positive"));
+        }
+        let camera = match (&description.keyframes[..], &description.turntable) {
+            ([], None) => None,
+            ([], Some(turntable)) => Some(CameraMotion::Turntable {
+                target: to_dvec3(turntable.target),
+                turns: turntable.turns,
+            }),
+            (_, Some(_)) => {
+                return Err(self.error_at(
+                    animation,
+                    "an animation has either camera keyframes or a turntable",
+                ));
+            }
+            (keyframes, None) => {
+                let mut path = vec![];
+                for keyframe in keyframes {
+                    let fov = keyframe.fov.unwrap_or(render.fov);
+                    if !(fov > 0f64 && fov < 180f64) {
+                        return Err(self.error_at(
+                            animation,
+                            "keyframe fov must be between 0 and 180 degrees",
+                        ));
+                    }
+                    if keyframe.position == keyframe.look_at {
+                        return Err(
+                            self.error_at(animation, "keyframe look_at is the camera position")
+                        );
+                    }
+                    path.push(Keyframe {
+                        time: keyframe.time,
+                        position: to_dvec3(keyframe.position),
+                        target: to_dvec3(keyframe.look_at),
+                        fov,
+                    });
+                }
+                Some(CameraMotion::Path(CameraPath::new(path)))
+            }
+        };
+        Ok(SceneAnimation {
+            frames: description.frames,
+            fps: description.fps,
+            camera,
+        })
+    }
+
+    fn build_environment(
+        &self,
+        environment: &Spanned<EnvironmentDescription>,
+    ) -> Result<EnvironmentMap, SceneError> {
+        let description = environment.get_ref();
+        let path = self.base_dir.join(&description.path);
+        let map = EnvironmentMap::load(&path).map_err(|e| {
+            self.error_at(
+                environment,
+                format!("cannot load environment map {}: {}", path.display(), e),
+            )
+        })?;
+        Ok(map
+            .with_rotation(euler_degrees_to_quat(description.rotation))
+            .with_intensity(description.intensity))
+    }
+
+    fn build_sky(&self, sky: &Spanned<SkyDescription>) -> Result<PhysicalSky, SceneError> {
+        let description = sky.get_ref();
+        if !(2f64..=10f64).contains(&description.turbidity) {
+            return Err(self.error_at(sky, "sky turbidity must be between 2 and 10"));
+        }
+        let elevation = description.sun_elevation.to_radians();
+        let azimuth = description.sun_azimuth.to_radians();
+        let sun_direction = DVec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        Ok(PhysicalSky::new(sun_direction, description.turbidity)
+            .with_ground_albedo(to_dvec3(description.ground_albedo))
+            .with_intensity(description.intensity))
+    }
+
+    fn build_material(
+        &self,
+        material: &Spanned<MaterialDescription>,
+    ) -> Result<SharedMaterial, SceneError> {
+        let shared = match *material.get_ref() {
+            MaterialDescription::SimpleDiffuse => {
+                SimpleDiffuseMaterial::make_shared(SimpleDiffuseMaterial::new())
+            }
+            MaterialDescription::Lambertian { albedo } => {
+                LambertianMaterial::make_shared(LambertianMaterial::new(to_dvec3(albedo)))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                MetalMaterial::make_shared(MetalMaterial::new(to_dvec3(albedo), fuzz))
+            }
+            MaterialDescription::Dielectric {
+                index_of_refraction,
+            } => {
+                if index_of_refraction.is_nan() || index_of_refraction <= 0f64 {
+                    return Err(self.error_at(material, "index_of_refraction must be positive"));
+                }
+                DielectricMaterial::make_shared(DielectricMaterial::new(index_of_refraction))
+            }
+            MaterialDescription::DiffuseLight { color, intensity } => {
+                if intensity.is_nan() || intensity < 0f64 {
+                    return Err(self.error_at(material, "light intensity must not be negative"));
+                }
+                DiffuseLight::make_shared(DiffuseLight::new(to_color(color), intensity))
+            }
+            MaterialDescription::Isotropic { albedo } => {
+                IsotropicPhase::make_shared(IsotropicPhase::new(to_dvec3(albedo)))
+            }
+            MaterialDescription::HenyeyGreenstein { albedo, g } => {
+                if !(-1f64 < g && g < 1f64) {
+                    return Err(self.error_at(material, "g must be between -1 and 1"));
+                }
+                HenyeyGreensteinPhase::make_shared(HenyeyGreensteinPhase::new(to_dvec3(albedo), g))
+            }
+        };
+        Ok(shared)
+    }
+
+    fn lookup_material(
+        &self,
+        object: &Spanned<ObjectDescription>,
+        materials: &HashMap<String, SharedMaterial>,
+        name: &str,
+    ) -> Result<SharedMaterial, SceneError> {
+        materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| self.error_at(object, format!("unknown material `{}`", name)))
+    }
+
+    fn node_material(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        materials: &HashMap<String, SharedMaterial>,
+        name: &Option<String>,
+    ) -> Result<Option<SharedMaterial>, SceneError> {
+        name.as_ref()
+            .map(|name| self.lookup_material(at, materials, name))
+            .transpose()
+    }
+
+    fn placement(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        kind: &str,
+        position: Vec3,
+        rotation: Vec3,
+        scale: ScaleDescription,
+    ) -> Result<DAffine3, SceneError> {
+        let scale = to_dvec3(scale.to_array());
+        if scale.cmpeq(DVec3::ZERO).any() {
+            return Err(self.error_at(at, format!("{} scale must not be zero", kind)));
+        }
+        Ok(DAffine3::from_scale_rotation_translation(
+            scale,
+            euler_degrees_to_quat(rotation),
+            to_dvec3(position),
+        ))
+    }
+
+    fn build_node(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        object: &ObjectDescription,
+        materials: &HashMap<String, SharedMaterial>,
+        prototypes: &BTreeMap<String, Spanned<ObjectDescription>>,
+    ) -> Result<SceneNode, SceneError> {
+        let (mut node, material) = match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+                end_center,
+                end_time,
+            } => {
+                let shape = self.sphere_shape(at, *center, *radius, *end_center, *end_time)?;
+                (SceneNode::shape(Arc::new(shape)), material)
+            }
+            ObjectDescription::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                let normal = to_dvec3(*normal);
+                if normal.length_squared() == 0f64 {
+                    return Err(self.error_at(at, "plane normal must not be zero"));
+                }
+                let point = to_dvec3(*point);
+                let normal = normal.normalize();
+                let node = self.primitive(move |m| InfinitePlane::new(point, normal, m));
+                (node, material)
+            }
+            ObjectDescription::Rectangle {
+                position,
+                rotation,
+                width,
+                height,
+                material,
+            } => {
+                if !(*width > 0f64 && *height > 0f64) {
+                    return Err(self.error_at(at, "rectangle width and height must be positive"));
+                }
+                let shape = RectangleShape {
+                    position: to_dvec3(*position),
+                    rotation: euler_degrees_to_quat(*rotation),
+                    width: *width,
+                    height: *height,
+                };
+                (SceneNode::shape(Arc::new(shape)), material)
+            }
+            ObjectDescription::Triangle { vertices, material } => {
+                let [p0, p1, p2] = vertices.map(to_dvec3);
+                let node = self.primitive(move |m| Triangle::new(p0, p1, p2, m));
+                (node, material)
+            }
+            ObjectDescription::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                let normal = to_dvec3(*normal);
+                if normal.length_squared() == 0f64 {
+                    return Err(self.error_at(at, "disk normal must not be zero"));
+                }
+                if radius.is_nan() || *radius <= 0f64 {
+                    return Err(self.error_at(at, "disk radius must be positive"));
+                }
+                let (center, radius) = (to_dvec3(*center), *radius);
+                let node = self.primitive(move |m| Disk::new(center, normal, radius, m));
+                (node, material)
+            }
+            ObjectDescription::Cuboid { material, .. }
+            | ObjectDescription::Cylinder { material, .. }
+            | ObjectDescription::Cone { material, .. }
+            | ObjectDescription::Torus { material, .. }
+            | ObjectDescription::Union { material, .. }
+            | ObjectDescription::Intersection { material, .. }
+            | ObjectDescription::Difference { material, .. } => {
+                let solid = self.build_solid(at, object, materials)?;
+                (self.primitive(move |m| solid.build(m)), material)
+            }
+            ObjectDescription::Sdf {
+                shape,
+                material,
+                step_scale,
+            } => {
+                if !(*step_scale > 0f64 && *step_scale <= 1f64) {
+                    return Err(self.error_at(at, "sdf step_scale must be between 0 and 1"));
+                }
+                let (sdf, step_scale) = (self.build_sdf(at, shape)?, *step_scale);
+                let node = self
+                    .primitive(move |m| SdfShape::new(sdf.clone(), m).with_step_scale(step_scale));
+                (node, material)
+            }
+            ObjectDescription::Implicit {
+                terms,
+                min,
+                max,
+                material,
+            } => {
+                if terms.is_empty() {
+                    return Err(self.error_at(at, "implicit surface needs at least one term"));
+                }
+                let bounds = Aabb::from_points([to_dvec3(*min), to_dvec3(*max)]);
+                let terms: Vec<_> = terms
+                    .iter()
+                    .map(|term| (term.coefficient, [term.x, term.y, term.z]))
+                    .collect();
+                let node = self.primitive(move |m| {
+                    ImplicitSurface::new(PolynomialFunction::new(terms.clone()), bounds, m)
+                });
+                (node, material)
+            }
+            ObjectDescription::Medium {
+                boundary,
+                density,
+                material,
+            } => {
+                if density.is_nan() || *density <= 0f64 {
+                    return Err(self.error_at(at, "medium density must be positive"));
+                }
+                let phase = self.node_material(at, materials, material)?;
+                if phase.is_some_and(|phase| !phase.is_volumetric()) {
+                    return Err(
+                        self.error_at(at, "medium material must be isotropic or henyey_greenstein")
+                    );
+                }
+                // the material of the boundary never shows
+                let (boundary, density) = (self.build_solid(at, boundary, materials)?, *density);
+                let white = IsotropicPhase::make_shared(IsotropicPhase::new(DVec3::ONE));
+                let node = self.primitive(move |m| {
+                    // inherited, or the default one, which cannot scatter
+                    // inside of a volume
+                    let phase = if m.is_volumetric() { m } else { &white };
+                    ConstantMedium::new(boundary.build(m), density, phase)
+                });
+                (node, material)
+            }
+            ObjectDescription::Mesh {
+                vertices,
+                indices,
+                material,
+            } => {
+                if indices.iter().flatten().any(|&i| i >= vertices.len()) {
+                    return Err(self.error_at(at, "mesh index out of the vertex list"));
+                }
+                let mesh = TriangleMesh::new(
+                    vertices.iter().copied().map(to_dvec3).collect(),
+                    indices.clone(),
+                    &self.default_material,
+                );
+                let shape = MeshShape { meshes: vec![mesh] };
+                (SceneNode::shape(Arc::new(shape)), material)
+            }
+            ObjectDescription::Obj {
+                path,
+                material,
+                position,
+                rotation,
+                scale,
+            } => {
+                let transform = self.placement(at, "obj", *position, *rotation, *scale)?;
+                let node = SceneNode::shape(self.load_model(at, path)?);
+                (node.with_transform(transform), material)
+            }
+            ObjectDescription::Group {
+                name,
+                material,
+                position,
+                rotation,
+                scale,
+                children,
+            } => {
+                let transform = self.placement(at, "group", *position, *rotation, *scale)?;
+                let mut nodes = vec![];
+                for child in children {
+                    nodes.push(self.build_node(at, child, materials, prototypes)?);
+                }
+                let mut node = SceneNode::group(nodes).with_transform(transform);
+                if let Some(name) = name {
+                    node = node.with_name(name);
+                }
+                (node, material)
+            }
+            ObjectDescription::Instance {
+                prototype,
+                name,
+                material,
+                position,
+                rotation,
+                scale,
+            } => {
+                let transform = self.placement(at, "instance", *position, *rotation, *scale)?;
+                let prototype = self.prototype(at, prototype, materials, prototypes)?;
+                let mut node = SceneNode::group(vec![prototype]).with_transform(transform);
+                if let Some(name) = name {
+                    node = node.with_name(name);
+                }
+                (node, material)
+            }
+        };
+        node.set_material(self.node_material(at, materials, material)?);
+        Ok(node)
+    }
+
+    fn sphere_shape(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        center: Vec3,
+        radius: f64,
+        end_center: Option<Vec3>,
+        end_time: f64,
+    ) -> Result<SphereShape, SceneError> {
+        if radius.is_nan() || radius <= 0f64 {
+            return Err(self.error_at(at, "sphere radius must be positive"));
+        }
+        if end_center.is_some() && (end_time.is_nan() || end_time <= 0f64) {
+            return Err(self.error_at(at, "sphere end_time must be positive"));
+        }
+        Ok(SphereShape {
+            center: to_dvec3(center),
+            radius,
+            motion: end_center.map(|end_center| (to_dvec3(end_center), end_time)),
+        })
+    }
+
+    fn build_solid(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        object: &ObjectDescription,
+        materials: &HashMap<String, SharedMaterial>,
+    ) -> Result<SolidKind, SceneError> {
+        let (operation, a, b) = match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                end_center,
+                end_time,
+                ..
+            } => {
+                let shape = self.sphere_shape(at, *center, *radius, *end_center, *end_time)?;
+                return Ok(SolidKind::Sphere(shape));
+            }
+            ObjectDescription::Cuboid { min, max, .. } => {
+                return Ok(SolidKind::Cuboid(to_dvec3(*min), to_dvec3(*max)));
+            }
+            ObjectDescription::Cylinder {
+                bottom,
+                top,
+                radius,
+                ..
+            } => {
+                if bottom == top {
+                    return Err(self.error_at(at, "cylinder bottom and top must differ"));
+                }
+                if radius.is_nan() || *radius <= 0f64 {
+                    return Err(self.error_at(at, "cylinder radius must be positive"));
+                }
+                return Ok(SolidKind::Cylinder(
+                    to_dvec3(*bottom),
+                    to_dvec3(*top),
+                    *radius,
+                ));
+            }
+            ObjectDescription::Cone {
+                base, apex, radius, ..
+            } => {
+                if base == apex {
+                    return Err(self.error_at(at, "cone base and apex must differ"));
+                }
+                if radius.is_nan() || *radius <= 0f64 {
+                    return Err(self.error_at(at, "cone radius must be positive"));
+                }
+                return Ok(SolidKind::Cone(to_dvec3(*base), to_dvec3(*apex), *radius));
+            }
+            ObjectDescription::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                ..
+            } => {
+                let axis = to_dvec3(*axis);
+                if axis.length_squared() == 0f64 {
+                    return Err(self.error_at(at, "torus axis must not be zero"));
+                }
+                // beyond that the tube overlaps itself around the hole
+                if !(*minor_radius > 0f64 && minor_radius < major_radius) {
+                    return Err(self.error_at(
+                        at,
+                        "torus minor_radius must be positive and smaller than major_radius",
+                    ));
+                }
+                return Ok(SolidKind::Torus(
+                    to_dvec3(*center),
+                    axis,
+                    *major_radius,
+                    *minor_radius,
+                ));
+            }
+            ObjectDescription::Union { a, b, .. } => (CsgOperation::Union, a, b),
+            ObjectDescription::Intersection { a, b, .. } => (CsgOperation::Intersection, a, b),
+            ObjectDescription::Difference { a, b, .. } => (CsgOperation::Difference, a, b),
+            _ => {
+                return Err(self.error_at(
+                    at,
+                    "only spheres, cuboids, cylinders, cones, tori and CSG objects can be combined or hold a medium",
+                ));
+            }
+        };
+        let operand = |operand: &ObjectDescription| -> Result<_, SceneError> {
+            Ok(Box::new(SolidShape {
+                material: self.node_material(at, materials, operand.material())?,
+                kind: self.build_solid(at, operand, materials)?,
+            }))
+        };
+        Ok(SolidKind::Csg(operation, operand(a)?, operand(b)?))
+    }
+
+    fn build_sdf(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        shape: &SdfDescription,
+    ) -> Result<SharedSdf, SceneError> {
+        let positive = |value: f64, what: &str| {
+            if value > 0f64 {
+                Ok(())
+            } else {
+                Err(self.error_at(at, format!("sdf {} must be positive", what)))
+            }
+        };
+        Ok(match shape {
+            SdfDescription::Sphere { center, radius } => {
+                positive(*radius, "sphere radius")?;
+                Arc::new(sdf::Sphere {
+                    center: to_dvec3(*center),
+                    radius: *radius,
+                })
+            }
+            SdfDescription::Box {
+                center,
+                size,
+                rounding,
+            } => {
+                let size = to_dvec3(*size);
+                positive(size.min_element(), "box size")?;
+                if *rounding < 0f64 || *rounding * 2f64 > size.min_element() {
+                    return Err(self.error_at(at, "sdf box rounding must fit in the box"));
+                }
+                Arc::new(sdf::Cuboid {
+                    center: to_dvec3(*center),
+                    half_size: size * 0.5,
+                    rounding: *rounding,
+                })
+            }
+            SdfDescription::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                positive(*major_radius, "torus major_radius")?;
+                positive(*minor_radius, "torus minor_radius")?;
+                Arc::new(sdf::Torus {
+                    center: to_dvec3(*center),
+                    major_radius: *major_radius,
+                    minor_radius: *minor_radius,
+                })
+            }
+            SdfDescription::Capsule { a, b, radius } => {
+                positive(*radius, "capsule radius")?;
+                Arc::new(sdf::Capsule {
+                    a: to_dvec3(*a),
+                    b: to_dvec3(*b),
+                    radius: *radius,
+                })
+            }
+            SdfDescription::Mandelbulb {
+                center,
+                scale,
+                power,
+                iterations,
+            } => {
+                positive(*scale, "mandelbulb scale")?;
+                if power.is_nan() || *power < 2f64 {
+                    return Err(self.error_at(at, "sdf mandelbulb power must be at least 2"));
+                }
+                Arc::new(sdf::Mandelbulb {
+                    power: *power,
+                    iterations: *iterations,
+                    ..sdf::Mandelbulb::new(to_dvec3(*center), *scale)
+                })
+            }
+            SdfDescription::SmoothUnion { a, b, smoothness } => {
+                if *smoothness < 0f64 {
+                    return Err(self.error_at(at, "sdf smoothness must not be negative"));
+                }
+                Arc::new(sdf::SmoothUnion {
+                    a: self.build_sdf(at, a)?,
+                    b: self.build_sdf(at, b)?,
+                    smoothness: *smoothness,
+                })
+            }
+            SdfDescription::Repeat {
+                shape,
+                spacing,
+                count,
+            } => {
+                let spacing = to_dvec3(*spacing);
+                if spacing.min_element() < 0f64 {
+                    return Err(self.error_at(at, "sdf repeat spacing must not be negative"));
+                }
+                Arc::new(sdf::Repeat::new(
+                    self.build_sdf(at, shape)?,
+                    spacing,
+                    count.map(|c| IVec3::from_array(c.map(|n| n as i32))),
+                ))
+            }
+        })
+    }
+
+    // A leaf for a primitive without any special handling
+    fn primitive<I: Intersectable + 'static>(
+        &self,
+        build: impl Fn(&SharedMaterial) -> I + Send + Sync + 'static,
+    ) -> SceneNode {
+        let shape = PrimitiveShape::new(move |m| build(m).into_box(), &self.default_material);
+        SceneNode::shape(Arc::new(shape))
+    }
+
+    // Built the first time it is placed, then copied. Copies share the shapes
+    // of the prototype, and with them the geometry of its models.
+    fn prototype(
+        &self,
+        at: &Spanned<ObjectDescription>,
+        name: &str,
+        materials: &HashMap<String, SharedMaterial>,
+        prototypes: &BTreeMap<String, Spanned<ObjectDescription>>,
+    ) -> Result<SceneNode, SceneError> {
+        if let Some(node) = self.prototypes.borrow().get(name) {
+            return Ok(node.clone());
+        }
+        let Some(description) = prototypes.get(name) else {
+            return Err(self.error_at(at, format!("unknown prototype `{}`", name)));
+        };
+        if self.building.borrow().iter().any(|n| n == name) {
+            return Err(self.error_at(at, format!("prototype `{}` contains itself", name)));
+        }
+        self.building.borrow_mut().push(name.to_string());
+        let node = self.build_node(description, description.get_ref(), materials, prototypes);
+        self.building.borrow_mut().pop();
+        let node = node?;
+        self.prototypes
+            .borrow_mut()
+            .insert(name.to_string(), node.clone());
+        Ok(node)
+    }
+
+    // Loaded once per path, so that every placement shares the same geometry
+    fn load_model(
+        &self,
+        object: &Spanned<ObjectDescription>,
+        path: &str,
+    ) -> Result<SharedShape, SceneError> {
+        let path = self.base_dir.join(path);
+        if let Some(model) = self.models.borrow().get(&path) {
+            return Ok(model.clone());
+        }
+        let objects = load_obj(&path, &self.default_material)
+            .map_err(|e| self.error_at(object, e.to_string()))?;
+        let model: SharedShape = Arc::new(MeshShape {
+            meshes: objects.into_iter().map(|object| object.mesh).collect(),
+        });
+        self.models.borrow_mut().insert(path, model.clone());
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::utils::Interval;
+    use crate::world::IntersectRecord;
+
+    const MINIMAL: &str = r#"
+[render]
+width = 4
+height = 2
+
+[materials.ground]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -1.0]
+radius = 0.5
+material = "ground"
+"#;
+
+    // MINIMAL followed by more materials and objects
+    fn with_minimal(more: &str) -> String {
+        format!("{}{}", MINIMAL, more)
+    }
+
+    // First hit looking down -Z from `origin`, beyond `min_t`
+    fn hit_from(scene: &LoadedScene, origin: DVec3, min_t: f64) -> Option<IntersectRecord> {
+        let ray = Ray::new(origin, DVec3::NEG_Z);
+        scene.world.hit(&ray, &Interval::greater_than(min_t))
+    }
+
+    #[test]
+    fn test_parse_minimal_scene() {
+        let scene = parse_scene(MINIMAL).unwrap();
+        assert_eq!(scene.materials.len(), 1);
+        assert!(scene.materials.contains_key("ground"));
+        assert_eq!(scene.render_spec().image_size().width, 4);
+        assert_eq!(scene.render_spec().image_size().height, 2);
+    }
+
+    #[test]
+    fn test_unknown_material_location() {
+        let source = MINIMAL.replace("material = \"ground\"", "material = \"gold\"");
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("gold"), "{}", err);
+        // points at the `[[objects]]` entry
+        assert_eq!(err.location().unwrap().line, 10);
+    }
+
+    #[test]
+    fn test_nan_radius() {
+        // TOML reads `nan` as a float, it must not slip past `radius <= 0`
+        let source = MINIMAL.replace("radius = 0.5", "radius = nan");
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("sphere radius"), "{}", err);
+    }
+
+    #[test]
+    fn test_nan_materials() {
+        // a NaN light would turn every pixel seeing it NaN
+        for (material, message) in [
+            (
+                "type = \"diffuse_light\"\ncolor = [1.0, 1.0, 1.0]\nintensity = nan",
+                "intensity",
+            ),
+            (
+                "type = \"dielectric\"\nindex_of_refraction = nan",
+                "index_of_refraction",
+            ),
+        ] {
+            let source = format!("{}\n[materials.broken]\n{}\n", MINIMAL, material);
+            let err = parse_scene(&source).err().unwrap();
+            assert!(err.message().contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let source = "[render]\nwidth = 4\nheight = \n";
+        let err = parse_scene(source).err().unwrap();
+        assert_eq!(err.location().unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_missing_environment_map() {
+        let source = format!("{}\n[environment]\npath = \"missing.hdr\"\n", MINIMAL);
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("missing.hdr"), "{}", err);
+        assert!(err.location().is_some());
+    }
+
+    #[test]
+    fn test_sky() {
+        let source = format!("{}\n[sky]\nsun_elevation = 30.0\n", MINIMAL);
+        assert!(parse_scene(&source).is_ok());
+
+        let source = format!(
+            "{}\n[sky]\nsun_elevation = 30.0\nturbidity = 20.0\n",
+            MINIMAL
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("turbidity"), "{}", err);
+
+        let source = format!(
+            "{}\n[environment]\npath = \"studio.hdr\"\n\n[sky]\nsun_elevation = 30.0\n",
+            MINIMAL
+        );
+        assert!(parse_scene(&source).is_err());
+    }
+
+    #[test]
+    fn test_camera_look_at() {
+        let source = format!(
+            "[camera]\nposition = [0.0, 2.0, 2.0]\nlook_at = [0.0, 0.0, 0.0]\n{}",
+            MINIMAL
+        );
+        let scene = parse_scene(&source).unwrap();
+        let forward = scene.camera.rotation() * DVec3::NEG_Z;
+        assert!((forward - DVec3::new(0f64, -1f64, -1f64).normalize()).length() < 1e-9);
+
+        let source = format!(
+            "[camera]\nrotation = [10.0, 0.0, 0.0]\nlook_at = [0.0, 0.0, -1.0]\n{}",
+            MINIMAL
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("look_at"), "{}", err);
+    }
+
+    #[test]
+    fn test_animation() {
+        let scene = parse_scene(MINIMAL).unwrap();
+        assert_eq!(scene.frame_count(), 1);
+
+        let source = with_minimal(
+            r#"
+[animation]
+frames = 3
+fps = 2.0
+
+[[animation.keyframes]]
+time = 1.0
+position = [0.0, 0.0, 2.0]
+look_at = [0.0, 0.0, 0.0]
+
+[[animation.keyframes]]
+time = 2.0
+position = [2.0, 0.0, 0.0]
+look_at = [0.0, 0.0, 0.0]
+fov = 40.0
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        assert_eq!(scene.frame_count(), 3);
+        let first = scene.frame(0);
+        assert_eq!(first.time, 1f64);
+        assert!((first.camera.position() - DVec3::new(0f64, 0f64, 2f64)).length() < 1e-9);
+        let last = scene.frame(2);
+        assert_eq!(last.time, 2f64);
+        assert!((last.camera.position() - DVec3::new(2f64, 0f64, 0f64)).length() < 1e-9);
+
+        let source = format!(
+            "{}\n[animation]\nframes = 3\n\n[animation.turntable]\n\n[[animation.keyframes]]\ntime = 0.0\nposition = [0.0, 0.0, 2.0]\nlook_at = [0.0, 0.0, 0.0]\n",
+            MINIMAL
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("turntable"), "{}", err);
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        let source = format!(
+            r#"{}
+[materials.lamp]
+type = "diffuse_light"
+color = [1.0, 1.0, 1.0]
+
+[[objects]]
+type = "sphere"
+center = [0.0, 2.0, -1.0]
+end_center = [1.0, 2.0, -1.0]
+end_time = 0.5
+radius = 0.5
+material = "lamp"
+
+[[objects]]
+type = "sphere"
+center = [0.0, -2.0, -1.0]
+radius = 0.5
+material = "lamp"
+"#,
+            MINIMAL.replace("height = 2", "height = 2\nshutter = 0.25"),
+        );
+        let scene = parse_scene(&source).unwrap();
+        // only the lamp at rest is sampled as a light
+        assert_eq!(scene.world.lights().len(), 1);
+        let bbox = scene.world.bounding_box().unwrap();
+        assert_eq!(bbox.max.x, 1.5);
+        assert!(scene
+            .render_spec()
+            .ray_for_pixel(0, 0)
+            .all(|ray| (0f64..=0.25).contains(&ray.time)));
+
+        // frozen in each frame without a shutter, but not all at the start
+        let source = with_minimal(
+            r#"
+[animation]
+frames = 3
+fps = 1.0
+
+[[objects]]
+type = "sphere"
+center = [0.0, 2.0, -5.0]
+end_center = [4.0, 2.0, -5.0]
+end_time = 2.0
+radius = 0.5
+material = "ground"
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        let center_seen = |index| {
+            let frame = scene.frame(index);
+            let time = frame.render_spec.ray_for_pixel(0, 0).next().unwrap().time;
+            assert_eq!(time, frame.time);
+            // which of a row of rays hits the moving sphere
+            (0..=4)
+                .find(|x| {
+                    let origin = DVec3::new(*x as f64, 2f64, 0f64);
+                    let ray = Ray::new(origin, DVec3::NEG_Z).with_time(time);
+                    scene
+                        .world
+                        .hit(&ray, &Interval::greater_than(0f64))
+                        .is_some()
+                })
+                .unwrap()
+        };
+        assert_eq!(center_seen(0), 0);
+        assert_eq!(center_seen(1), 2);
+        assert_eq!(center_seen(2), 4);
+
+        let source = MINIMAL.replace("height = 2", "height = 2\nshutter = -1.0");
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("shutter"), "{}", err);
+    }
+
+    #[test]
+    fn test_obj_instances() {
+        let dir = std::env::temp_dir().join(format!("obj_instances_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // a unit quad facing +Z
+        fs::write(
+            dir.join("quad.obj"),
+            "v -0.5 -0.5 0\nv 0.5 -0.5 0\nv 0.5 0.5 0\nv -0.5 0.5 0\nf 1 2 3 4\n",
+        )
+        .unwrap();
+        let source = r#"
+[render]
+width = 4
+height = 2
+
+[[objects]]
+type = "obj"
+path = "quad.obj"
+
+[[objects]]
+type = "obj"
+path = "quad.obj"
+position = [3.0, 0.0, 0.0]
+scale = 2.0
+
+[[objects]]
+type = "obj"
+path = "quad.obj"
+position = [-3.0, 0.0, 0.0]
+rotation = [0.0, 90.0, 0.0]
+scale = [1.0, 4.0, 1.0]
+"#;
+        let scene = parse_scene_in(source, &dir).unwrap();
+        // the untransformed model is split into triangles, the placed ones
+        // are an instance each
+        assert_eq!(scene.object_count, 4);
+
+        let range = Interval::greater_than(0f64);
+        let hit =
+            |origin: DVec3, direction: DVec3| scene.world.hit(&Ray::new(origin, direction), &range);
+        // scaled up, so wider than the original quad
+        let rec = hit(DVec3::new(3.9, 0f64, 5f64), DVec3::NEG_Z).unwrap();
+        assert!((rec.t - 5f64).abs() < 1e-9);
+        assert!(hit(DVec3::new(0.9, 0f64, 5f64), DVec3::NEG_Z).is_none());
+        // turned to face +X, and stretched vertically
+        let rec = hit(DVec3::new(0f64, 1.5, 0f64), DVec3::NEG_X).unwrap();
+        assert!((rec.t - 3f64).abs() < 1e-9);
+        assert!((rec.normal - DVec3::X).length() < 1e-9);
+
+        let source = source.replace("[1.0, 4.0, 1.0]", "[1.0, 0.0, 1.0]");
+        let err = parse_scene_in(&source, &dir).err().unwrap();
+        assert!(err.message().contains("scale"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prototypes() {
+        let source = r#"
+[render]
+width = 4
+height = 2
+
+[materials.wood]
+type = "lambertian"
+albedo = [0.5, 0.3, 0.1]
+
+[materials.metal]
+type = "metal"
+albedo = [0.9, 0.9, 0.9]
+
+[prototypes.table]
+type = "group"
+material = "wood"
+
+[[prototypes.table.children]]
+type = "rectangle"
+position = [0.0, 1.0, 0.0]
+width = 2.0
+height = 1.0
+
+[[prototypes.table.children]]
+type = "group"
+name = "legs"
+material = "metal"
+
+[[prototypes.table.children.children]]
+type = "sphere"
+center = [-0.9, 0.5, -0.4]
+radius = 0.05
+
+[[prototypes.table.children.children]]
+type = "sphere"
+center = [0.9, 0.5, 0.4]
+radius = 0.05
+
+[[objects]]
+type = "instance"
+prototype = "table"
+name = "kitchen"
+position = [-5.0, 0.0, 0.0]
+
+[[objects]]
+type = "instance"
+prototype = "table"
+name = "garden"
+position = [5.0, 0.0, 0.0]
+scale = 2.0
+"#;
+        let scene = parse_scene(source).unwrap();
+        // a top and two legs per table
+        assert_eq!(scene.object_count, 6);
+        let legs = scene.graph.world_bounding_box("garden/legs").unwrap();
+        assert!((legs.min - DVec3::new(3.1, 0.9, -0.9)).length() < 1e-9);
+        assert!(scene.graph.find("kitchen/legs").is_some());
+
+        let range = Interval::greater_than(0f64);
+        let rec = scene
+            .world
+            .hit(
+                &Ray::new(DVec3::new(-5f64, 5f64, 0f64), DVec3::NEG_Y),
+                &range,
+            )
+            .unwrap();
+        assert!((rec.point.y - 1f64).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&rec.mat, &scene.materials["wood"]));
+        let rec = hit_from(&scene, DVec3::new(6.8, 1f64, 5f64), 0f64).unwrap();
+        assert!(Arc::ptr_eq(&rec.mat, &scene.materials["metal"]));
+
+        let source = source.replacen(
+            "[[objects]]",
+            "[[prototypes.table.children]]\ntype = \"instance\"\nprototype = \"table\"\n\n[[objects]]",
+            1,
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("itself"), "{}", err);
+        let source = format!(
+            "{}\n[[objects]]\ntype = \"instance\"\nprototype = \"chair\"\n",
+            MINIMAL
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("chair"), "{}", err);
+    }
+
+    #[test]
+    fn test_primitives() {
+        let source = with_minimal(
+            r#"
+[[objects]]
+type = "cuboid"
+min = [-1.0, 0.0, -1.0]
+max = [1.0, 0.5, 1.0]
+
+[[objects]]
+type = "disk"
+center = [0.0, 3.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+radius = 2.0
+
+[[objects]]
+type = "cylinder"
+bottom = [0.0, 0.5, 0.0]
+top = [0.0, 2.0, 0.0]
+radius = 0.2
+
+[[objects]]
+type = "cone"
+base = [0.0, 2.0, 0.0]
+apex = [0.0, 2.5, 0.0]
+radius = 0.4
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        assert_eq!(scene.object_count, 5);
+        let bbox = scene.graph.world_bounding_box("").unwrap();
+        assert_eq!(bbox.min, DVec3::new(-2f64, -0.5, -2f64));
+        assert_eq!(bbox.max, DVec3::new(2f64, 3f64, 2f64));
+
+        let source = source.replace("radius = 0.4", "radius = 0.0");
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("cone radius"), "{}", err);
+    }
+
+    #[test]
+    fn test_csg() {
+        let source = with_minimal(
+            r#"
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[objects]]
+type = "difference"
+material = "red"
+
+[objects.a]
+type = "cuboid"
+min = [-1.0, -1.0, -6.0]
+max = [1.0, 1.0, -4.0]
+
+[objects.b]
+type = "sphere"
+center = [0.0, 0.0, -5.0]
+radius = 0.5
+material = "ground"
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        let origin = DVec3::new(0f64, 0f64, 5f64);
+        // past the sphere of MINIMAL, into the box
+        let rec = hit_from(&scene, origin, 7f64).unwrap();
+        assert!((rec.t - 9f64).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&rec.mat, &scene.materials["red"]));
+        // the wall of the hole, in the material of the sphere
+        let rec = hit_from(&scene, origin, 9.1).unwrap();
+        assert!((rec.t - 9.5).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&rec.mat, &scene.materials["ground"]));
+
+        let source = source.replace(
+            "type = \"sphere\"\ncenter = [0.0, 0.0, -5.0]",
+            "type = \"disk\"\nnormal = [0.0, 0.0, 1.0]\ncenter = [0.0, 0.0, -5.0]",
+        );
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("combined"), "{}", err);
+    }
+
+    #[test]
+    fn test_torus_and_implicit() {
+        // a ring facing the camera, and a unit sphere as a polynomial behind it
+        let source = with_minimal(
+            r#"
+[[objects]]
+type = "torus"
+center = [0.0, 0.0, -5.0]
+axis = [0.0, 0.0, 1.0]
+major_radius = 2.0
+minor_radius = 0.5
+
+[[objects]]
+type = "implicit"
+min = [-2.0, -2.0, -12.0]
+max = [2.0, 2.0, -8.0]
+terms = [
+    { coefficient = 1.0, x = 2 },
+    { coefficient = 1.0, y = 2 },
+    { coefficient = 1.0, z = 2 },
+    { coefficient = 20.0, z = 1 },
+    { coefficient = 99.0 },
+]
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        let rec = hit_from(&scene, DVec3::new(2f64, 0f64, 5f64), 0f64).unwrap();
+        assert!((rec.t - 9.5).abs() < 1e-9);
+
+        // through the hole of the ring, past the sphere of MINIMAL
+        let rec = hit_from(&scene, DVec3::new(0f64, 0f64, 5f64), 7f64).unwrap();
+        assert!((rec.t - 14f64).abs() < 1e-9);
+
+        for (from, to) in [
+            ("minor_radius = 0.5", "minor_radius = 2.5"),
+            ("major_radius = 2.0", "major_radius = nan"),
+        ] {
+            let err = parse_scene(&source.replace(from, to)).err().unwrap();
+            assert!(err.message().contains("minor_radius"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_medium() {
+        let source = with_minimal(
+            r#"
+[materials.smoke]
+type = "henyey_greenstein"
+albedo = [0.5, 0.5, 0.5]
+g = 0.3
+
+[[objects]]
+type = "medium"
+density = 1000.0
+material = "smoke"
+
+[objects.boundary]
+type = "cuboid"
+min = [-1.0, -1.0, -6.0]
+max = [1.0, 1.0, -4.0]
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        // so dense that light barely gets in before scattering
+        let origin = DVec3::new(0f64, 0f64, 5f64);
+        let rec = hit_from(&scene, origin, 7f64).unwrap();
+        assert!((9f64..9.1).contains(&rec.t));
+        assert!(Arc::ptr_eq(&rec.mat, &scene.materials["smoke"]));
+
+        let err = parse_scene(&source.replace("\"smoke\"\n", "\"ground\"\n"))
+            .err()
+            .unwrap();
+        assert!(err.message().contains("isotropic"), "{}", err);
+        for density in ["0.0", "nan"] {
+            let err = parse_scene(&source.replace("1000.0", density))
+                .err()
+                .unwrap();
+            assert!(err.message().contains("density"), "{}", err);
+        }
+
+        // without a material of its own, in a group of surfaces or not
+        let own = source.replace("material = \"smoke\"\n", "");
+        let inherited = own
+            .replace(
+                "[[objects]]\ntype = \"medium\"",
+                "[[objects]]\ntype = \"group\"\nmaterial = \"ground\"\n\n[[objects.children]]\ntype = \"medium\"",
+            )
+            .replace("[objects.boundary]", "[objects.children.boundary]");
+        for source in [own, inherited] {
+            let scene = parse_scene(&source).unwrap();
+            let rec = hit_from(&scene, origin, 7f64).unwrap();
+            assert!(rec.mat.is_volumetric());
+        }
+
+        let err = parse_scene(&source.replace("g = 0.3", "g = 1.0"))
+            .err()
+            .unwrap();
+        assert!(err.message().contains("between"), "{}", err);
+    }
+
+    #[test]
+    fn test_sdf() {
+        let source = with_minimal(
+            r#"
+[[objects]]
+type = "sdf"
+
+[objects.shape]
+type = "smooth_union"
+smoothness = 0.5
+
+[objects.shape.a]
+type = "sphere"
+center = [-0.6, 0.0, -5.0]
+radius = 0.5
+
+[objects.shape.b]
+type = "box"
+center = [0.6, 0.0, -5.0]
+size = [1.0, 1.0, 1.0]
+rounding = 0.1
+"#,
+        );
+        let scene = parse_scene(&source).unwrap();
+        // through the blend between the two, past the sphere of MINIMAL
+        let rec = hit_from(&scene, DVec3::new(0f64, 0f64, 5f64), 7f64).unwrap();
+        assert!(rec.t < 10f64 && rec.t > 9f64);
+
+        let source = source.replace("smoothness = 0.5", "smoothness = -0.5");
+        let err = parse_scene(&source).err().unwrap();
+        assert!(err.message().contains("smoothness"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        let source = MINIMAL.replace("radius", "raduis");
+        assert!(parse_scene(&source).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+pub mod description;
+pub mod error;
+pub mod loader;
+mod shapes;
+
+pub use description::SceneDescription;
+pub use error::{Location, SceneError};
+pub use loader::{load_scene, parse_scene, validate_render, LoadedScene, SceneAnimation};
@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use glam::{DQuat, DVec3};
+
+use crate::materials::SharedMaterial;
+use crate::world::{
+    Aabb, Cone, Csg, CsgOperation, Cuboid, Cylinder, Intersectable, Rectangle, ShapeTemplate,
+    SharedLight, SharedSolid, Sphere, Torus, TriangleMesh,
+};
+
+// The leaf objects of a scene file, waiting for the material they inherit
+
+pub struct SphereShape {
+    pub center: DVec3,
+    pub radius: f64,
+    // where the center is at `end_time`, for motion blur
+    pub motion: Option<(DVec3, f64)>,
+}
+
+impl SphereShape {
+    pub fn sphere(&self, material: &SharedMaterial) -> Sphere {
+        let sphere = Sphere::new(self.center, self.radius, material);
+        match self.motion {
+            Some((end_center, end_time)) => sphere.with_motion(end_center, 0f64, end_time),
+            None => sphere,
+        }
+    }
+}
+
+impl ShapeTemplate for SphereShape {
+    fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>> {
+        vec![self.sphere(material).into_box()]
+    }
+
+    fn build_light(&self, material: &SharedMaterial) -> Option<SharedLight> {
+        // lights are sampled where they rest, so moving ones are only found
+        // by the paths hitting them
+        match self.motion {
+            Some(_) => None,
+            None => Some(Arc::new(self.sphere(material))),
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = DVec3::splat(self.radius);
+        let end = self
+            .motion
+            .map_or(self.center, |(end_center, _)| end_center);
+        Some(Aabb::from_points([
+            self.center - r,
+            self.center + r,
+            end - r,
+            end + r,
+        ]))
+    }
+}
+
+pub struct RectangleShape {
+    pub position: DVec3,
+    pub rotation: DQuat,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl RectangleShape {
+    fn rectangle(&self, material: &SharedMaterial) -> Rectangle {
+        Rectangle::new(
+            self.position,
+            self.rotation,
+            self.width,
+            self.height,
+            material,
+        )
+    }
+}
+
+impl ShapeTemplate for RectangleShape {
+    fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>> {
+        vec![self.rectangle(material).into_box()]
+    }
+
+    fn build_light(&self, material: &SharedMaterial) -> Option<SharedLight> {
+        Some(Arc::new(self.rectangle(material)))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // spans `width` along its X axis and `height` along its Z axis
+        let r = self.rotation * DVec3::X * self.width * 0.5;
+        let d = self.rotation * DVec3::Z * self.height * 0.5;
+        let p = self.position;
+        Some(Aabb::from_points([
+            p - r - d,
+            p - r + d,
+            p + r - d,
+            p + r + d,
+        ]))
+    }
+}
+
+// Inline meshes and OBJ models. Faces with a material of their own (e.g.
+// from an MTL file) keep it, the others take the inherited one.
+pub struct MeshShape {
+    pub meshes: Vec<TriangleMesh>,
+}
+
+impl ShapeTemplate for MeshShape {
+    fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>> {
+        // individual triangles let the BVH cull most of the mesh
+        self.meshes
+            .iter()
+            .flat_map(|mesh| {
+                mesh.with_default_material(material)
+                    .triangles()
+                    .map(|t| t.into_box())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // only the vertices used by a face
+        let points = self.meshes.iter().flat_map(|mesh| {
+            let buffers = mesh.buffers();
+            buffers
+                .indices
+                .iter()
+                .flatten()
+                .map(|&i| buffers.vertices[i])
+        });
+        Some(Aabb::from_points(points))
+    }
+}
+
+// Any other single primitive, built by a closure
+pub struct PrimitiveShape<F> {
+    build: F,
+    bounding_box: Option<Aabb>,
+}
+
+impl<F> PrimitiveShape<F>
+where
+    F: Fn(&SharedMaterial) -> Box<dyn Intersectable> + Send + Sync,
+{
+    // `material` is only used to measure the primitive
+    pub fn new(build: F, material: &SharedMaterial) -> Self {
+        let bounding_box = build(material).bounding_box();
+        Self {
+            build,
+            bounding_box,
+        }
+    }
+}
+
+impl<F> ShapeTemplate for PrimitiveShape<F>
+where
+    F: Fn(&SharedMaterial) -> Box<dyn Intersectable> + Send + Sync,
+{
+    fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>> {
+        vec![(self.build)(material)]
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
+
+// Operands of constructive solid geometry, with their own material or the
+// one of the CSG object
+pub struct SolidShape {
+    pub material: Option<SharedMaterial>,
+    pub kind: SolidKind,
+}
+
+pub enum SolidKind {
+    Sphere(SphereShape),
+    Cuboid(DVec3, DVec3),
+    Cylinder(DVec3, DVec3, f64),
+    Cone(DVec3, DVec3, f64),
+    // center, axis and the major and minor radii
+    Torus(DVec3, DVec3, f64, f64),
+    Csg(CsgOperation, Box<SolidShape>, Box<SolidShape>),
+}
+
+impl SolidKind {
+    pub fn build(&self, material: &SharedMaterial) -> SharedSolid {
+        match self {
+            Self::Sphere(sphere) => Arc::new(sphere.sphere(material)),
+            Self::Cuboid(min, max) => Arc::new(Cuboid::new(*min, *max, material)),
+            Self::Cylinder(bottom, top, radius) => {
+                Arc::new(Cylinder::new(*bottom, *top, *radius, material))
+            }
+            Self::Cone(base, apex, radius) => Arc::new(Cone::new(*base, *apex, *radius, material)),
+            Self::Torus(center, axis, major_radius, minor_radius) => Arc::new(Torus::new(
+                *center,
+                *axis,
+                *major_radius,
+                *minor_radius,
+                material,
+            )),
+            Self::Csg(operation, a, b) => {
+                Arc::new(Csg::new(*operation, a.build(material), b.build(material)))
+            }
+        }
+    }
+}
+
+impl SolidShape {
+    fn build(&self, inherited: &SharedMaterial) -> SharedSolid {
+        self.kind.build(self.material.as_ref().unwrap_or(inherited))
+    }
+}
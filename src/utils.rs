@@ -57,37 +57,42 @@ impl Interval {
     }
 }
 
-fn unit_spherical_to_cartesian(theta: f64, phi: f64) -> DVec3 {
-    // Convert to Cartesian coordinates
-    let x = f64::cos(phi);
-    let y = f64::sin(phi) * f64::cos(theta);
-    let z = f64::sin(phi) * f64::sin(theta);
-    DVec3::new(x, y, z)
-}
-
+// Uniformly distributed on the unit sphere. Picking z uniformly (instead of
+// the polar angle) keeps the samples from clustering at the poles, which the
+// materials rely on for their sampling densities.
 pub fn random_unit_vector() -> DVec3 {
     let mut rng = thread_rng();
-    // Generate random angles
-    let theta = rng.gen_range(0.0..2.0 * PI);
-    let phi = rng.gen_range(0.0..PI);
-
-    unit_spherical_to_cartesian(theta, phi)
+    let z = rng.gen_range(-1f64..1f64);
+    let phi = rng.gen_range(0f64..2f64 * PI);
+    let r = (1f64 - z * z).max(0f64).sqrt();
+    DVec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
+// Uniformly distributed on the hemisphere around `normal`
 pub fn random_unit_vector_on_hemisphere(normal: DVec3) -> DVec3 {
-    // generate a vector on the hemisphere given by (1,0,0)
-    let mut rng = thread_rng();
-
-    // Generate random angles for the hemisphere
-    let theta = rng.gen_range(0f64..PI);
-    let phi = rng.gen_range(0f64..PI);
-
-    // Create the vector
-    let v = unit_spherical_to_cartesian(theta, phi);
-
+    let v = random_unit_vector();
     if normal.dot(v) < 0f64 {
         -v
     } else {
         v
     }
 }
+
+// Uniformly distributed direction inside the cone around `axis` (unit) whose
+// half angle has the given cosine
+pub fn random_in_cone(axis: DVec3, cos_max: f64) -> DVec3 {
+    let mut rng = thread_rng();
+    let cos_theta = 1f64 - rng.gen::<f64>() * (1f64 - cos_max);
+    let sin_theta = (1f64 - cos_theta * cos_theta).max(0f64).sqrt();
+    let phi = rng.gen_range(0f64..2f64 * PI);
+    let (u, v) = axis.any_orthonormal_pair();
+    (u * phi.cos() + v * phi.sin()) * sin_theta + axis * cos_theta
+}
+
+// Uniformly distributed in the unit disk of the XY plane
+pub fn random_in_unit_disk() -> DVec3 {
+    let mut rng = thread_rng();
+    let r = rng.gen::<f64>().sqrt();
+    let phi = rng.gen_range(0f64..2f64 * PI);
+    DVec3::new(r * phi.cos(), r * phi.sin(), 0f64)
+}
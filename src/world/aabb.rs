@@ -0,0 +1,140 @@
+use crate::{ray::Ray, utils::Interval};
+use glam::DVec3;
+
+// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: DVec3,
+    pub max: DVec3,
+}
+
+impl Aabb {
+    pub fn new(a: DVec3, b: DVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    // Contains nothing, the identity of `union`
+    pub fn empty() -> Self {
+        Self {
+            min: DVec3::INFINITY,
+            max: DVec3::NEG_INFINITY,
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = DVec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::empty(), |acc, p| acc.include(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn include(&self, p: DVec3) -> Self {
+        Self {
+            min: self.min.min(p),
+            max: self.max.max(p),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn extent(&self) -> DVec3 {
+        if self.is_empty() {
+            DVec3::ZERO
+        } else {
+            self.max - self.min
+        }
+    }
+
+    pub fn centroid(&self) -> DVec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let e = self.extent();
+        2f64 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let e = self.extent();
+        if e.x >= e.y && e.x >= e.z {
+            0
+        } else if e.y >= e.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Slab test, returns the parametric range of the ray inside the box
+    pub fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<Interval> {
+        let mut range = *avaliable_range;
+        for axis in 0..3 {
+            let inv_d = 1f64 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+            if inv_d < 0f64 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max`/`min` ignore the NaN of a ray lying on the slab boundary
+            range.lower = range.lower.max(t0);
+            range.upper = range.upper.min(t1);
+            if range.upper < range.lower {
+                return None;
+            }
+        }
+        Some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_and_miss() {
+        let aabb = Aabb::new(DVec3::NEG_ONE, DVec3::ONE);
+        let range = Interval::greater_than(0f64);
+
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 5f64), DVec3::NEG_Z);
+        let inside = aabb.hit(&ray, &range).unwrap();
+        assert_eq!(inside.lower, 4f64);
+        assert_eq!(inside.upper, 6f64);
+
+        let ray = Ray::new(DVec3::new(2f64, 0f64, 5f64), DVec3::NEG_Z);
+        assert!(aabb.hit(&ray, &range).is_none());
+
+        // pointing away
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 5f64), DVec3::Z);
+        assert!(aabb.hit(&ray, &range).is_none());
+    }
+
+    #[test]
+    fn test_flat_box() {
+        // e.g. the box of a triangle lying in the z = 0 plane
+        let aabb = Aabb::new(DVec3::ZERO, DVec3::new(1f64, 1f64, 0f64));
+        let ray = Ray::new(DVec3::new(0.5, 0.5, 1f64), DVec3::NEG_Z);
+        assert!(aabb.hit(&ray, &Interval::greater_than(0f64)).is_some());
+    }
+
+    #[test]
+    fn test_union_and_area() {
+        let a = Aabb::new(DVec3::ZERO, DVec3::ONE);
+        let b = Aabb::new(DVec3::ONE, DVec3::splat(2f64));
+        let u = a.union(&b);
+        assert_eq!(u, Aabb::new(DVec3::ZERO, DVec3::splat(2f64)));
+        assert_eq!(u.surface_area(), 24f64);
+        assert_eq!(Aabb::empty().union(&a), a);
+        assert_eq!(Aabb::empty().surface_area(), 0f64);
+    }
+}
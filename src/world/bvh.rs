@@ -0,0 +1,427 @@
+use std::fmt::{Display, Formatter};
+
+use glam::DVec3;
+
+use crate::{ray::Ray, utils::Interval};
+
+use super::{Aabb, IntersectContainer, IntersectRecord, Intersectable};
+
+// Number of centroid buckets evaluated by the surface area heuristic
+const SAH_BINS: usize = 12;
+// Cost of visiting a node relative to intersecting one object
+const TRAVERSAL_COST: f64 = 0.125;
+// Leaves larger than this are split even if the SAH disagrees, unless they
+// cannot be: when all of their centroids coincide, or once the tree reaches
+// MAX_STACK_DEPTH. So leaves are usually, but not always, at most this large.
+const MAX_LEAF_SIZE: usize = 8;
+const MAX_STACK_DEPTH: usize = 64;
+
+enum BvhNode {
+    Leaf {
+        aabb: Aabb,
+        first: usize,
+        count: usize,
+    },
+    // The first child is stored right after its parent
+    Interior {
+        aabb: Aabb,
+        second_child: usize,
+        axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { aabb, .. } | BvhNode::Interior { aabb, .. } => aabb,
+        }
+    }
+}
+
+struct Primitive {
+    index: usize,
+    aabb: Aabb,
+    centroid: DVec3,
+}
+
+// Bounding volume hierarchy over the bounded objects, built with a binned
+// surface area heuristic. Unbounded objects are kept aside and tested for
+// every ray.
+pub struct BvhContainer {
+    // ordered so that every leaf refers to a contiguous range
+    objects: Vec<Box<dyn Intersectable>>,
+    nodes: Vec<BvhNode>,
+    unbounded: Vec<Box<dyn Intersectable>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BvhStats {
+    pub depth: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub mean_leaf_size: f64,
+    pub unbounded_count: usize,
+}
+
+impl Display for BvhStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} leaves, depth {}, leaf size {}..={} (mean {:.2}), {} unbounded",
+            self.node_count,
+            self.leaf_count,
+            self.depth,
+            self.min_leaf_size,
+            self.max_leaf_size,
+            self.mean_leaf_size,
+            self.unbounded_count
+        )
+    }
+}
+
+impl BvhContainer {
+    pub fn new() -> Self {
+        Self {
+            objects: vec![],
+            nodes: vec![],
+            unbounded: vec![],
+        }
+    }
+
+    pub fn build(objects: Vec<Box<dyn Intersectable>>) -> Self {
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        let mut primitives = vec![];
+        for object in objects {
+            match object.bounding_box() {
+                Some(aabb) => {
+                    primitives.push(Primitive {
+                        index: bounded.len(),
+                        aabb,
+                        centroid: aabb.centroid(),
+                    });
+                    bounded.push(Some(object));
+                }
+                None => unbounded.push(object),
+            }
+        }
+
+        let mut nodes = vec![];
+        if !primitives.is_empty() {
+            Self::build_node(&mut primitives, 0, 1, &mut nodes);
+        }
+
+        // reorder the objects following the leaves
+        let objects = primitives
+            .iter()
+            .map(|p| bounded[p.index].take().unwrap())
+            .collect();
+
+        Self {
+            objects,
+            nodes,
+            unbounded,
+        }
+    }
+
+    fn build_node(
+        primitives: &mut [Primitive],
+        first: usize,
+        depth: usize,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
+        let aabb = primitives
+            .iter()
+            .fold(Aabb::empty(), |acc, p| acc.union(&p.aabb));
+        let node_index = nodes.len();
+        let count = primitives.len();
+        let leaf = BvhNode::Leaf { aabb, first, count };
+
+        let centroid_bounds = Aabb::from_points(primitives.iter().map(|p| p.centroid));
+        let axis = centroid_bounds.longest_axis();
+        let axis_min = centroid_bounds.min[axis];
+        let axis_extent = centroid_bounds.extent()[axis];
+        // the traversal stack holds at most one entry per level plus one
+        if count == 1 || axis_extent <= 0f64 || depth + 1 >= MAX_STACK_DEPTH {
+            // nothing to split, or the tree is already too deep
+            nodes.push(leaf);
+            return node_index;
+        }
+
+        let bin_of = |p: &Primitive| {
+            let relative = (p.centroid[axis] - axis_min) / axis_extent;
+            ((relative * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+        };
+
+        let mut bin_counts = [0usize; SAH_BINS];
+        let mut bin_bounds = [Aabb::empty(); SAH_BINS];
+        for p in primitives.iter() {
+            let b = bin_of(p);
+            bin_counts[b] += 1;
+            bin_bounds[b] = bin_bounds[b].union(&p.aabb);
+        }
+
+        // cost of splitting between every pair of neighboring bins
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        let area = aabb.surface_area().max(f64::MIN_POSITIVE);
+        for split in 1..SAH_BINS {
+            let (left, right) = (0..SAH_BINS).fold(
+                ((Aabb::empty(), 0), (Aabb::empty(), 0)),
+                |((lb, lc), (rb, rc)), b| {
+                    if b < split {
+                        ((lb.union(&bin_bounds[b]), lc + bin_counts[b]), (rb, rc))
+                    } else {
+                        ((lb, lc), (rb.union(&bin_bounds[b]), rc + bin_counts[b]))
+                    }
+                },
+            );
+            if left.1 == 0 || right.1 == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (left.0.surface_area() * left.1 as f64 + right.0.surface_area() * right.1 as f64)
+                    / area;
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        if best_split == 0 || (best_cost >= count as f64 && count <= MAX_LEAF_SIZE) {
+            nodes.push(leaf);
+            return node_index;
+        }
+
+        primitives.sort_unstable_by_key(|p| bin_of(p) >= best_split);
+        let mid = primitives.partition_point(|p| bin_of(p) < best_split);
+
+        nodes.push(BvhNode::Interior {
+            aabb,
+            second_child: 0,
+            axis,
+        });
+        let (left, right) = primitives.split_at_mut(mid);
+        Self::build_node(left, first, depth + 1, nodes);
+        let right_index = Self::build_node(right, first + mid, depth + 1, nodes);
+        if let BvhNode::Interior { second_child, .. } = &mut nodes[node_index] {
+            *second_child = right_index;
+        }
+        node_index
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            depth: 0,
+            node_count: self.nodes.len(),
+            leaf_count: 0,
+            min_leaf_size: 0,
+            max_leaf_size: 0,
+            mean_leaf_size: 0f64,
+            unbounded_count: self.unbounded.len(),
+        };
+        if self.nodes.is_empty() {
+            return stats;
+        }
+        stats.min_leaf_size = usize::MAX;
+        let mut stack = vec![(0, 1)];
+        while let Some((index, depth)) = stack.pop() {
+            stats.depth = stats.depth.max(depth);
+            match self.nodes[index] {
+                BvhNode::Leaf { count, .. } => {
+                    stats.leaf_count += 1;
+                    stats.min_leaf_size = stats.min_leaf_size.min(count);
+                    stats.max_leaf_size = stats.max_leaf_size.max(count);
+                }
+                BvhNode::Interior { second_child, .. } => {
+                    stack.push((index + 1, depth + 1));
+                    stack.push((second_child, depth + 1));
+                }
+            }
+        }
+        stats.mean_leaf_size = self.objects.len() as f64 / stats.leaf_count as f64;
+        stats
+    }
+
+    fn into_objects(self) -> Vec<Box<dyn Intersectable>> {
+        let mut objects = self.objects;
+        objects.extend(self.unbounded);
+        objects
+    }
+}
+
+impl Default for BvhContainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Intersectable for BvhContainer {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let mut nearest_record = None;
+        let mut current_range = *avaliable_range;
+        for h in &self.unbounded {
+            if let Some(rec) = h.hit(ray, &current_range) {
+                current_range.upper = rec.t;
+                nearest_record = Some(rec);
+            }
+        }
+        if self.nodes.is_empty() {
+            return nearest_record;
+        }
+
+        let mut stack = [0usize; MAX_STACK_DEPTH];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            if node.aabb().hit(ray, &current_range).is_none() {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for h in &self.objects[first..first + count] {
+                        if let Some(rec) = h.hit(ray, &current_range) {
+                            current_range.upper = rec.t;
+                            nearest_record = Some(rec);
+                        }
+                    }
+                }
+                BvhNode::Interior {
+                    second_child, axis, ..
+                } => {
+                    // visit the nearer child first so the range shrinks early
+                    let first_child = stack[top] + 1;
+                    let (near, far) = if ray.direction[axis] < 0f64 {
+                        (second_child, first_child)
+                    } else {
+                        (first_child, second_child)
+                    };
+                    stack[top] = far;
+                    stack[top + 1] = near;
+                    top += 2;
+                }
+            }
+        }
+        nearest_record
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        Some(self.nodes.first().map_or(Aabb::empty(), |n| *n.aabb()))
+    }
+}
+
+impl IntersectContainer for BvhContainer {
+    // Rebuilds the whole hierarchy, prefer `from_iter` to add many objects
+    fn add<I: Intersectable + 'static>(&mut self, i: I) {
+        let mut objects = std::mem::take(self).into_objects();
+        objects.push(Box::new(i));
+        *self = Self::build(objects);
+    }
+
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = Box<dyn Intersectable>>,
+    {
+        Self::build(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::world::{InfinitePlane, Sphere, VecContainer};
+    use rand::{
+        distributions::{Distribution, Uniform},
+        rngs::StdRng,
+        SeedableRng,
+    };
+
+    fn random_spheres(rng: &mut StdRng, n: usize) -> Vec<Box<dyn Intersectable>> {
+        let material = DummyMaterial::new_shared();
+        let position = Uniform::new(-10f64, 10f64);
+        let radius = Uniform::new(0.05, 0.5);
+        (0..n)
+            .map(|_| {
+                let center = DVec3::new(
+                    position.sample(rng),
+                    position.sample(rng),
+                    position.sample(rng),
+                );
+                Sphere::new(center, radius.sample(rng), &material).into_box()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        let mut rng = StdRng::from_seed([42; 32]);
+        let bvh = BvhContainer::build(random_spheres(&mut rng, 500));
+        let linear: VecContainer = random_spheres(&mut StdRng::from_seed([42; 32]), 500);
+
+        let uniform = Uniform::new(-1f64, 1f64);
+        let range = Interval::greater_than(0f64);
+        for _ in 0..1000 {
+            let direction = DVec3::new(
+                uniform.sample(&mut rng),
+                uniform.sample(&mut rng),
+                uniform.sample(&mut rng),
+            );
+            let ray = Ray::new(DVec3::new(0f64, 0f64, 20f64), direction);
+            let expected = linear.hit(&ray, &range).map(|rec| rec.t);
+            let actual = bvh.hit(&ray, &range).map(|rec| rec.t);
+            assert_eq!(expected, actual, "direction {}", direction);
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut rng = StdRng::from_seed([7; 32]);
+        let bvh = BvhContainer::build(random_spheres(&mut rng, 1000));
+        let stats = bvh.stats();
+        assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+        assert!(stats.max_leaf_size <= MAX_LEAF_SIZE);
+        assert!(stats.min_leaf_size >= 1);
+        assert!(stats.depth < MAX_STACK_DEPTH);
+        assert!((stats.mean_leaf_size * stats.leaf_count as f64 - 1000f64).abs() < 1e-6);
+
+        // nested spheres around the same center cannot be split apart
+        let material = DummyMaterial::new_shared();
+        let nested: Vec<Box<dyn Intersectable>> = (1..=20)
+            .map(|r| Sphere::new(DVec3::ZERO, r as f64, &material).into_box())
+            .collect();
+        let stats = BvhContainer::build(nested).stats();
+        assert_eq!((stats.leaf_count, stats.max_leaf_size), (1, 20));
+    }
+
+    #[test]
+    fn test_unbounded_and_add() {
+        let material = DummyMaterial::new_shared();
+        let mut bvh = BvhContainer::new();
+        bvh.add(InfinitePlane::new(DVec3::ZERO, DVec3::Y, &material));
+        assert!(bvh.bounding_box().is_none());
+
+        let ray = Ray::new(DVec3::new(0f64, 5f64, 0f64), DVec3::NEG_Y);
+        let range = Interval::greater_than(0f64);
+        assert_eq!(bvh.hit(&ray, &range).unwrap().t, 5f64);
+
+        bvh.add(Sphere::new(DVec3::new(0f64, 2f64, 0f64), 1f64, &material));
+        assert_eq!(bvh.len(), 2);
+        assert_eq!(bvh.stats().unbounded_count, 1);
+        assert_eq!(bvh.hit(&ray, &range).unwrap().t, 2f64);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{ray::Ray, utils::Interval};
 
-use super::{IntersectRecord, Intersectable};
+use super::{Aabb, IntersectRecord, Intersectable};
 
 pub trait IntersectContainer: Intersectable {
     fn add<I: Intersectable + 'static>(&mut self, i: I);
@@ -14,7 +14,7 @@ pub type VecContainer = Vec<Box<dyn Intersectable>>;
 impl Intersectable for VecContainer {
     fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
         let mut nearest_record = None;
-        let mut current_range = avaliable_range.clone();
+        let mut current_range = *avaliable_range;
         for h in self {
             if let Some(rec) = h.hit(ray, &current_range) {
                 // Decrease the upperbound of the range to intersction test
@@ -24,6 +24,12 @@ impl Intersectable for VecContainer {
         }
         nearest_record
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.iter().try_fold(Aabb::empty(), |acc, h| {
+            h.bounding_box().map(|aabb| acc.union(&aabb))
+        })
+    }
 }
 
 impl IntersectContainer for VecContainer {
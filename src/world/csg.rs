@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::ray::Ray;
+use crate::utils::Interval;
+
+// Where a ray goes through a solid, from the surface it enters by to the one
+// it leaves by. Both records face the ray like any other hit, `is_front` tells
+// the entry from the exit.
+pub struct Span {
+    pub enter: IntersectRecord,
+    pub exit: IntersectRecord,
+}
+
+// A closed shape with an inside, which can be combined with others
+pub trait Solid: Intersectable {
+    // Every span of the whole line of `ray`, including behind its origin,
+    // sorted and without overlaps
+    fn spans(&self, ray: &Ray) -> Vec<Span>;
+}
+
+pub type SharedSolid = Arc<dyn Solid>;
+
+impl<T: Solid + ?Sized> Solid for Arc<T> {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        (**self).spans(ray)
+    }
+}
+
+// The nearest entry or exit within the range
+pub fn first_hit(spans: Vec<Span>, avaliable_range: &Interval) -> Option<IntersectRecord> {
+    for span in spans {
+        if avaliable_range.contains(span.enter.t) {
+            return Some(span.enter);
+        }
+        if avaliable_range.contains(span.exit.t) {
+            return Some(span.exit);
+        }
+    }
+    None
+}
+
+// Walks the boundaries of both operands in order, keeping the parts of the
+// line where `inside` holds for whether the ray is in `a` and in `b`
+fn combine(a: Vec<Span>, b: Vec<Span>, inside: impl Fn(bool, bool) -> bool) -> Vec<Span> {
+    let mut boundaries = vec![];
+    for (spans, is_a) in [(a, true), (b, false)] {
+        for span in spans {
+            boundaries.push((is_a, true, span.enter));
+            boundaries.push((is_a, false, span.exit));
+        }
+    }
+    boundaries.sort_by(|x, y| x.2.t.total_cmp(&y.2.t));
+
+    let (mut in_a, mut in_b) = (false, false);
+    let mut enter = None;
+    let mut spans = vec![];
+    for (is_a, entering, mut rec) in boundaries {
+        if is_a {
+            in_a = entering;
+        } else {
+            in_b = entering;
+        }
+        // The normal of `rec` already faces the ray, only which side of the
+        // result it lies on may differ from the operand, e.g. leaving the
+        // subtracted solid is entering the difference.
+        match (inside(in_a, in_b), enter.take()) {
+            (true, None) => {
+                rec.is_front = true;
+                enter = Some(rec);
+            }
+            (false, Some(start)) => {
+                rec.is_front = false;
+                spans.push(Span {
+                    enter: start,
+                    exit: rec,
+                });
+            }
+            (_, start) => enter = start,
+        }
+    }
+    spans
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CsgOperation {
+    // inside either
+    Union,
+    // inside both, e.g. a lens from two overlapping spheres
+    Intersection,
+    // inside the first but not the second, e.g. a hollowed box
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Self::Union => in_a || in_b,
+            Self::Intersection => in_a && in_b,
+            Self::Difference => in_a && !in_b,
+        }
+    }
+}
+
+pub struct Csg<A: Solid, B: Solid> {
+    operation: CsgOperation,
+    a: A,
+    b: B,
+}
+
+impl<A: Solid, B: Solid> Csg<A, B> {
+    pub fn new(operation: CsgOperation, a: A, b: B) -> Self {
+        Self { operation, a, b }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Self::new(CsgOperation::Union, a, b)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Self::new(CsgOperation::Intersection, a, b)
+    }
+
+    pub fn difference(a: A, b: B) -> Self {
+        Self::new(CsgOperation::Difference, a, b)
+    }
+
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+}
+
+impl<A: Solid, B: Solid> Intersectable for Csg<A, B> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        first_hit(self.spans(ray), avaliable_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let a = self.a.bounding_box()?;
+        match self.operation {
+            CsgOperation::Union => Some(a.union(&self.b.bounding_box()?)),
+            CsgOperation::Intersection => {
+                let b = self.b.bounding_box()?;
+                Some(Aabb {
+                    min: a.min.max(b.min),
+                    max: a.max.min(b.max),
+                })
+            }
+            CsgOperation::Difference => Some(a),
+        }
+    }
+}
+
+impl<A: Solid, B: Solid> Solid for Csg<A, B> {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let operation = self.operation;
+        combine(self.a.spans(ray), self.b.spans(ray), |in_a, in_b| {
+            operation.inside(in_a, in_b)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::world::{Cuboid, Sphere};
+    use glam::DVec3;
+
+    fn spheres() -> (Sphere, Sphere) {
+        let material = DummyMaterial::new_shared();
+        (
+            Sphere::new(DVec3::new(-0.5, 0f64, 0f64), 1f64, &material),
+            Sphere::new(DVec3::new(0.5, 0f64, 0f64), 1f64, &material),
+        )
+    }
+
+    fn along_x() -> Ray {
+        Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X)
+    }
+
+    fn bounds(spans: &[Span]) -> Vec<(f64, f64)> {
+        spans.iter().map(|s| (s.enter.t, s.exit.t)).collect()
+    }
+
+    #[test]
+    fn test_sphere_spans() {
+        let (a, _) = spheres();
+        let spans = a.spans(&along_x());
+        assert_eq!(bounds(&spans), vec![(3.5, 5.5)]);
+        assert!(spans[0].enter.is_front);
+        assert!(!spans[0].exit.is_front);
+        // behind the origin too
+        let ray = Ray::new(DVec3::new(5f64, 0f64, 0f64), DVec3::X);
+        assert_eq!(bounds(&a.spans(&ray)), vec![(-6.5, -4.5)]);
+    }
+
+    #[test]
+    fn test_union() {
+        let (a, b) = spheres();
+        let union = Csg::union(a, b);
+        assert_eq!(bounds(&union.spans(&along_x())), vec![(3.5, 6.5)]);
+
+        let rec = union
+            .hit(&along_x(), &Interval::greater_than(0f64))
+            .unwrap();
+        assert!((rec.t - 3.5).abs() < 1e-9);
+        // no surface where the spheres overlap
+        let rec = union
+            .hit(&along_x(), &Interval::greater_than(4f64))
+            .unwrap();
+        assert!((rec.t - 6.5).abs() < 1e-9);
+        assert!(!rec.is_front);
+    }
+
+    #[test]
+    fn test_intersection_lens() {
+        let (a, b) = spheres();
+        let lens = Csg::intersection(a, b);
+        assert_eq!(bounds(&lens.spans(&along_x())), vec![(4.5, 5.5)]);
+
+        let rec = lens.hit(&along_x(), &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        // entering through the surface of the right sphere
+        assert!((rec.normal - DVec3::NEG_X).length() < 1e-9);
+        assert!(rec.is_front);
+
+        let bbox = lens.bounding_box().unwrap();
+        assert!((bbox.min - DVec3::new(-0.5, -1f64, -1f64)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(0.5, 1f64, 1f64)).length() < 1e-9);
+
+        // hits either sphere, but not both
+        let ray = Ray::new(DVec3::new(-1.2, 0f64, 5f64), DVec3::NEG_Z);
+        assert!(lens.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_difference_hollow_box() {
+        let material = DummyMaterial::new_shared();
+        let solid = Cuboid::from_center(DVec3::ZERO, DVec3::splat(2f64), &material);
+        let hole = Sphere::new(DVec3::ZERO, 0.5, &material);
+        let hollow = Csg::difference(solid, hole);
+        assert_eq!(
+            bounds(&hollow.spans(&along_x())),
+            vec![(4f64, 4.5), (5.5, 6f64)]
+        );
+
+        // into the wall of the cavity, which faces the ray
+        let rec = hollow
+            .hit(&along_x(), &Interval::greater_than(4.1))
+            .unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!(!rec.is_front);
+        assert!((rec.normal - DVec3::NEG_X).length() < 1e-9);
+        // out of the cavity, entering the box again
+        let rec = hollow
+            .hit(&along_x(), &Interval::greater_than(4.6))
+            .unwrap();
+        assert!((rec.t - 5.5).abs() < 1e-9);
+        assert!(rec.is_front);
+        assert!((rec.normal - DVec3::NEG_X).length() < 1e-9);
+
+        // through the corner, away from the cavity
+        let ray = Ray::new(DVec3::new(-5f64, 0.9, 0.9), DVec3::X);
+        assert_eq!(bounds(&hollow.spans(&ray)), vec![(4f64, 6f64)]);
+    }
+
+    #[test]
+    fn test_nested() {
+        // the lens with a hole drilled through it
+        let material = DummyMaterial::new_shared();
+        let (a, b) = spheres();
+        let drill = crate::world::Cylinder::new(
+            DVec3::new(-2f64, 0f64, 0f64),
+            DVec3::new(2f64, 0f64, 0f64),
+            0.1,
+            &material,
+        );
+        let drilled = Csg::difference(Csg::intersection(a, b), drill);
+        assert!(drilled
+            .hit(&along_x(), &Interval::greater_than(0f64))
+            .is_none());
+        let ray = Ray::new(DVec3::new(-5f64, 0.2, 0f64), DVec3::X);
+        assert!(drilled.hit(&ray, &Interval::greater_than(0f64)).is_some());
+    }
+}
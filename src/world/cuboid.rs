@@ -0,0 +1,169 @@
+use super::aabb::Aabb;
+use super::csg::{Solid, Span};
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+use glam::DVec3;
+
+// Axis-aligned box, solid so that rays starting inside hit it from the back
+pub struct Cuboid {
+    min: DVec3,
+    max: DVec3,
+    material: SharedMaterial,
+}
+
+impl Cuboid {
+    // Any two opposite corners
+    pub fn new(a: DVec3, b: DVec3, material: &SharedMaterial) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            material: material.clone(),
+        }
+    }
+
+    pub fn from_center(center: DVec3, size: DVec3, material: &SharedMaterial) -> Self {
+        Self::new(center - size * 0.5, center + size * 0.5, material)
+    }
+
+    // Where the line of `ray` enters and leaves the box, with the outward
+    // normals of the faces it goes through
+    fn crossings(&self, ray: &Ray) -> Option<[(f64, DVec3); 2]> {
+        // Slab test, remembering which axis the ray enters and leaves the box
+        // through to know the face that was hit
+        let (mut t_enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let origin = ray.origin[axis];
+            let direction = ray.direction[axis];
+            if direction == 0f64 {
+                if origin < self.min[axis] || origin > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (self.min[axis] - origin) / direction;
+            let t1 = (self.max[axis] - origin) / direction;
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > t_enter {
+                (t_enter, enter_axis) = (near, axis);
+            }
+            if far < t_exit {
+                (t_exit, exit_axis) = (far, axis);
+            }
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+
+        // the outward normal of the entry face points against the ray, the
+        // one of the exit face along it
+        let normal = |axis: usize, sign: f64| {
+            let mut normal = DVec3::ZERO;
+            normal[axis] = sign * ray.direction[axis].signum();
+            normal
+        };
+        Some([
+            (t_enter, normal(enter_axis, -1f64)),
+            (t_exit, normal(exit_axis, 1f64)),
+        ])
+    }
+}
+
+impl Intersectable for Cuboid {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let (t, outward_normal) = self
+            .crossings(ray)?
+            .into_iter()
+            .find(|(t, _)| avaliable_range.contains(*t))?;
+        Some(IntersectRecord::new(
+            ray,
+            outward_normal,
+            t,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+impl Solid for Cuboid {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let Some([enter, exit]) = self.crossings(ray) else {
+            return vec![];
+        };
+        let record = |(t, normal)| IntersectRecord::new(ray, normal, t, self.material.clone());
+        vec![Span {
+            enter: record(enter),
+            exit: record(exit),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn unit_cube() -> Cuboid {
+        let material = DummyMaterial::new_shared();
+        Cuboid::from_center(DVec3::ZERO, DVec3::splat(2f64), &material)
+    }
+
+    #[test]
+    fn test_hit_each_face() {
+        let cube = unit_cube();
+        for normal in [
+            DVec3::X,
+            DVec3::NEG_X,
+            DVec3::Y,
+            DVec3::NEG_Y,
+            DVec3::Z,
+            DVec3::NEG_Z,
+        ] {
+            let ray = Ray::new(normal * 5f64, -normal);
+            let rec = cube.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+            assert!((rec.t - 4f64).abs() < 1e-9);
+            assert_eq!(rec.normal, normal);
+            assert!(rec.is_front);
+        }
+    }
+
+    #[test]
+    fn test_from_inside() {
+        let cube = unit_cube();
+        let ray = Ray::new(DVec3::new(0.5, 0f64, 0f64), DVec3::Y);
+        let rec = cube.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+        assert!(!rec.is_front);
+        // faces the ray, against the outward normal
+        assert_eq!(rec.normal, DVec3::NEG_Y);
+    }
+
+    #[test]
+    fn test_miss() {
+        let cube = unit_cube();
+        // parallel to a face, just outside of it
+        let ray = Ray::new(DVec3::new(1.01, 0f64, 5f64), DVec3::NEG_Z);
+        assert!(cube.hit(&ray, &Interval::greater_than(0f64)).is_none());
+        // passing by a corner
+        let ray = Ray::new(DVec3::new(0f64, 2.1, 0f64), DVec3::new(1f64, -1f64, 0f64));
+        assert!(cube.hit(&ray, &Interval::greater_than(0f64)).is_none());
+        // behind the ray
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 5f64), DVec3::Z);
+        assert!(cube.hit(&ray, &Interval::greater_than(0f64)).is_none());
+        // out of range
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 5f64), DVec3::NEG_Z);
+        assert!(cube.hit(&ray, &Interval::new(0f64, 3f64)).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let material = DummyMaterial::new_shared();
+        let cuboid = Cuboid::new(DVec3::new(1f64, 0f64, -2f64), DVec3::ZERO, &material);
+        let bbox = cuboid.bounding_box().unwrap();
+        assert_eq!(bbox.min, DVec3::new(0f64, 0f64, -2f64));
+        assert_eq!(bbox.max, DVec3::new(1f64, 0f64, 0f64));
+    }
+}
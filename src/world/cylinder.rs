@@ -0,0 +1,371 @@
+use super::aabb::Aabb;
+use super::csg::{Solid, Span};
+use super::intersectable::{IntersectRecord, Intersectable};
+use super::plane::{disk_extent, disk_hit};
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+use glam::DVec3;
+
+// Solid cylinder closed by a disk at each end
+pub struct Cylinder {
+    bottom: DVec3,
+    // unit vector from the bottom to the top
+    axis: DVec3,
+    height: f64,
+    radius: f64,
+    material: SharedMaterial,
+}
+
+impl Cylinder {
+    pub fn new(bottom: DVec3, top: DVec3, radius: f64, material: &SharedMaterial) -> Self {
+        Self {
+            bottom,
+            axis: (top - bottom).normalize(),
+            height: (top - bottom).length(),
+            radius,
+            material: material.clone(),
+        }
+    }
+
+    fn top(&self) -> DVec3 {
+        self.bottom + self.axis * self.height
+    }
+
+    // Every point where the line of `ray` crosses the surface, with the
+    // outward normal there
+    fn crossings(&self, ray: &Ray) -> Vec<(f64, DVec3)> {
+        let local = AxialRay::new(ray, self.bottom, self.axis);
+        let mut candidates = vec![];
+
+        // the side, where the distance to the axis is the radius
+        let a = local.direction.length_squared();
+        let b_half = local.origin.dot(local.direction);
+        let c = local.origin.length_squared() - self.radius * self.radius;
+        for t in solve_quadratic(a, b_half, c) {
+            if (0f64..=self.height).contains(&local.height_at(t)) {
+                candidates.push((t, local.radial_at(t) / self.radius));
+            }
+        }
+
+        if let Some(t) = disk_hit(self.bottom, self.axis, self.radius, ray) {
+            candidates.push((t, -self.axis));
+        }
+        if let Some(t) = disk_hit(self.top(), self.axis, self.radius, ray) {
+            candidates.push((t, self.axis));
+        }
+        candidates
+    }
+}
+
+impl Intersectable for Cylinder {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let (t, outward_normal) = nearest(self.crossings(ray), avaliable_range)?;
+        Some(IntersectRecord::new(
+            ray,
+            outward_normal,
+            t,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = disk_extent(self.axis, self.radius);
+        Some(Aabb::from_points([
+            self.bottom - extent,
+            self.bottom + extent,
+            self.top() - extent,
+            self.top() + extent,
+        ]))
+    }
+}
+
+// Solid cone closed by a disk at its base
+pub struct Cone {
+    base: DVec3,
+    // unit vector from the base to the apex
+    axis: DVec3,
+    height: f64,
+    radius: f64,
+    material: SharedMaterial,
+}
+
+impl Cone {
+    pub fn new(base: DVec3, apex: DVec3, radius: f64, material: &SharedMaterial) -> Self {
+        Self {
+            base,
+            axis: (apex - base).normalize(),
+            height: (apex - base).length(),
+            radius,
+            material: material.clone(),
+        }
+    }
+
+    fn apex(&self) -> DVec3 {
+        self.base + self.axis * self.height
+    }
+
+    // Same as for the cylinder
+    fn crossings(&self, ray: &Ray) -> Vec<(f64, DVec3)> {
+        let local = AxialRay::new(ray, self.base, self.axis);
+        let mut candidates = vec![];
+
+        // The side, where the distance to the axis shrinks linearly from the
+        // radius at the base to zero at the apex:
+        // |o + t d| = k (w - t dy), with w the height left above the origin
+        let k = self.radius / self.height;
+        let w = self.height - local.origin_height;
+        let dy = local.direction_height;
+        let a = local.direction.length_squared() - k * k * dy * dy;
+        let b_half = local.origin.dot(local.direction) + k * k * w * dy;
+        let c = local.origin.length_squared() - k * k * w * w;
+        for t in solve_quadratic(a, b_half, c) {
+            if (0f64..=self.height).contains(&local.height_at(t)) {
+                // gradient of the distance to the axis minus the allowed one
+                let radial = local.radial_at(t).normalize_or_zero();
+                candidates.push((t, (radial + k * self.axis).normalize()));
+            }
+        }
+
+        if let Some(t) = disk_hit(self.base, self.axis, self.radius, ray) {
+            candidates.push((t, -self.axis));
+        }
+        candidates
+    }
+}
+
+impl Intersectable for Cone {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let (t, outward_normal) = nearest(self.crossings(ray), avaliable_range)?;
+        Some(IntersectRecord::new(
+            ray,
+            outward_normal,
+            t,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = disk_extent(self.axis, self.radius);
+        Some(Aabb::from_points([
+            self.base - extent,
+            self.base + extent,
+            self.apex(),
+        ]))
+    }
+}
+
+impl Solid for Cylinder {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        convex_spans(self.crossings(ray), ray, &self.material)
+    }
+}
+
+impl Solid for Cone {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        convex_spans(self.crossings(ray), ray, &self.material)
+    }
+}
+
+// A line goes through a convex solid at most once, from its first crossing to
+// its last
+fn convex_spans(crossings: Vec<(f64, DVec3)>, ray: &Ray, material: &SharedMaterial) -> Vec<Span> {
+    let first = crossings.iter().min_by(|a, b| a.0.total_cmp(&b.0));
+    let last = crossings.iter().max_by(|a, b| a.0.total_cmp(&b.0));
+    match (first, last) {
+        (Some(&(enter, enter_normal)), Some(&(exit, exit_normal))) if enter < exit => {
+            vec![Span {
+                enter: IntersectRecord::new(ray, enter_normal, enter, material.clone()),
+                exit: IntersectRecord::new(ray, exit_normal, exit, material.clone()),
+            }]
+        }
+        _ => vec![],
+    }
+}
+
+// A ray split into its components across and along the axis of a shape
+struct AxialRay {
+    origin: DVec3,
+    direction: DVec3,
+    origin_height: f64,
+    direction_height: f64,
+}
+
+impl AxialRay {
+    fn new(ray: &Ray, base: DVec3, axis: DVec3) -> Self {
+        let origin = ray.origin - base;
+        let origin_height = origin.dot(axis);
+        let direction_height = ray.direction.dot(axis);
+        Self {
+            origin: origin - origin_height * axis,
+            direction: ray.direction - direction_height * axis,
+            origin_height,
+            direction_height,
+        }
+    }
+
+    fn height_at(&self, t: f64) -> f64 {
+        self.origin_height + t * self.direction_height
+    }
+
+    // From the axis to the point at `t`
+    fn radial_at(&self, t: f64) -> DVec3 {
+        self.origin + t * self.direction
+    }
+}
+
+// Real roots of a t² + 2 b_half t + c
+fn solve_quadratic(a: f64, b_half: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        // the ray is parallel to the side
+        if b_half.abs() < 1e-12 {
+            return vec![];
+        }
+        return vec![-c / (2f64 * b_half)];
+    }
+    let delta = b_half * b_half - a * c;
+    if delta < 0f64 {
+        return vec![];
+    }
+    let sqrt = delta.sqrt();
+    vec![(-b_half - sqrt) / a, (-b_half + sqrt) / a]
+}
+
+fn nearest(candidates: Vec<(f64, DVec3)>, avaliable_range: &Interval) -> Option<(f64, DVec3)> {
+    candidates
+        .into_iter()
+        .filter(|(t, _)| avaliable_range.contains(*t))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+#[cfg(test)]
+mod cylinder_tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn cylinder() -> Cylinder {
+        let material = DummyMaterial::new_shared();
+        Cylinder::new(DVec3::ZERO, DVec3::new(0f64, 2f64, 0f64), 1f64, &material)
+    }
+
+    #[test]
+    fn test_side() {
+        let cylinder = cylinder();
+        let ray = Ray::new(DVec3::new(5f64, 1f64, 0f64), DVec3::NEG_X);
+        let rec = cylinder.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4f64).abs() < 1e-9);
+        assert!((rec.normal - DVec3::X).length() < 1e-9);
+        assert!(rec.is_front);
+    }
+
+    #[test]
+    fn test_caps() {
+        let cylinder = cylinder();
+        let ray = Ray::new(DVec3::new(0.5, 5f64, 0f64), DVec3::NEG_Y);
+        let rec = cylinder.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 3f64).abs() < 1e-9);
+        assert_eq!(rec.normal, DVec3::Y);
+
+        let ray = Ray::new(DVec3::new(0.5, -5f64, 0f64), DVec3::Y);
+        let rec = cylinder.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 5f64).abs() < 1e-9);
+        assert_eq!(rec.normal, DVec3::NEG_Y);
+    }
+
+    #[test]
+    fn test_from_inside() {
+        let cylinder = cylinder();
+        let ray = Ray::new(DVec3::new(0f64, 1f64, 0f64), DVec3::Z);
+        let rec = cylinder.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+        assert!(!rec.is_front);
+        assert!((rec.normal - DVec3::NEG_Z).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_miss() {
+        let cylinder = cylinder();
+        // above the top
+        let ray = Ray::new(DVec3::new(5f64, 2.1, 0f64), DVec3::NEG_X);
+        assert!(cylinder.hit(&ray, &Interval::greater_than(0f64)).is_none());
+        // beside it, along the axis
+        let ray = Ray::new(DVec3::new(1.1, 5f64, 0f64), DVec3::NEG_Y);
+        assert!(cylinder.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let material = DummyMaterial::new_shared();
+        let cylinder = Cylinder::new(DVec3::ZERO, DVec3::new(2f64, 0f64, 0f64), 0.5, &material);
+        let bbox = cylinder.bounding_box().unwrap();
+        assert!((bbox.min - DVec3::new(0f64, -0.5, -0.5)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(2f64, 0.5, 0.5)).length() < 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod cone_tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn cone() -> Cone {
+        let material = DummyMaterial::new_shared();
+        Cone::new(DVec3::ZERO, DVec3::Y, 1f64, &material)
+    }
+
+    #[test]
+    fn test_side() {
+        let cone = cone();
+        // halfway up, where the radius is 0.5
+        let ray = Ray::new(DVec3::new(5f64, 0.5, 0f64), DVec3::NEG_X);
+        let rec = cone.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        let expected = DVec3::new(1f64, 1f64, 0f64).normalize();
+        assert!((rec.normal - expected).length() < 1e-9);
+        assert!(rec.is_front);
+    }
+
+    #[test]
+    fn test_base() {
+        let cone = cone();
+        let ray = Ray::new(DVec3::new(0.9, -1f64, 0f64), DVec3::Y);
+        let rec = cone.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+        assert_eq!(rec.normal, DVec3::NEG_Y);
+
+        // through the base and out of the side
+        let rec = cone
+            .hit(&ray, &Interval::greater_than(rec.t + 1e-6))
+            .unwrap();
+        assert!((rec.point.y - 0.1).abs() < 1e-9);
+        assert!(!rec.is_front);
+    }
+
+    #[test]
+    fn test_along_the_side() {
+        // parallel to the slope of the far side, only one root
+        let cone = cone();
+        let ray = Ray::new(DVec3::new(-1f64, 1f64, 0f64), DVec3::new(1f64, -1f64, 0f64));
+        let rec = cone.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.point - DVec3::new(-0.5, 0.5, 0f64)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_miss() {
+        let cone = cone();
+        // above the apex, where the double cone would be hit
+        let ray = Ray::new(DVec3::new(5f64, 1.5, 0f64), DVec3::NEG_X);
+        assert!(cone.hit(&ray, &Interval::greater_than(0f64)).is_none());
+        // near the tip
+        let ray = Ray::new(DVec3::new(5f64, 0.9, 0f64), DVec3::NEG_X);
+        let rec = cone.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.point.x - 0.1).abs() < 1e-9);
+        let ray = Ray::new(DVec3::new(5f64, 0.9, 0.2), DVec3::NEG_X);
+        assert!(cone.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let bbox = cone().bounding_box().unwrap();
+        assert!((bbox.min - DVec3::new(-1f64, 0f64, -1f64)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(1f64, 1f64, 1f64)).length() < 1e-9);
+    }
+}
@@ -0,0 +1,238 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use glam::{DQuat, DVec2, DVec3};
+use image::{ImageResult, Rgb32FImage};
+use rand::random;
+
+use super::{EnvironmentLight, LightSample};
+use crate::color::LinearRgbColor;
+use crate::distribution::Distribution2D;
+
+// Light arriving from infinitely far away, stored as an equirectangular
+// (latitude/longitude) image. The center of the image is seen looking down
+// -Z, its top row straight up.
+pub struct EnvironmentMap {
+    image: Rgb32FImage,
+    rotation: DQuat,
+    intensity: f64,
+    // over the image coordinates, following the brightness of the texels
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Rgb32FImage) -> Self {
+        let (width, height) = image.dimensions();
+        // Lookups blend each texel with its neighbours, so light seen through
+        // a texel may come from any of them. The brightest one keeps the
+        // density positive wherever the radiance is.
+        let func: Vec<f64> = (0..height as i64)
+            .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let brightest = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                    .map(|(dx, dy)| LinearRgbColor::from_vec(&texel(&image, x + dx, y + dy)))
+                    .map(|color| color.luminance())
+                    .fold(0f64, f64::max);
+                // rows near the poles cover less of the sphere
+                let sin_theta = ((y as f64 + 0.5) / height as f64 * PI).sin();
+                brightest * sin_theta
+            })
+            .collect();
+        let distribution = Distribution2D::new(&func, width as usize, height as usize);
+        Self {
+            image,
+            rotation: DQuat::IDENTITY,
+            intensity: 1f64,
+            distribution,
+        }
+    }
+
+    // Any format `image` can decode works, but only HDR ones (Radiance .hdr,
+    // OpenEXR) hold linear radiance above 1.
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Ok(Self::new(image::open(path)?.into_rgb32f()))
+    }
+
+    // Rotates the whole environment around the scene
+    pub fn with_rotation(mut self, rotation: DQuat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // Scales the radiance of every pixel
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn lookup(&self, direction: DVec3) -> LinearRgbColor {
+        let uv = direction_to_uv(self.rotation.inverse() * direction.normalize());
+        LinearRgbColor::from_vec(&(self.bilinear(uv) * self.intensity))
+    }
+
+    fn bilinear(&self, uv: DVec2) -> DVec3 {
+        let (width, height) = self.image.dimensions();
+        // texel centers sit at half integers
+        let x = uv.x * width as f64 - 0.5;
+        let y = uv.y * height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let texel = |x, y| texel(&self.image, x, y);
+        let top = texel(x0, y0).lerp(texel(x0 + 1, y0), fx);
+        let bottom = texel(x0, y0 + 1).lerp(texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+impl EnvironmentLight for EnvironmentMap {
+    fn radiance(&self, direction: DVec3) -> LinearRgbColor {
+        self.lookup(direction)
+    }
+
+    fn sample(&self) -> Option<LightSample> {
+        let (uv, uv_pdf) = self.distribution.sample(DVec2::new(random(), random()));
+        let sin_theta = (uv.y * PI).sin();
+        if uv_pdf == 0f64 || sin_theta == 0f64 {
+            return None;
+        }
+        Some(LightSample {
+            direction: self.rotation * uv_to_direction(uv),
+            // the image spans 2 PI by PI radians
+            pdf: uv_pdf / (2f64 * PI * PI * sin_theta),
+        })
+    }
+
+    fn pdf(&self, direction: DVec3) -> f64 {
+        let uv = direction_to_uv(self.rotation.inverse() * direction.normalize());
+        let sin_theta = (uv.y * PI).sin();
+        if sin_theta == 0f64 {
+            return 0f64;
+        }
+        self.distribution.pdf(uv) / (2f64 * PI * PI * sin_theta)
+    }
+}
+
+fn texel(image: &Rgb32FImage, x: i64, y: i64) -> DVec3 {
+    let (width, height) = image.dimensions();
+    // wraps around horizontally, clamps at the poles
+    let x = x.rem_euclid(width as i64) as u32;
+    let y = y.clamp(0, height as i64 - 1) as u32;
+    let [r, g, b] = image.get_pixel(x, y).0;
+    DVec3::new(r as f64, g as f64, b as f64)
+}
+
+// Maps a unit direction to [0, 1]^2 image coordinates, v growing downwards
+pub fn direction_to_uv(direction: DVec3) -> DVec2 {
+    let phi = direction.x.atan2(-direction.z);
+    let theta = direction.y.clamp(-1f64, 1f64).acos();
+    DVec2::new(0.5 + phi / (2f64 * PI), theta / PI)
+}
+
+pub fn uv_to_direction(uv: DVec2) -> DVec3 {
+    let phi = (uv.x - 0.5) * 2f64 * PI;
+    let theta = uv.y * PI;
+    DVec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_uv_round_trip() {
+        assert!(direction_to_uv(DVec3::NEG_Z).abs_diff_eq(DVec2::new(0.5, 0.5), 1e-12));
+        assert!(direction_to_uv(DVec3::X).abs_diff_eq(DVec2::new(0.75, 0.5), 1e-12));
+        assert_eq!(direction_to_uv(DVec3::Y).y, 0f64);
+        for uv in [
+            DVec2::new(0.1, 0.2),
+            DVec2::new(0.6, 0.9),
+            DVec2::new(0.99, 0.5),
+        ] {
+            assert!(direction_to_uv(uv_to_direction(uv)).abs_diff_eq(uv, 1e-12));
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        // left half red, right half blue
+        let image = Rgb32FImage::from_fn(4, 2, |x, _| {
+            if x < 2 {
+                Rgb([1f32, 0f32, 0f32])
+            } else {
+                Rgb([0f32, 0f32, 1f32])
+            }
+        });
+        let env = EnvironmentMap::new(image).with_intensity(2f64);
+        assert_eq!(
+            env.lookup(DVec3::NEG_X).to_vec(),
+            DVec3::new(2f64, 0f64, 0f64)
+        );
+        assert_eq!(env.lookup(DVec3::X).to_vec(), DVec3::new(0f64, 0f64, 2f64));
+        // the seam in the middle blends both halves
+        assert_eq!(
+            env.lookup(DVec3::NEG_Z).to_vec(),
+            DVec3::new(1f64, 0f64, 1f64)
+        );
+
+        // turning the map half a turn swaps the sides
+        let env = env.with_rotation(DQuat::from_rotation_y(PI));
+        assert!(env
+            .lookup(DVec3::NEG_X)
+            .to_vec()
+            .abs_diff_eq(DVec3::new(0f64, 0f64, 2f64), 1e-6));
+    }
+
+    #[test]
+    fn test_sampling_follows_brightness() {
+        // a single bright texel, above the horizon on the +X side
+        let image = Rgb32FImage::from_fn(8, 4, |x, y| {
+            if (x, y) == (6, 1) {
+                Rgb([100f32, 100f32, 100f32])
+            } else {
+                Rgb([0f32, 0f32, 0f32])
+            }
+        });
+        let rotation = DQuat::from_rotation_y(0.3);
+        let env = EnvironmentMap::new(image).with_rotation(rotation);
+        for _ in 0..100 {
+            let sample = env.sample().unwrap();
+            // within the texels the bright one is blended into
+            let uv = direction_to_uv(rotation.inverse() * sample.direction);
+            let (x, y) = ((uv.x * 8f64) as i64, (uv.y * 4f64) as i64);
+            assert!((5..=7).contains(&x) && (0..=2).contains(&y), "{x} {y}");
+            assert!((env.pdf(sample.direction) - sample.pdf).abs() < 1e-9 * sample.pdf);
+        }
+
+        // wherever the bright texel is seen, even blended into a dark one
+        for i in 0..64 {
+            for j in 0..32 {
+                let uv = DVec2::new((i as f64 + 0.5) / 64f64, (j as f64 + 0.5) / 32f64);
+                let direction = rotation * uv_to_direction(uv);
+                if env.radiance(direction).r() > 0f64 {
+                    assert!(env.pdf(direction) > 0f64, "{uv}");
+                }
+            }
+        }
+
+        // the density integrates to one over the sphere
+        let n = 400;
+        let mut total = 0f64;
+        for i in 0..n {
+            let z = -1f64 + (i as f64 + 0.5) * 2f64 / n as f64;
+            let r = (1f64 - z * z).sqrt();
+            for j in 0..n {
+                let phi = (j as f64 + 0.5) * 2f64 * PI / n as f64;
+                total += env.pdf(DVec3::new(r * phi.cos(), z, r * phi.sin()));
+            }
+        }
+        let integral = total * 4f64 * PI / (n * n) as f64;
+        assert!((integral - 1f64).abs() < 0.01, "{integral}");
+    }
+}
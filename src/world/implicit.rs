@@ -0,0 +1,382 @@
+use super::aabb::Aabb;
+use super::intersectable::{IntersectRecord, Intersectable};
+use super::polynomial::Polynomial;
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+use glam::DVec3;
+
+// A function of space whose zero level is a surface, negative inside
+pub trait ImplicitFunction: Send + Sync {
+    fn value(&self, p: DVec3) -> f64;
+
+    // Points outwards, by central differences unless the function knows
+    // better
+    fn gradient(&self, p: DVec3) -> DVec3 {
+        let h = 1e-6 * (1f64 + p.abs().max_element());
+        let d = |offset: DVec3| self.value(p + offset) - self.value(p - offset);
+        DVec3::new(d(DVec3::X * h), d(DVec3::Y * h), d(DVec3::Z * h)) / (2f64 * h)
+    }
+
+    // Sorted parameters of `ray` within the range, which is finite, where the
+    // function changes sign
+    fn crossings(&self, ray: &Ray, range: &Interval) -> Vec<f64>;
+}
+
+// Sum of terms `coefficient * x^i * y^j * z^k`, e.g. rings, pipes and other
+// algebraic surfaces. Along a ray it is a polynomial in the ray parameter,
+// solved exactly.
+pub struct PolynomialFunction {
+    terms: Vec<(f64, [u32; 3])>,
+}
+
+impl PolynomialFunction {
+    // Each term as its coefficient and the powers of x, y and z
+    pub fn new(terms: Vec<(f64, [u32; 3])>) -> Self {
+        Self { terms }
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.terms
+            .iter()
+            .map(|(_, powers)| powers.iter().sum())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl ImplicitFunction for PolynomialFunction {
+    fn value(&self, p: DVec3) -> f64 {
+        self.terms
+            .iter()
+            .map(|(c, [i, j, k])| {
+                c * p.x.powi(*i as i32) * p.y.powi(*j as i32) * p.z.powi(*k as i32)
+            })
+            .sum()
+    }
+
+    fn gradient(&self, p: DVec3) -> DVec3 {
+        // d/dx of x^i is i x^(i-1), no term for a zero power
+        let derivative = |v: f64, power: u32| {
+            if power == 0 {
+                0f64
+            } else {
+                power as f64 * v.powi(power as i32 - 1)
+            }
+        };
+        self.terms
+            .iter()
+            .map(|(c, [i, j, k])| {
+                let (x, y, z) = (
+                    p.x.powi(*i as i32),
+                    p.y.powi(*j as i32),
+                    p.z.powi(*k as i32),
+                );
+                *c * DVec3::new(
+                    derivative(p.x, *i) * y * z,
+                    x * derivative(p.y, *j) * z,
+                    x * y * derivative(p.z, *k),
+                )
+            })
+            .sum()
+    }
+
+    fn crossings(&self, ray: &Ray, range: &Interval) -> Vec<f64> {
+        // Substituting the ray from the start of the range, e.g. where it
+        // enters the bounding box, keeps the coefficients small
+        let origin = ray.at(range.lower);
+        let max_power = self
+            .terms
+            .iter()
+            .flat_map(|(_, powers)| *powers)
+            .max()
+            .unwrap_or(0);
+        let powers: Vec<Vec<Polynomial>> = (0..3)
+            .map(|axis| {
+                let coordinate = Polynomial::linear(origin[axis], ray.direction[axis]);
+                (0..=max_power).map(|n| coordinate.powi(n)).collect()
+            })
+            .collect();
+
+        let mut along_ray = Polynomial::new(vec![]);
+        for (c, [i, j, k]) in &self.terms {
+            let term =
+                &(&powers[0][*i as usize] * &powers[1][*j as usize]) * &powers[2][*k as usize];
+            along_ray = &along_ray + &(&term * *c);
+        }
+        along_ray
+            .roots_in(&Interval::new(0f64, range.upper - range.lower))
+            .into_iter()
+            .map(|s| range.lower + s)
+            .collect()
+    }
+}
+
+// Any function, sampled at even steps along the ray and refined where it
+// changes sign in between. Parts of the surface thinner than a step may be
+// missed, more samples find them at a higher cost.
+//
+// Given a Lipschitz constant instead, a bound on how much the function changes
+// over a unit of distance, stretches of the ray too far from zero to reach it are
+// skipped and the rest is split until every crossing is found, however thin.
+pub struct ImplicitFn<F> {
+    function: F,
+    samples: usize,
+    lipschitz: Option<f64>,
+}
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> ImplicitFn<F> {
+    pub fn new(function: F) -> Self {
+        Self {
+            function,
+            samples: 64,
+            lipschitz: None,
+        }
+    }
+
+    // Per ray, across the bounding box
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    // Bound on the length of the gradient within the bounding box, e.g. 1 for
+    // signed distances. A bound too low misses crossings, one too high only
+    // costs time.
+    pub fn with_lipschitz(mut self, lipschitz: f64) -> Self {
+        self.lipschitz = Some(lipschitz);
+        self
+    }
+
+    // Where the function changes sign between `a` and `b`, given its value at
+    // `a`
+    fn bisect(&self, ray: &Ray, (a, fa): (f64, f64), b: f64) -> f64 {
+        let (mut low, mut high) = (a, b);
+        for _ in 0..REFINE_STEPS {
+            let middle = 0.5 * (low + high);
+            if (self.value(ray.at(middle)) < 0f64) == (fa < 0f64) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        0.5 * (low + high)
+    }
+
+    // Crossings between `a` and `b`, which are both sampled already, into
+    // `crossings` in order. A crossing needs the function to get from both
+    // ends down to zero, which takes `|f| / (lipschitz * speed)` of the ray
+    // parameter from each, so none fit if those add up to more than the
+    // whole stretch.
+    fn search(
+        &self,
+        ray: &Ray,
+        rate: f64,
+        min_width: f64,
+        (a, fa): (f64, f64),
+        (b, fb): (f64, f64),
+        crossings: &mut Vec<f64>,
+    ) {
+        if fa.abs() + fb.abs() > rate * (b - a) {
+            return;
+        }
+        if b - a <= min_width {
+            // a pair of crossings closer than this is only touching
+            if (fa < 0f64) != (fb < 0f64) {
+                crossings.push(self.bisect(ray, (a, fa), b));
+            }
+            return;
+        }
+        let m = 0.5 * (a + b);
+        let fm = self.value(ray.at(m));
+        self.search(ray, rate, min_width, (a, fa), (m, fm), crossings);
+        self.search(ray, rate, min_width, (m, fm), (b, fb), crossings);
+    }
+}
+
+// Bisection steps refining a crossing
+const REFINE_STEPS: usize = 50;
+
+// Smallest part of the range searched with a Lipschitz bound, relative to the
+// whole of it. Rays running along the surface are split down to it all the
+// way, so it cannot be much smaller.
+const MIN_WIDTH: f64 = 1e-6;
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> ImplicitFunction for ImplicitFn<F> {
+    fn value(&self, p: DVec3) -> f64 {
+        (self.function)(p)
+    }
+
+    fn crossings(&self, ray: &Ray, range: &Interval) -> Vec<f64> {
+        if let Some(lipschitz) = self.lipschitz {
+            let mut crossings = vec![];
+            let (a, b) = (range.lower, range.upper);
+            self.search(
+                ray,
+                lipschitz * ray.direction.length(),
+                MIN_WIDTH * (b - a),
+                (a, self.value(ray.at(a))),
+                (b, self.value(ray.at(b))),
+                &mut crossings,
+            );
+            return crossings;
+        }
+        let step = (range.upper - range.lower) / self.samples as f64;
+        let value_at = |t: f64| self.value(ray.at(t));
+        let mut crossings = vec![];
+        let (mut a, mut fa) = (range.lower, value_at(range.lower));
+        for n in 1..=self.samples {
+            let b = range.lower + step * n as f64;
+            let fb = value_at(b);
+            if fa == 0f64 {
+                crossings.push(a);
+            } else if fa.signum() != fb.signum() && fb != 0f64 {
+                crossings.push(self.bisect(ray, (a, fa), b));
+            }
+            (a, fa) = (b, fb);
+        }
+        if fa == 0f64 {
+            crossings.push(a);
+        }
+        crossings
+    }
+}
+
+// The zero level of an implicit function within a bounding box, which keeps
+// the search for crossings finite
+pub struct ImplicitSurface<F: ImplicitFunction> {
+    function: F,
+    bounds: Aabb,
+    material: SharedMaterial,
+}
+
+impl<F: ImplicitFunction> ImplicitSurface<F> {
+    pub fn new(function: F, bounds: Aabb, material: &SharedMaterial) -> Self {
+        Self {
+            function,
+            bounds,
+            material: material.clone(),
+        }
+    }
+}
+
+impl<F: ImplicitFunction> Intersectable for ImplicitSurface<F> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let range = self.bounds.hit(ray, avaliable_range)?;
+        let t = *self
+            .function
+            .crossings(ray, &range)
+            .iter()
+            .find(|t| avaliable_range.contains(**t))?;
+        let normal = self.function.gradient(ray.at(t)).normalize_or_zero();
+        Some(IntersectRecord::new(ray, normal, t, self.material.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    // x^2 + y^2 + z^2 - 1
+    fn unit_sphere() -> PolynomialFunction {
+        PolynomialFunction::new(vec![
+            (1f64, [2, 0, 0]),
+            (1f64, [0, 2, 0]),
+            (1f64, [0, 0, 2]),
+            (-1f64, [0, 0, 0]),
+        ])
+    }
+
+    fn bounds() -> Aabb {
+        Aabb::new(DVec3::splat(-2f64), DVec3::splat(2f64))
+    }
+
+    #[test]
+    fn test_polynomial_sphere() {
+        let material = DummyMaterial::new_shared();
+        let function = unit_sphere();
+        assert_eq!(function.degree(), 2);
+        assert_eq!(function.gradient(DVec3::X), DVec3::new(2f64, 0f64, 0f64));
+
+        let surface = ImplicitSurface::new(function, bounds(), &material);
+        let ray = Ray::new(DVec3::new(0f64, 0.6, 5f64), DVec3::NEG_Z);
+        let rec = surface.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4.2).abs() < 1e-9);
+        assert!((rec.normal - DVec3::new(0f64, 0.6, 0.8)).length() < 1e-9);
+        assert!(rec.is_front);
+
+        // from inside
+        let ray = Ray::new(DVec3::ZERO, DVec3::X);
+        let rec = surface.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+        assert!(!rec.is_front);
+    }
+
+    #[test]
+    fn test_polynomial_torus() {
+        // (x^2 + y^2 + z^2 + R^2 - r^2)^2 - 4 R^2 (x^2 + z^2) with R = 1,
+        // r = 0.5, expanded
+        let function = PolynomialFunction::new(vec![
+            (1f64, [4, 0, 0]),
+            (1f64, [0, 4, 0]),
+            (1f64, [0, 0, 4]),
+            (2f64, [2, 2, 0]),
+            (2f64, [2, 0, 2]),
+            (2f64, [0, 2, 2]),
+            (-2.5, [2, 0, 0]),
+            (1.5, [0, 2, 0]),
+            (-2.5, [0, 0, 2]),
+            (0.5625, [0, 0, 0]),
+        ]);
+        assert_eq!(function.degree(), 4);
+        let ray = Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X);
+        let crossings = function.crossings(&ray, &Interval::new(0f64, 10f64));
+        let expected = [3.5, 4.5, 5.5, 6.5];
+        assert_eq!(crossings.len(), 4);
+        for (t, e) in crossings.iter().zip(expected) {
+            assert!((t - e).abs() < 1e-9, "{:?}", crossings);
+        }
+    }
+
+    #[test]
+    fn test_closure() {
+        let material = DummyMaterial::new_shared();
+        let function = ImplicitFn::new(|p: DVec3| p.length_squared() - 1f64);
+        let surface = ImplicitSurface::new(function, bounds(), &material);
+        let ray = Ray::new(DVec3::new(0f64, 0.6, 5f64), DVec3::NEG_Z);
+        let rec = surface.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4.2).abs() < 1e-9);
+        assert!((rec.normal - DVec3::new(0f64, 0.6, 0.8)).length() < 1e-6);
+
+        // thinner than a step between samples
+        let thin = ImplicitFn::new(|p: DVec3| p.x.abs() - 1e-3);
+        let ray = Ray::new(DVec3::new(-2.03, 0.1, 0.1), DVec3::X);
+        let range = Interval::new(0f64, 4f64);
+        assert!(thin.crossings(&ray, &range).is_empty());
+        assert_eq!(thin.with_samples(4001).crossings(&ray, &range).len(), 2);
+    }
+
+    #[test]
+    fn test_lipschitz() {
+        // found however thin, without choosing a number of samples
+        let thin = ImplicitFn::new(|p: DVec3| p.x.abs() - 1e-5).with_lipschitz(1f64);
+        let ray = Ray::new(DVec3::new(-2.03, 0.1, 0.1), DVec3::new(2f64, 0f64, 0f64));
+        let crossings = thin.crossings(&ray, &Interval::new(0f64, 2f64));
+        assert_eq!(crossings.len(), 2, "{:?}", crossings);
+        assert!((crossings[0] * 2f64 - (2.03 - 1e-5)).abs() < 1e-9);
+        assert!((crossings[1] * 2f64 - (2.03 + 1e-5)).abs() < 1e-9);
+
+        // |p|^2 - 1 changes by at most 2 |p| over a unit, under 7 in the box
+        let material = DummyMaterial::new_shared();
+        let function = ImplicitFn::new(|p: DVec3| p.length_squared() - 1f64).with_lipschitz(7f64);
+        let surface = ImplicitSurface::new(function, bounds(), &material);
+        let ray = Ray::new(DVec3::new(0f64, 0.6, 5f64), DVec3::NEG_Z);
+        let rec = surface.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 4.2).abs() < 1e-9);
+        let ray = Ray::new(DVec3::new(0f64, 1.2, 5f64), DVec3::NEG_Z);
+        assert!(surface.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+}
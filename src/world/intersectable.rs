@@ -1,10 +1,19 @@
 use crate::materials::SharedMaterial;
 use crate::ray::Ray;
 use crate::utils::Interval;
-use glam::DVec3;
+use crate::world::Aabb;
+use glam::{DVec2, DVec3};
+use std::sync::Arc;
 
-pub trait Intersectable: Sync {
+pub trait Intersectable: Send + Sync {
     fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord>;
+
+    // The box enclosing the object, or None if it is unbounded (e.g. an
+    // infinite plane).
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
     fn into_box(self) -> Box<dyn Intersectable + 'static>
     where
         Self: Sized + 'static,
@@ -13,6 +22,17 @@ pub trait Intersectable: Sync {
     }
 }
 
+// Lets a single object be shared, e.g. by a container and the light list
+impl<T: Intersectable + ?Sized> Intersectable for Arc<T> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        (**self).hit(ray, avaliable_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
 pub struct IntersectRecord {
     pub point: DVec3,
     // Note that normal should unit vector.
@@ -21,6 +41,9 @@ pub struct IntersectRecord {
     pub t: f64,
     pub is_front: bool,
     pub mat: SharedMaterial,
+    // Surface coordinates of the hit point. Triangles report the barycentric
+    // weights of their second and third vertices here.
+    pub uv: DVec2,
 }
 
 impl IntersectRecord {
@@ -39,6 +62,12 @@ impl IntersectRecord {
             t,
             is_front,
             mat,
+            uv: DVec2::ZERO,
         }
     }
+
+    pub fn with_uv(mut self, uv: DVec2) -> Self {
+        self.uv = uv;
+        self
+    }
 }
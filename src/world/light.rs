@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use glam::DVec3;
+
+use super::Intersectable;
+use crate::color::LinearRgbColor;
+
+pub struct LightSample {
+    // unit vector from the shaded point towards the light
+    pub direction: DVec3,
+    // density of `direction`, with respect to solid angle
+    pub pdf: f64,
+}
+
+// A shape that can be sampled directly, so that the integrator can aim shadow
+// rays at it instead of waiting for paths to hit it by chance.
+pub trait Light: Intersectable {
+    // Samples a direction from `origin` towards the surface, or None if the
+    // surface cannot be seen from there.
+    fn sample(&self, origin: DVec3) -> Option<LightSample>;
+
+    // Density `sample` would give to `direction`, zero when it misses.
+    fn pdf(&self, origin: DVec3, direction: DVec3) -> f64;
+}
+
+pub type SharedLight = Arc<dyn Light>;
+
+// Light arriving from infinitely far away, around all of the geometry, like
+// the background of a scene. Sampled independently of the shaded point.
+pub trait EnvironmentLight: Send + Sync {
+    fn radiance(&self, direction: DVec3) -> LinearRgbColor;
+
+    fn sample(&self) -> Option<LightSample>;
+
+    fn pdf(&self, direction: DVec3) -> f64;
+}
@@ -0,0 +1,152 @@
+use rand::random;
+
+use super::aabb::Aabb;
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+
+// Past an entry into the boundary, where to look for the exit
+const EPS: f64 = 1e-6;
+
+// Smoke, fog or anything else of the same density throughout the inside of a
+// closed boundary. Light going through it scatters at random points, after
+// distances following the exponential distribution of free flights. The hit
+// is such a point, scattered by the phase function of the medium.
+pub struct ConstantMedium<B: Intersectable> {
+    boundary: B,
+    // chance of scattering per unit of length
+    density: f64,
+    phase: SharedMaterial,
+}
+
+impl<B: Intersectable> ConstantMedium<B> {
+    pub fn new(boundary: B, density: f64, phase: &SharedMaterial) -> Self {
+        Self {
+            boundary,
+            density,
+            phase: phase.clone(),
+        }
+    }
+
+    // Parts of the line of `ray` inside of the boundary, from its entry to its
+    // exit, clipped to the range. The boundary may be concave and the ray go
+    // in and out several times.
+    fn segments(&self, ray: &Ray, avaliable_range: &Interval) -> Vec<Interval> {
+        let mut segments = vec![];
+        let mut from = f64::NEG_INFINITY;
+        while from < avaliable_range.upper {
+            let Some(enter) = self.boundary.hit(ray, &Interval::greater_than(from)) else {
+                break;
+            };
+            let Some(exit) = self
+                .boundary
+                .hit(ray, &Interval::greater_than(enter.t + EPS))
+            else {
+                break;
+            };
+            let segment = Interval::new(
+                enter.t.max(avaliable_range.lower),
+                exit.t.min(avaliable_range.upper),
+            );
+            if segment.lower < segment.upper {
+                segments.push(segment);
+            }
+            from = exit.t + EPS;
+        }
+        segments
+    }
+}
+
+impl<B: Intersectable> Intersectable for ConstantMedium<B> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let length = ray.direction.length();
+        // Free flights are memoryless, a fresh one for each segment is as good
+        // as one across all of them
+        for segment in self.segments(ray, avaliable_range) {
+            let distance = -(1f64 - random::<f64>()).ln() / self.density;
+            let t = segment.lower + distance / length;
+            if t < segment.upper {
+                // no surface, any normal facing the ray will do
+                let normal = -ray.direction / length;
+                return Some(IntersectRecord::new(ray, normal, t, self.phase.clone()));
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::world::{Csg, Cuboid, Sphere};
+    use glam::DVec3;
+
+    // Fraction of the light going straight through `distance` of the medium
+    fn transmittance(density: f64, distance: f64) -> f64 {
+        (-density * distance).exp()
+    }
+
+    // Checks the fraction of many rays along `ray` that get through without
+    // scattering, within five standard errors of the expected one
+    fn assert_passing(medium: &dyn Intersectable, ray: &Ray, range: &Interval, expected: f64) {
+        let n = 100000;
+        let passed = (0..n).filter(|_| medium.hit(ray, range).is_none()).count();
+        let passed = passed as f64 / n as f64;
+        let error = (expected * (1f64 - expected) / n as f64).sqrt();
+        assert!(
+            (passed - expected).abs() <= 5f64 * error,
+            "{passed} != {expected}"
+        );
+    }
+
+    #[test]
+    fn test_transmittance() {
+        let material = DummyMaterial::new_shared();
+        let boundary = Cuboid::new(DVec3::splat(-1f64), DVec3::ONE, &material);
+        let fog = ConstantMedium::new(boundary, 0.5, &material);
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 5f64), DVec3::new(0f64, 0f64, -2f64));
+        let range = Interval::greater_than(0f64);
+        assert_passing(&fog, &ray, &range, transmittance(0.5, 2f64));
+
+        // every scattering point is inside
+        for _ in 0..1000 {
+            if let Some(rec) = fog.hit(&ray, &range) {
+                assert!(rec.point.z.abs() <= 1f64 && (2f64..=3f64).contains(&rec.t));
+                assert!(rec.is_front);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_inside() {
+        // only the part ahead of the ray, half of the box
+        let material = DummyMaterial::new_shared();
+        let boundary = Cuboid::new(DVec3::splat(-1f64), DVec3::ONE, &material);
+        let fog = ConstantMedium::new(boundary, 1f64, &material);
+        let ray = Ray::new(DVec3::ZERO, DVec3::X);
+        let range = Interval::greater_than(0f64);
+        assert_passing(&fog, &ray, &range, transmittance(1f64, 1f64));
+        // and only up to the end of the range
+        let range = Interval::new(0f64, 0.5);
+        assert_passing(&fog, &ray, &range, transmittance(1f64, 0.5));
+    }
+
+    #[test]
+    fn test_concave_boundary() {
+        // two slabs of a hollow ball, with nothing in the cavity
+        let material = DummyMaterial::new_shared();
+        let shell = Csg::difference(
+            Sphere::new(DVec3::ZERO, 2f64, &material),
+            Sphere::new(DVec3::ZERO, 1f64, &material),
+        );
+        let fog = ConstantMedium::new(shell, 0.5, &material);
+        let ray = Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X);
+        let range = Interval::greater_than(0f64);
+        assert_passing(&fog, &ray, &range, transmittance(0.5, 2f64));
+        assert!(fog.bounding_box().is_some());
+    }
+}
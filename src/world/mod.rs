@@ -1,11 +1,45 @@
+pub mod aabb;
+pub mod bvh;
 pub mod containers;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod environment;
+pub mod implicit;
 pub mod intersectable;
+pub mod light;
+pub mod medium;
+pub mod obj;
 pub mod plane;
+pub mod polynomial;
 pub mod scene;
+pub mod scene_graph;
+pub mod sdf;
+pub mod sky;
 pub mod sphere;
+pub mod torus;
+pub mod transformed;
+pub mod triangle;
 
+pub use aabb::Aabb;
+pub use bvh::{BvhContainer, BvhStats};
 pub use containers::{IntersectContainer, VecContainer};
+pub use csg::{first_hit, Csg, CsgOperation, SharedSolid, Solid, Span};
+pub use cuboid::Cuboid;
+pub use cylinder::{Cone, Cylinder};
+pub use environment::EnvironmentMap;
+pub use implicit::{ImplicitFn, ImplicitFunction, ImplicitSurface, PolynomialFunction};
 pub use intersectable::{IntersectRecord, Intersectable};
-pub use plane::{InfinitePlane, Rectangle};
-pub use scene::{LerpScene, Scene};
+pub use light::{EnvironmentLight, Light, LightSample, SharedLight};
+pub use medium::ConstantMedium;
+pub use obj::{load_obj, ObjError, ObjObject};
+pub use plane::{Disk, InfinitePlane, Rectangle};
+pub use polynomial::Polynomial;
+pub use scene::{EnvMapScene, EnvironmentScene, LerpScene, Scene, SkyScene};
+pub use scene_graph::{FlatScene, NodeContent, SceneGraph, SceneNode, ShapeTemplate, SharedShape};
+pub use sdf::{Sdf, SdfFn, SdfShape, SharedSdf};
+pub use sky::PhysicalSky;
 pub use sphere::Sphere;
+pub use torus::Torus;
+pub use transformed::{SharedIntersectable, Transformed};
+pub use triangle::{MeshBuffers, MeshTriangle, Triangle, TriangleMesh};
@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::DVec3;
+
+use super::triangle::{MeshBuffers, TriangleMesh};
+use crate::materials::{
+    DielectricMaterial, LambertianMaterial, Material, MetalMaterial, SharedMaterial,
+};
+
+#[derive(Debug)]
+pub struct ObjError {
+    path: Option<PathBuf>,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ObjError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    fn at_line(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    fn in_file(mut self, path: &Path) -> Self {
+        self.path.get_or_insert_with(|| path.to_path_buf());
+        self
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if self.path.is_some() || self.line.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// The subset of an MTL material we can map onto our own materials.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MtlMaterial {
+    Lambertian { albedo: DVec3 },
+    Metal { albedo: DVec3, fuzz: f64 },
+    Dielectric { index_of_refraction: f64 },
+}
+
+impl MtlMaterial {
+    pub fn to_material(&self) -> SharedMaterial {
+        match *self {
+            MtlMaterial::Lambertian { albedo } => {
+                LambertianMaterial::make_shared(LambertianMaterial::new(albedo))
+            }
+            MtlMaterial::Metal { albedo, fuzz } => {
+                MetalMaterial::make_shared(MetalMaterial::new(albedo, fuzz))
+            }
+            MtlMaterial::Dielectric {
+                index_of_refraction,
+            } => DielectricMaterial::make_shared(DielectricMaterial::new(index_of_refraction)),
+        }
+    }
+}
+
+// Raw statements of a `newmtl` block
+struct MtlStatements {
+    kd: DVec3,
+    ks: DVec3,
+    ns: f64,
+    ni: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for MtlStatements {
+    fn default() -> Self {
+        Self {
+            kd: DVec3::splat(0.8),
+            ks: DVec3::ZERO,
+            ns: 0f64,
+            ni: 1.5,
+            dissolve: 1f64,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlStatements {
+    fn to_mtl_material(&self) -> MtlMaterial {
+        let luminance = |c: DVec3| c.dot(DVec3::new(0.2126, 0.7152, 0.0722));
+        if self.dissolve < 1f64 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            // see-through, treated as glass
+            MtlMaterial::Dielectric {
+                index_of_refraction: self.ni,
+            }
+        } else if self.illum == 3 || luminance(self.ks) > luminance(self.kd) {
+            // mostly specular, a high exponent gives a sharp reflection
+            MtlMaterial::Metal {
+                albedo: self.ks,
+                fuzz: (2f64 / (self.ns + 2f64)).sqrt(),
+            }
+        } else {
+            MtlMaterial::Lambertian { albedo: self.kd }
+        }
+    }
+}
+
+fn parse_floats<const N: usize>(
+    line_no: usize,
+    keyword: &str,
+    args: &[&str],
+) -> Result<[f64; N], ObjError> {
+    if args.len() < N {
+        return Err(ObjError::at_line(
+            line_no,
+            format!("`{}` expects {} numbers", keyword, N),
+        ));
+    }
+    let mut result = [0f64; N];
+    for (value, arg) in result.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| ObjError::at_line(line_no, format!("`{}` is not a number", arg)))?;
+    }
+    Ok(result)
+}
+
+// Iterates over the non-empty, non-comment lines as (1-based line number,
+// keyword, arguments).
+fn statements(source: &str) -> impl Iterator<Item = (usize, &str, Vec<&str>)> {
+    source.lines().enumerate().filter_map(|(i, line)| {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next()?;
+        Some((i + 1, keyword, tokens.collect()))
+    })
+}
+
+pub fn parse_mtl(source: &str) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut result = HashMap::new();
+    let mut current: Option<(String, MtlStatements)> = None;
+    for (line_no, keyword, args) in statements(source) {
+        if keyword == "newmtl" {
+            if let Some((name, statements)) = current.take() {
+                result.insert(name, statements.to_mtl_material());
+            }
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(ObjError::at_line(line_no, "`newmtl` without a name"));
+            }
+            current = Some((name, MtlStatements::default()));
+            continue;
+        }
+        let Some((_, statements)) = current.as_mut() else {
+            return Err(ObjError::at_line(
+                line_no,
+                format!("`{}` before any `newmtl`", keyword),
+            ));
+        };
+        match keyword {
+            "Kd" => statements.kd = parse_floats::<3>(line_no, keyword, &args)?.into(),
+            "Ks" => statements.ks = parse_floats::<3>(line_no, keyword, &args)?.into(),
+            "Ns" => statements.ns = parse_floats::<1>(line_no, keyword, &args)?[0],
+            "Ni" => statements.ni = parse_floats::<1>(line_no, keyword, &args)?[0],
+            "d" => statements.dissolve = parse_floats::<1>(line_no, keyword, &args)?[0],
+            "Tr" => statements.dissolve = 1f64 - parse_floats::<1>(line_no, keyword, &args)?[0],
+            "illum" => statements.illum = parse_floats::<1>(line_no, keyword, &args)?[0] as u32,
+            // textures, emission, etc. are not supported
+            _ => {}
+        }
+    }
+    if let Some((name, statements)) = current {
+        result.insert(name, statements.to_mtl_material());
+    }
+    Ok(result)
+}
+
+pub struct ObjObject {
+    pub name: String,
+    pub mesh: TriangleMesh,
+}
+
+// Faces of one `o` block, before the vertices are compacted into its own buffer
+struct PendingObject {
+    name: String,
+    faces: Vec<[usize; 3]>,
+    face_materials: Vec<usize>,
+}
+
+impl PendingObject {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            faces: vec![],
+            face_materials: vec![],
+        }
+    }
+
+    fn build(self, vertices: &[DVec3], materials: &[SharedMaterial]) -> Option<ObjObject> {
+        if self.faces.is_empty() {
+            return None;
+        }
+        // keep only the vertices this object refers to
+        let mut remap = HashMap::new();
+        let mut local_vertices = vec![];
+        let indices = self
+            .faces
+            .iter()
+            .map(|face| {
+                face.map(|i| {
+                    *remap.entry(i).or_insert_with(|| {
+                        local_vertices.push(vertices[i]);
+                        local_vertices.len() - 1
+                    })
+                })
+            })
+            .collect();
+        let buffers = Arc::new(MeshBuffers {
+            vertices: local_vertices,
+            indices,
+        });
+        Some(ObjObject {
+            name: self.name,
+            mesh: TriangleMesh::with_face_materials(
+                buffers,
+                materials.to_vec(),
+                self.face_materials,
+            ),
+        })
+    }
+}
+
+fn resolve_index(line_no: usize, token: &str, vertex_count: usize) -> Result<usize, ObjError> {
+    // only the position index of `v/vt/vn` is used
+    let position = token.split('/').next().unwrap_or("");
+    let index: i64 = position
+        .parse()
+        .map_err(|_| ObjError::at_line(line_no, format!("invalid vertex index `{}`", token)))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        // negative indices count backwards from the latest vertex
+        vertex_count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(ObjError::at_line(
+            line_no,
+            format!("vertex index `{}` out of range", token),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+// Parses an OBJ document. `mtl` supplies the materials named by `usemtl`;
+// faces without a known material use `default_material`. Polygons are
+// triangulated as fans, and every `o` statement starts a new mesh.
+pub fn parse_obj(
+    source: &str,
+    mtl: &HashMap<String, SharedMaterial>,
+    default_material: &SharedMaterial,
+) -> Result<Vec<ObjObject>, ObjError> {
+    let mut vertices = vec![];
+    // index 0 is the default material
+    let mut materials = vec![default_material.clone()];
+    let mut material_slots: HashMap<&str, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    let mut objects = vec![];
+    let mut current = PendingObject::new("default".to_string());
+
+    for (line_no, keyword, args) in statements(source) {
+        match keyword {
+            "v" => vertices.push(DVec3::from(parse_floats::<3>(line_no, keyword, &args)?)),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(ObjError::at_line(line_no, "a face needs 3 vertices"));
+                }
+                let polygon = args
+                    .iter()
+                    .map(|token| resolve_index(line_no, token, vertices.len()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for i in 1..polygon.len() - 1 {
+                    current.faces.push([polygon[0], polygon[i], polygon[i + 1]]);
+                    current.face_materials.push(current_material);
+                }
+            }
+            "usemtl" => {
+                let name = args.first().copied().unwrap_or("");
+                current_material = match mtl.get(name) {
+                    Some(material) => *material_slots.entry(name).or_insert_with(|| {
+                        materials.push(material.clone());
+                        materials.len() - 1
+                    }),
+                    None => 0,
+                };
+            }
+            "o" => {
+                let name = args.join(" ");
+                let previous = std::mem::replace(&mut current, PendingObject::new(name));
+                objects.push(previous);
+            }
+            // normals, texture coordinates, groups, smoothing, etc. are ignored
+            _ => {}
+        }
+    }
+    objects.push(current);
+
+    Ok(objects
+        .into_iter()
+        .filter_map(|object| object.build(&vertices, &materials))
+        .collect())
+}
+
+// Loads an OBJ file together with the MTL files it references. Relative MTL
+// paths are resolved against the directory of the OBJ file.
+pub fn load_obj(
+    path: impl AsRef<Path>,
+    default_material: &SharedMaterial,
+) -> Result<Vec<ObjObject>, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|e| ObjError::new(format!("cannot read: {}", e)).in_file(path))?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut mtl = HashMap::new();
+    for (line_no, keyword, args) in statements(&source) {
+        if keyword != "mtllib" {
+            continue;
+        }
+        for file in args {
+            let mtl_path = base_dir.join(file);
+            let mtl_source = fs::read_to_string(&mtl_path).map_err(|e| {
+                ObjError::at_line(
+                    line_no,
+                    format!("cannot read {}: {}", mtl_path.display(), e),
+                )
+                .in_file(path)
+            })?;
+            let parsed = parse_mtl(&mtl_source).map_err(|e| e.in_file(&mtl_path))?;
+            mtl.extend(
+                parsed
+                    .into_iter()
+                    .map(|(name, material)| (name, material.to_material())),
+            );
+        }
+    }
+
+    parse_obj(&source, &mtl, default_material).map_err(|e| e.in_file(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::utils::Interval;
+    use crate::world::Intersectable;
+
+    const QUAD: &str = "
+# a unit quad in the z = 0 plane
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn test_quad_is_triangulated() {
+        let material = DummyMaterial::new_shared();
+        let objects = parse_obj(QUAD, &HashMap::new(), &material).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].name, "quad");
+        assert_eq!(objects[0].mesh.len(), 2);
+
+        let ray = Ray::new(DVec3::new(0.9, 0.1, 1f64), DVec3::NEG_Z);
+        assert!(objects[0]
+            .mesh
+            .hit(&ray, &Interval::greater_than(0f64))
+            .is_some());
+    }
+
+    #[test]
+    fn test_negative_indices_and_objects() {
+        let source = "
+o first
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+o second
+v 0 0 1
+v 1 0 1
+v 0 1 1
+f 4 5 6
+";
+        let material = DummyMaterial::new_shared();
+        let objects = parse_obj(source, &HashMap::new(), &material).unwrap();
+        assert_eq!(objects.len(), 2);
+        // every object only keeps its own vertices
+        assert_eq!(objects[0].mesh.buffers().vertices.len(), 3);
+        assert_eq!(objects[1].mesh.buffers().vertices[0], DVec3::Z);
+    }
+
+    #[test]
+    fn test_face_materials() {
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+usemtl missing
+f 1 2 3
+";
+        let default = DummyMaterial::new_shared();
+        let red = DummyMaterial::new_shared();
+        let mtl = HashMap::from([("red".to_string(), red.clone())]);
+        let objects = parse_obj(source, &mtl, &default).unwrap();
+        let mesh = &objects[0].mesh;
+        assert!(Arc::ptr_eq(mesh.material_of(0), &red));
+        assert!(Arc::ptr_eq(mesh.material_of(1), &default));
+    }
+
+    #[test]
+    fn test_index_out_of_range() {
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        let material = DummyMaterial::new_shared();
+        let err = parse_obj(source, &HashMap::new(), &material).err().unwrap();
+        assert_eq!(err.line(), Some(3));
+    }
+
+    #[test]
+    fn test_mtl_mapping() {
+        let source = "
+newmtl matte
+Kd 0.2 0.4 0.6
+
+newmtl chrome
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 998
+
+newmtl glass
+Ni 1.33
+d 0.1
+";
+        let materials = parse_mtl(source).unwrap();
+        assert_eq!(
+            materials["matte"],
+            MtlMaterial::Lambertian {
+                albedo: DVec3::new(0.2, 0.4, 0.6)
+            }
+        );
+        match materials["chrome"] {
+            MtlMaterial::Metal { albedo, fuzz } => {
+                assert_eq!(albedo, DVec3::splat(0.9));
+                // a high specular exponent is an almost perfect mirror
+                assert!(fuzz < 0.05);
+            }
+            ref other => panic!("expected a metal, got {:?}", other),
+        }
+        assert_eq!(
+            materials["glass"],
+            MtlMaterial::Dielectric {
+                index_of_refraction: 1.33
+            }
+        );
+    }
+}
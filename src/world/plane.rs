@@ -1,6 +1,9 @@
+use super::aabb::Aabb;
 use super::intersectable::{IntersectRecord, Intersectable};
+use super::light::{Light, LightSample};
 use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
 use glam::{DQuat, DVec3};
+use rand::random;
 
 fn plenary_hit() {}
 
@@ -62,7 +65,7 @@ impl Intersectable for Rectangle {
         //dbg!("f1 {}, f2 {}", factor1, factor2);
 
         // Check if the factors are within the range [0, 1] for both edges
-        if factor1 >= -0.5 && factor1 <= 0.5 && factor2 >= -0.5 && factor2 <= 0.5 {
+        if (-0.5..=0.5).contains(&factor1) && (-0.5..=0.5).contains(&factor2) {
             Some(IntersectRecord::new(
                 ray,
                 self.normal,
@@ -73,6 +76,56 @@ impl Intersectable for Rectangle {
             None
         }
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (r, d) = (self.right * 0.5, self.down * 0.5);
+        let p = self.position;
+        Some(Aabb::from_points([
+            p - r - d,
+            p - r + d,
+            p + r - d,
+            p + r + d,
+        ]))
+    }
+}
+
+impl Rectangle {
+    fn area(&self) -> f64 {
+        self.right.cross(self.down).length()
+    }
+
+    // Converts the uniform area density to solid angle as seen from `origin`
+    fn solid_angle_pdf(&self, origin: DVec3, point: DVec3) -> Option<(DVec3, f64)> {
+        let to_point = point - origin;
+        let dist_squared = to_point.length_squared();
+        let direction = to_point.normalize_or_zero();
+        // only the front side is visible
+        let cos = -self.normal.dot(direction);
+        if dist_squared == 0f64 || cos <= 1e-6 {
+            return None;
+        }
+        Some((direction, dist_squared / (cos * self.area())))
+    }
+}
+
+impl Light for Rectangle {
+    fn sample(&self, origin: DVec3) -> Option<LightSample> {
+        let point = self.position
+            + (random::<f64>() - 0.5) * self.right
+            + (random::<f64>() - 0.5) * self.down;
+        let (direction, pdf) = self.solid_angle_pdf(origin, point)?;
+        Some(LightSample { direction, pdf })
+    }
+
+    fn pdf(&self, origin: DVec3, direction: DVec3) -> f64 {
+        let ray = Ray::new(origin, direction);
+        match self.hit(&ray, &Interval::greater_than(0f64)) {
+            Some(rec) => self
+                .solid_angle_pdf(origin, rec.point)
+                .map_or(0f64, |(_, pdf)| pdf),
+            None => 0f64,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -121,6 +174,28 @@ mod finite_plane_tests {
         assert!(plane.hit(&ray, &Interval::greater_than(0f64)).is_none());
     }
 
+    #[test]
+    fn test_light_sample() {
+        let material = DummyMaterial::new_shared();
+        let plane = Rectangle::new(DVec3::ZERO, DQuat::IDENTITY, 2f64, 2f64, &material);
+        let origin = DVec3::Y;
+
+        // a 2x2 square seen from distance 1 covers 2 * PI / 3 steradians
+        let n = 20000;
+        let mut solid_angle = 0f64;
+        for _ in 0..n {
+            let sample = plane.sample(origin).unwrap();
+            assert!((plane.pdf(origin, sample.direction) - sample.pdf).abs() < 1e-6);
+            solid_angle += 1f64 / sample.pdf;
+        }
+        solid_angle /= n as f64;
+        let expected = 2f64 * std::f64::consts::PI / 3f64;
+        assert!((solid_angle - expected).abs() < 0.02, "{}", solid_angle);
+
+        // invisible from the back side
+        assert!(plane.sample(DVec3::NEG_Y).is_none());
+    }
+
     #[test]
     fn test_inside_region() {
         let material = DummyMaterial::new_shared();
@@ -200,6 +275,7 @@ mod infinite_plane_tests {
         let ray = Ray {
             origin: DVec3::NEG_Y,
             direction: DVec3::Y,
+            time: 0f64,
         };
         let available_range = Interval::new(0f64, 2f64);
         assert!(
@@ -215,6 +291,7 @@ mod infinite_plane_tests {
         let ray_parallel = Ray {
             origin: DVec3::NEG_Y,
             direction: DVec3::X,
+            time: 0f64,
         };
         let available_range = Interval::new(0f64, 2f64);
         assert!(
@@ -230,6 +307,7 @@ mod infinite_plane_tests {
         let ray_away = Ray {
             origin: DVec3::Y,
             direction: DVec3::Y,
+            time: 0f64,
         };
         let available_range = Interval::new(0f64, 2f64);
         assert!(
@@ -245,6 +323,7 @@ mod infinite_plane_tests {
         let ray_on_plane = Ray {
             origin: DVec3::new(0.0, 10.0, 0.0),
             direction: DVec3::NEG_Y,
+            time: 0f64,
         };
         let available_range = Interval::new(0f64, 2f64);
         assert!(
@@ -260,6 +339,7 @@ mod infinite_plane_tests {
         let ray_on_plane = Ray {
             origin: DVec3::Y,
             direction: DVec3::NEG_Y,
+            time: 0f64,
         };
         let available_range = Interval::new(0f64, 2f64);
         assert!(
@@ -268,3 +348,112 @@ mod infinite_plane_tests {
         );
     }
 }
+
+// Flat round shape, seen from both sides
+pub struct Disk {
+    center: DVec3,
+    normal: DVec3,
+    radius: f64,
+    material: SharedMaterial,
+}
+
+impl Disk {
+    pub fn new(center: DVec3, normal: DVec3, radius: f64, material: &SharedMaterial) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material: material.clone(),
+        }
+    }
+}
+
+impl Intersectable for Disk {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let t = disk_hit(self.center, self.normal, self.radius, ray)?;
+        if !avaliable_range.contains(t) {
+            return None;
+        }
+        Some(IntersectRecord::new(
+            ray,
+            self.normal,
+            t,
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = disk_extent(self.normal, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+// Distance along `ray` to the disk, from either side
+pub(super) fn disk_hit(center: DVec3, normal: DVec3, radius: f64, ray: &Ray) -> Option<f64> {
+    let denom = normal.dot(ray.direction);
+    if denom.abs() < 1e-12 {
+        // parallel
+        return None;
+    }
+    let t = (center - ray.origin).dot(normal) / denom;
+    ((ray.at(t) - center).length_squared() <= radius * radius).then_some(t)
+}
+
+// Half size of the box enclosing a disk, e.g. zero along its normal
+pub(super) fn disk_extent(normal: DVec3, radius: f64) -> DVec3 {
+    let n = normal.normalize();
+    radius * (DVec3::ONE - n * n).max(DVec3::ZERO).powf(0.5)
+}
+
+#[cfg(test)]
+mod disk_tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    #[test]
+    fn test_both_sides() {
+        let material = DummyMaterial::new_shared();
+        let disk = Disk::new(DVec3::ZERO, DVec3::Y, 1f64, &material);
+
+        let ray = Ray::new(DVec3::Y, DVec3::NEG_Y);
+        let rec = disk.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!(rec.is_front);
+        assert_eq!(rec.normal, DVec3::Y);
+
+        let ray = Ray::new(DVec3::NEG_Y, DVec3::Y);
+        let rec = disk.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!(!rec.is_front);
+        assert_eq!(rec.normal, DVec3::NEG_Y);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let material = DummyMaterial::new_shared();
+        let disk = Disk::new(DVec3::ZERO, DVec3::Y, 1f64, &material);
+        let ray = Ray::new(DVec3::NEG_X * 2f64, DVec3::X);
+        assert!(disk.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_outside_region() {
+        let material = DummyMaterial::new_shared();
+        let disk = Disk::new(DVec3::ZERO, DVec3::Y, 1f64, &material);
+
+        // inside the square around the disk, outside of the disk itself
+        let ray = Ray::new(DVec3::new(0.8, 1f64, 0.8), DVec3::NEG_Y);
+        assert!(disk.hit(&ray, &Interval::greater_than(0f64)).is_none());
+
+        let ray = Ray::new(DVec3::new(0.7, 1f64, 0.7), DVec3::NEG_Y);
+        assert!(disk.hit(&ray, &Interval::greater_than(0f64)).is_some());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let material = DummyMaterial::new_shared();
+        let disk = Disk::new(DVec3::Y, DVec3::new(1f64, 1f64, 0f64), 1f64, &material);
+        let bbox = disk.bounding_box().unwrap();
+        let half = 0.5f64.sqrt();
+        assert!((bbox.min - DVec3::new(-half, 1f64 - half, -1f64)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(half, 1f64 + half, 1f64)).length() < 1e-9);
+    }
+}
@@ -0,0 +1,235 @@
+use std::ops::{Add, Mul};
+
+use crate::utils::Interval;
+
+// Polynomial in one variable, by its coefficients from the constant term up
+#[derive(Clone, PartialEq, Debug)]
+pub struct Polynomial {
+    coefficients: Vec<f64>,
+}
+
+// Bisection steps when refining a root, enough to get down to the precision
+// of f64 from any bracket
+const MAX_ITERATIONS: usize = 100;
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<f64>) -> Self {
+        let mut polynomial = Self { coefficients };
+        // leading zeros would make the degree look higher than it is
+        while polynomial.coefficients.last() == Some(&0f64) {
+            polynomial.coefficients.pop();
+        }
+        polynomial
+    }
+
+    pub fn constant(value: f64) -> Self {
+        Self::new(vec![value])
+    }
+
+    // a + b x
+    pub fn linear(a: f64, b: f64) -> Self {
+        Self::new(vec![a, b])
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    // Zero for constants, including the zero polynomial
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0f64, |value, c| value * x + c)
+    }
+
+    pub fn derivative(&self) -> Self {
+        Self::new(
+            self.coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(power, c)| c * power as f64)
+                .collect(),
+        )
+    }
+
+    pub fn powi(&self, power: u32) -> Self {
+        (0..power).fold(Self::constant(1f64), |result, _| &result * self)
+    }
+
+    // Sorted roots within the range, which must be finite. Only the roots the
+    // polynomial changes sign at are found, so a double root where it only
+    // touches zero is missed unless it lands right on zero.
+    //
+    // The roots of the derivative split the range into parts where the
+    // polynomial is monotonic, each holding at most one root, which is then
+    // bracketed safely. Unlike closed form solutions (e.g. Ferrari's for
+    // quartics) this does not lose its precision to cancellations.
+    pub fn roots_in(&self, range: &Interval) -> Vec<f64> {
+        match self.degree() {
+            0 => return vec![],
+            1 => {
+                let root = -self.coefficients[0] / self.coefficients[1];
+                return if range.contains(root) {
+                    vec![root]
+                } else {
+                    vec![]
+                };
+            }
+            _ => {}
+        }
+        let derivative = self.derivative();
+        let mut bounds = vec![range.lower];
+        bounds.extend(derivative.roots_in(range));
+        bounds.push(range.upper);
+
+        let mut roots: Vec<f64> = vec![];
+        for pair in bounds.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (fa, fb) = (self.eval(a), self.eval(b));
+            let root = if fa == 0f64 {
+                a
+            } else if fb == 0f64 {
+                b
+            } else if fa.signum() != fb.signum() {
+                self.refine(&derivative, a, b, fa)
+            } else {
+                continue;
+            };
+            // the same root at the end of a part and the start of the next
+            if roots.last() != Some(&root) {
+                roots.push(root);
+            }
+        }
+        roots
+    }
+
+    // Newton's method, kept within the bracket [a, b] of a root by falling
+    // back to bisection
+    fn refine(&self, derivative: &Polynomial, mut a: f64, mut b: f64, fa: f64) -> f64 {
+        let mut x = 0.5 * (a + b);
+        for _ in 0..MAX_ITERATIONS {
+            let fx = self.eval(x);
+            if fx == 0f64 {
+                return x;
+            }
+            if fx.signum() == fa.signum() {
+                a = x;
+            } else {
+                b = x;
+            }
+            let newton = x - fx / derivative.eval(x);
+            let next = if newton > a && newton < b {
+                newton
+            } else {
+                0.5 * (a + b)
+            };
+            if (next - x).abs() <= f64::EPSILON * x.abs() || b - a <= f64::EPSILON * x.abs() {
+                return next;
+            }
+            x = next;
+        }
+        x
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: &Polynomial) -> Polynomial {
+        let length = self.coefficients.len().max(other.coefficients.len());
+        let at = |p: &Polynomial, i: usize| p.coefficients.get(i).copied().unwrap_or(0f64);
+        Polynomial::new((0..length).map(|i| at(self, i) + at(other, i)).collect())
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        if self.coefficients.is_empty() || other.coefficients.is_empty() {
+            return Polynomial::new(vec![]);
+        }
+        let mut coefficients = vec![0f64; self.coefficients.len() + other.coefficients.len() - 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] += a * b;
+            }
+        }
+        Polynomial::new(coefficients)
+    }
+}
+
+impl Mul<f64> for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, factor: f64) -> Polynomial {
+        Polynomial::new(self.coefficients.iter().map(|c| c * factor).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (x - r0)(x - r1)...
+    fn from_roots(roots: &[f64]) -> Polynomial {
+        roots.iter().fold(Polynomial::constant(1f64), |p, r| {
+            &p * &Polynomial::linear(-r, 1f64)
+        })
+    }
+
+    fn assert_roots(found: Vec<f64>, expected: &[f64]) {
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (f, e) in found.iter().zip(expected) {
+            assert!((f - e).abs() < 1e-9, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let p = Polynomial::linear(1f64, 2f64);
+        assert_eq!(p.powi(2).coefficients(), &[1f64, 4f64, 4f64]);
+        assert_eq!((&p + &Polynomial::linear(-1f64, -2f64)).degree(), 0);
+        assert_eq!((&p * 3f64).coefficients(), &[3f64, 6f64]);
+        assert_eq!(p.powi(3).derivative(), &p.powi(2) * 6f64);
+        assert_eq!(p.powi(3).eval(2f64), 125f64);
+    }
+
+    #[test]
+    fn test_quartic_roots() {
+        let range = Interval::new(-10f64, 10f64);
+        let roots = [-3f64, -0.5, 0.25, 4f64];
+        assert_roots(from_roots(&roots).roots_in(&range), &roots);
+        // only those within the range
+        assert_roots(
+            from_roots(&roots).roots_in(&Interval::new(0f64, 1f64)),
+            &[0.25],
+        );
+        // x^4 + 1 has none
+        assert!(Polynomial::new(vec![1f64, 0f64, 0f64, 0f64, 1f64])
+            .roots_in(&range)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_close_roots() {
+        // a ray just grazing a torus gives two roots very close to each other,
+        // where closed form solutions usually lose them
+        let roots = [1f64, 1f64 + 1e-6, 5f64, 7f64];
+        let found = from_roots(&roots).roots_in(&Interval::new(0f64, 10f64));
+        assert_eq!(found.len(), 4);
+        assert!((found[1] - found[0] - 1e-6).abs() < 1e-8, "{:?}", found);
+    }
+
+    #[test]
+    fn test_roots_on_range_ends() {
+        let p = from_roots(&[1f64, 2f64, 3f64]);
+        assert_roots(p.roots_in(&Interval::new(1f64, 3f64)), &[1f64, 2f64, 3f64]);
+    }
+}
@@ -1,15 +1,30 @@
-use crate::{color::LinearRgbColor, ray::Ray};
+use crate::{color::LinearRgbColor, ray::Ray, utils::Interval};
 
-use super::{IntersectContainer, Intersectable};
+use super::{
+    Aabb, EnvironmentLight, EnvironmentMap, IntersectContainer, IntersectRecord, Intersectable,
+    PhysicalSky, SharedLight,
+};
 
 pub trait Scene: Intersectable {
     fn miss(&self, ray: &Ray) -> LinearRgbColor;
+
+    // Emitters the integrator samples explicitly. They must also be part of
+    // the intersectable geometry of the scene.
+    fn lights(&self) -> &[SharedLight] {
+        &[]
+    }
+
+    // The light `miss` returns, when it can be sampled directly
+    fn environment_light(&self) -> Option<&dyn EnvironmentLight> {
+        None
+    }
 }
 
 pub struct LerpScene<C: IntersectContainer> {
     container: C,
     start_color: LinearRgbColor,
     end_color: LinearRgbColor,
+    lights: Vec<SharedLight>,
 }
 
 impl<C: IntersectContainer> LerpScene<C> {
@@ -18,8 +33,18 @@ impl<C: IntersectContainer> LerpScene<C> {
             container,
             start_color,
             end_color,
+            lights: vec![],
         }
     }
+
+    pub fn with_lights(mut self, lights: Vec<SharedLight>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn container(&self) -> &C {
+        &self.container
+    }
 }
 
 impl<C: IntersectContainer> Intersectable for LerpScene<C> {
@@ -30,6 +55,10 @@ impl<C: IntersectContainer> Intersectable for LerpScene<C> {
     ) -> Option<super::IntersectRecord> {
         self.container.hit(ray, avaliable_range)
     }
+
+    fn bounding_box(&self) -> Option<super::Aabb> {
+        self.container.bounding_box()
+    }
 }
 
 impl<C: IntersectContainer> Scene for LerpScene<C> {
@@ -44,4 +73,66 @@ impl<C: IntersectContainer> Scene for LerpScene<C> {
             t,
         )
     }
+
+    fn lights(&self) -> &[SharedLight] {
+        &self.lights
+    }
+}
+
+// Lit from every direction by light from infinitely far away, e.g. a studio
+// HDRI or the sky
+pub struct EnvironmentScene<C: IntersectContainer, E: EnvironmentLight> {
+    container: C,
+    environment: E,
+    lights: Vec<SharedLight>,
+}
+
+pub type EnvMapScene<C> = EnvironmentScene<C, EnvironmentMap>;
+pub type SkyScene<C> = EnvironmentScene<C, PhysicalSky>;
+
+impl<C: IntersectContainer, E: EnvironmentLight> EnvironmentScene<C, E> {
+    pub fn new(container: C, environment: E) -> Self {
+        Self {
+            container,
+            environment,
+            lights: vec![],
+        }
+    }
+
+    pub fn with_lights(mut self, lights: Vec<SharedLight>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn container(&self) -> &C {
+        &self.container
+    }
+
+    pub fn environment(&self) -> &E {
+        &self.environment
+    }
+}
+
+impl<C: IntersectContainer, E: EnvironmentLight> Intersectable for EnvironmentScene<C, E> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        self.container.hit(ray, avaliable_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.container.bounding_box()
+    }
+}
+
+impl<C: IntersectContainer, E: EnvironmentLight> Scene for EnvironmentScene<C, E> {
+    fn miss(&self, ray: &Ray) -> LinearRgbColor {
+        self.environment.radiance(ray.direction)
+    }
+
+    fn lights(&self) -> &[SharedLight] {
+        &self.lights
+    }
+
+    fn environment_light(&self) -> Option<&dyn EnvironmentLight> {
+        Some(&self.environment)
+    }
 }
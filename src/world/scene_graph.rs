@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use glam::{DAffine3, DVec3};
+
+use super::aabb::Aabb;
+use super::bvh::BvhContainer;
+use super::intersectable::Intersectable;
+use super::light::SharedLight;
+use super::transformed::{SharedIntersectable, Transformed};
+use crate::materials::SharedMaterial;
+
+// Geometry of a leaf of the scene graph, built once the material it inherits
+// is known. The same shape can sit under several nodes.
+pub trait ShapeTemplate: Send + Sync {
+    // The shape in the space of its node, possibly split into parts (e.g.
+    // the triangles of a mesh) for the BVH to cull
+    fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>>;
+
+    // The shape as a light the integrator can sample, when it supports it
+    fn build_light(&self, _material: &SharedMaterial) -> Option<SharedLight> {
+        None
+    }
+
+    // In the space of its node, None when unbounded
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+pub type SharedShape = Arc<dyn ShapeTemplate>;
+
+#[derive(Clone)]
+pub enum NodeContent {
+    Group(Vec<SceneNode>),
+    Shape(SharedShape),
+}
+
+// A node of the scene graph, placed by `transform` in the space of its parent.
+// Shapes without a material of their own use the one of their nearest
+// ancestor that has one.
+#[derive(Clone)]
+pub struct SceneNode {
+    name: Option<String>,
+    transform: DAffine3,
+    material: Option<SharedMaterial>,
+    content: NodeContent,
+}
+
+impl SceneNode {
+    pub fn group(children: Vec<SceneNode>) -> Self {
+        Self::new(NodeContent::Group(children))
+    }
+
+    pub fn shape(shape: SharedShape) -> Self {
+        Self::new(NodeContent::Shape(shape))
+    }
+
+    fn new(content: NodeContent) -> Self {
+        Self {
+            name: None,
+            transform: DAffine3::IDENTITY,
+            material: None,
+            content,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_transform(mut self, transform: DAffine3) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_material(mut self, material: &SharedMaterial) -> Self {
+        self.material = Some(material.clone());
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn transform(&self) -> DAffine3 {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: DAffine3) {
+        self.transform = transform;
+    }
+
+    pub fn material(&self) -> Option<&SharedMaterial> {
+        self.material.as_ref()
+    }
+
+    pub fn set_material(&mut self, material: Option<SharedMaterial>) {
+        self.material = material;
+    }
+
+    pub fn content(&self) -> &NodeContent {
+        &self.content
+    }
+
+    // Empty for shapes
+    pub fn children(&self) -> &[SceneNode] {
+        match &self.content {
+            NodeContent::Group(children) => children,
+            NodeContent::Shape(_) => &[],
+        }
+    }
+
+    // None for shapes, which cannot have children
+    pub fn children_mut(&mut self) -> Option<&mut Vec<SceneNode>> {
+        match &mut self.content {
+            NodeContent::Group(children) => Some(children),
+            NodeContent::Shape(_) => None,
+        }
+    }
+
+    // The named descendant at `path`, names separated by `/`. Unnamed groups
+    // are looked through, e.g. `table/leg` finds the leg of a table even when
+    // the legs are grouped together without a name.
+    pub fn find(&self, path: &str) -> Option<&SceneNode> {
+        self.find_path(path).map(|(_, node)| node)
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut SceneNode> {
+        let indices = self.find_path(path)?.0;
+        let mut node = self;
+        for index in indices {
+            node = &mut node.children_mut()?[index];
+        }
+        Some(node)
+    }
+
+    // Child indices leading to the node at `path`, and the node
+    fn find_path(&self, path: &str) -> Option<(Vec<usize>, &SceneNode)> {
+        let mut indices = vec![];
+        let mut node = self;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            node = node.find_child(name, &mut indices)?;
+        }
+        Some((indices, node))
+    }
+
+    fn find_child<'a>(&'a self, name: &str, indices: &mut Vec<usize>) -> Option<&'a SceneNode> {
+        for (index, child) in self.children().iter().enumerate() {
+            indices.push(index);
+            if child.name() == Some(name) {
+                return Some(child);
+            }
+            if child.name.is_none() {
+                if let Some(found) = child.find_child(name, indices) {
+                    return Some(found);
+                }
+            }
+            indices.pop();
+        }
+        None
+    }
+
+    // Encloses the node in the space of its parent, None when unbounded
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        Some(transform_box(&self.local_bounding_box()?, self.transform))
+    }
+
+    // Same, in the space of the node itself
+    fn local_bounding_box(&self) -> Option<Aabb> {
+        let bbox = match &self.content {
+            NodeContent::Shape(shape) => shape.bounding_box()?,
+            NodeContent::Group(children) => {
+                let mut bbox = Aabb::empty();
+                for child in children {
+                    bbox = bbox.union(&child.bounding_box()?);
+                }
+                bbox
+            }
+        };
+        Some(bbox)
+    }
+}
+
+fn transform_box(bbox: &Aabb, transform: DAffine3) -> Aabb {
+    if bbox.is_empty() || transform == DAffine3::IDENTITY {
+        return *bbox;
+    }
+    let corners = (0..8).map(|i| {
+        let corner = DVec3::new(
+            if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+            if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+            if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+        );
+        transform.transform_point3(corner)
+    });
+    Aabb::from_points(corners)
+}
+
+// What the graph flattens to for rendering
+pub struct FlatScene {
+    pub objects: Vec<Box<dyn Intersectable>>,
+    pub lights: Vec<SharedLight>,
+}
+
+// A hierarchy of named, transformed and materialed nodes kept around for
+// editing and querying, flattened into plain objects for rendering
+#[derive(Clone)]
+pub struct SceneGraph {
+    root: SceneNode,
+    // for shapes without a material anywhere above them
+    default_material: SharedMaterial,
+}
+
+impl SceneGraph {
+    pub fn new(root: SceneNode, default_material: &SharedMaterial) -> Self {
+        Self {
+            root,
+            default_material: default_material.clone(),
+        }
+    }
+
+    pub fn root(&self) -> &SceneNode {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut SceneNode {
+        &mut self.root
+    }
+
+    pub fn find(&self, path: &str) -> Option<&SceneNode> {
+        self.root.find(path)
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut SceneNode> {
+        self.root.find_mut(path)
+    }
+
+    // Transform from the space of the node at `path` to the world
+    pub fn world_transform(&self, path: &str) -> Option<DAffine3> {
+        let (indices, _) = self.root.find_path(path)?;
+        let mut node = &self.root;
+        let mut transform = node.transform;
+        for index in indices {
+            node = &node.children()[index];
+            transform *= node.transform;
+        }
+        Some(transform)
+    }
+
+    // Where the node at `path` ends up in the world, None when unbounded
+    pub fn world_bounding_box(&self, path: &str) -> Option<Aabb> {
+        let local = self.find(path)?.local_bounding_box()?;
+        Some(transform_box(&local, self.world_transform(path)?))
+    }
+
+    // Shapes placed without a transform are built as is, and emissive ones
+    // become lights. Transformed shapes are built once per material and
+    // shared by all of their placements, which are not sampled as lights.
+    pub fn flatten(&self) -> FlatScene {
+        let mut flattener = Flattener {
+            default_material: &self.default_material,
+            shared: HashMap::new(),
+            scene: FlatScene {
+                objects: vec![],
+                lights: vec![],
+            },
+        };
+        flattener.visit(&self.root, DAffine3::IDENTITY, None);
+        flattener.scene
+    }
+}
+
+struct Flattener<'a> {
+    default_material: &'a SharedMaterial,
+    // built shapes, by shape and material
+    shared: HashMap<(*const (), *const ()), SharedIntersectable>,
+    scene: FlatScene,
+}
+
+impl<'a> Flattener<'a> {
+    fn visit(
+        &mut self,
+        node: &'a SceneNode,
+        parent_to_world: DAffine3,
+        parent_material: Option<&'a SharedMaterial>,
+    ) {
+        let to_world = parent_to_world * node.transform;
+        let material = node.material.as_ref().or(parent_material);
+        match &node.content {
+            NodeContent::Group(children) => {
+                for child in children {
+                    self.visit(child, to_world, material);
+                }
+            }
+            NodeContent::Shape(shape) => {
+                let material = material.unwrap_or(self.default_material);
+                if to_world == DAffine3::IDENTITY {
+                    self.add_in_place(shape, material);
+                } else {
+                    let key = (
+                        Arc::as_ptr(shape) as *const (),
+                        Arc::as_ptr(material) as *const (),
+                    );
+                    let object = self
+                        .shared
+                        .entry(key)
+                        .or_insert_with(|| {
+                            let mut parts = shape.build(material);
+                            if parts.len() == 1 {
+                                Arc::from(parts.pop().unwrap())
+                            } else {
+                                Arc::new(BvhContainer::build(parts))
+                            }
+                        })
+                        .clone();
+                    self.scene
+                        .objects
+                        .push(Transformed::new(object, to_world).into_box());
+                }
+            }
+        }
+    }
+
+    fn add_in_place(&mut self, shape: &SharedShape, material: &SharedMaterial) {
+        if material.is_emissive() {
+            if let Some(light) = shape.build_light(material) {
+                self.scene.lights.push(light.clone());
+                self.scene.objects.push(Box::new(light));
+                return;
+            }
+        }
+        self.scene.objects.extend(shape.build(material));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{DiffuseLight, LambertianMaterial, Material};
+    use crate::ray::Ray;
+    use crate::utils::Interval;
+    use crate::world::{IntersectContainer, Sphere};
+
+    struct Ball(f64);
+
+    impl ShapeTemplate for Ball {
+        fn build(&self, material: &SharedMaterial) -> Vec<Box<dyn Intersectable>> {
+            vec![Sphere::new(DVec3::ZERO, self.0, material).into_box()]
+        }
+
+        fn build_light(&self, material: &SharedMaterial) -> Option<SharedLight> {
+            Some(Arc::new(Sphere::new(DVec3::ZERO, self.0, material)))
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(Aabb::new(DVec3::splat(-self.0), DVec3::splat(self.0)))
+        }
+    }
+
+    fn lambertian(v: f64) -> SharedMaterial {
+        LambertianMaterial::make_shared(LambertianMaterial::new(DVec3::splat(v)))
+    }
+
+    // A table top with four legs, placed twice
+    fn furniture() -> (SceneGraph, SharedMaterial, SharedMaterial) {
+        let wood = lambertian(0.5);
+        let metal = lambertian(0.9);
+        let ball: SharedShape = Arc::new(Ball(0.1));
+        let legs = [(-1f64, -1f64), (-1f64, 1f64), (1f64, -1f64), (1f64, 1f64)]
+            .map(|(x, z)| {
+                SceneNode::shape(ball.clone())
+                    .with_transform(DAffine3::from_translation(DVec3::new(x, 0f64, z)))
+            })
+            .to_vec();
+        let table = SceneNode::group(vec![
+            SceneNode::shape(Arc::new(Ball(1f64)))
+                .with_name("top")
+                .with_transform(DAffine3::from_translation(DVec3::Y)),
+            SceneNode::group(legs).with_material(&metal),
+        ])
+        .with_material(&wood);
+        let root = SceneNode::group(vec![
+            table
+                .clone()
+                .with_name("left")
+                .with_transform(DAffine3::from_translation(DVec3::new(-5f64, 0f64, 0f64))),
+            table
+                .with_name("right")
+                .with_transform(DAffine3::from_scale_rotation_translation(
+                    DVec3::splat(2f64),
+                    glam::DQuat::IDENTITY,
+                    DVec3::new(5f64, 0f64, 0f64),
+                )),
+        ]);
+        (SceneGraph::new(root, &lambertian(0.1)), wood, metal)
+    }
+
+    #[test]
+    fn test_flatten_inherits_materials() {
+        let (graph, wood, metal) = furniture();
+        let flat = graph.flatten();
+        assert_eq!(flat.objects.len(), 10);
+        assert!(flat.lights.is_empty());
+        let world = BvhContainer::from_iter(flat.objects);
+
+        let range = Interval::greater_than(0f64);
+        let top = world
+            .hit(
+                &Ray::new(DVec3::new(-5f64, 5f64, 0f64), DVec3::NEG_Y),
+                &range,
+            )
+            .unwrap();
+        assert!((top.point.y - 2f64).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&top.mat, &wood));
+        let leg = world
+            .hit(
+                &Ray::new(DVec3::new(7f64, 0f64, 5f64), DVec3::NEG_Z),
+                &range,
+            )
+            .unwrap();
+        // scaled by two along with the right table
+        assert!((leg.point.z - 2.2).abs() < 1e-9);
+        assert!(Arc::ptr_eq(&leg.mat, &metal));
+    }
+
+    #[test]
+    fn test_query() {
+        let (mut graph, _, _) = furniture();
+        assert!(graph.find("left/top").is_some());
+        assert!(graph.find("top").is_none());
+        assert!(graph.find("left/leg").is_none());
+
+        let transform = graph.world_transform("right/top").unwrap();
+        let center = transform.transform_point3(DVec3::ZERO);
+        assert!((center - DVec3::new(5f64, 2f64, 0f64)).length() < 1e-9);
+
+        let bbox = graph.world_bounding_box("right/top").unwrap();
+        assert!((bbox.min - DVec3::new(3f64, 0f64, -2f64)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(7f64, 4f64, 2f64)).length() < 1e-9);
+
+        // editing moves what the flattened scene contains
+        graph
+            .find_mut("left")
+            .unwrap()
+            .set_transform(DAffine3::from_translation(DVec3::new(0f64, 0f64, -20f64)));
+        let bbox = graph.world_bounding_box("left/top").unwrap();
+        assert!((bbox.centroid() - DVec3::new(0f64, 1f64, -20f64)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_emissive_shapes_in_place_are_lights() {
+        let lamp = DiffuseLight::make_shared(DiffuseLight::new(
+            crate::color::LinearRgbColor::new(1f64, 1f64, 1f64),
+            1f64,
+        ));
+        let ball: SharedShape = Arc::new(Ball(0.5));
+        let root = SceneNode::group(vec![
+            SceneNode::shape(ball.clone()),
+            SceneNode::shape(ball).with_transform(DAffine3::from_translation(DVec3::X)),
+        ])
+        .with_material(&lamp);
+        let flat = SceneGraph::new(root, &lambertian(0.1)).flatten();
+        assert_eq!(flat.objects.len(), 2);
+        assert_eq!(flat.lights.len(), 1);
+    }
+}
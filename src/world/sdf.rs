@@ -0,0 +1,610 @@
+use std::sync::Arc;
+
+use glam::{DVec3, IVec3};
+
+use super::aabb::Aabb;
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+
+// A signed distance field: the distance from a point to the surface of a
+// shape, negative inside. It may underestimate the distance (e.g. fractal
+// distance estimators), never overestimate it, or sphere tracing steps
+// through the surface.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: DVec3) -> f64;
+
+    // Encloses the surface, None when unbounded (e.g. infinite repetition)
+    fn bounds(&self) -> Option<Aabb>;
+}
+
+pub type SharedSdf = Arc<dyn Sdf>;
+
+impl<T: Sdf + ?Sized> Sdf for Box<T> {
+    fn distance(&self, p: DVec3) -> f64 {
+        (**self).distance(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (**self).bounds()
+    }
+}
+
+impl<T: Sdf + ?Sized> Sdf for Arc<T> {
+    fn distance(&self, p: DVec3) -> f64 {
+        (**self).distance(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (**self).bounds()
+    }
+}
+
+// Any distance function, inside the given bounds
+pub struct SdfFn<F> {
+    distance: F,
+    bounds: Option<Aabb>,
+}
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> SdfFn<F> {
+    pub fn new(distance: F, bounds: Option<Aabb>) -> Self {
+        Self { distance, bounds }
+    }
+}
+
+impl<F: Fn(DVec3) -> f64 + Send + Sync> Sdf for SdfFn<F> {
+    fn distance(&self, p: DVec3) -> f64 {
+        (self.distance)(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+}
+
+// How far rays are traced through unbounded fields
+const MAX_DISTANCE: f64 = 1e3;
+
+// The surface where a distance field is zero, found by sphere tracing: the
+// ray can safely advance by the distance to the nearest surface until it is
+// close enough to count as a hit.
+pub struct SdfShape<S: Sdf> {
+    sdf: S,
+    material: SharedMaterial,
+    max_steps: usize,
+    // how close to the surface counts as a hit, also the step of the finite
+    // differences giving the normal
+    epsilon: f64,
+    // below one for fields that overestimate the distance here and there,
+    // trading speed for not missing thin parts
+    step_scale: f64,
+}
+
+impl<S: Sdf> SdfShape<S> {
+    pub fn new(sdf: S, material: &SharedMaterial) -> Self {
+        Self {
+            sdf,
+            material: material.clone(),
+            max_steps: 256,
+            epsilon: 1e-4,
+            step_scale: 1f64,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn sdf(&self) -> &S {
+        &self.sdf
+    }
+
+    // Gradient of the field by central differences, pointing outwards
+    pub fn normal_at(&self, p: DVec3) -> DVec3 {
+        let h = self.epsilon;
+        let d = |offset: DVec3| self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        DVec3::new(d(DVec3::X * h), d(DVec3::Y * h), d(DVec3::Z * h)).normalize_or_zero()
+    }
+}
+
+impl<S: Sdf> Intersectable for SdfShape<S> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let range = match self.bounding_box() {
+            Some(bbox) => bbox.hit(ray, avaliable_range)?,
+            None => Interval::new(
+                avaliable_range.lower,
+                avaliable_range.upper.min(MAX_DISTANCE),
+            ),
+        };
+        // march in world units along a unit direction
+        let length = ray.direction.length();
+        let direction = ray.direction / length;
+        let (start, end) = (range.lower * length, range.upper * length);
+
+        let start_distance = self.sdf.distance(ray.origin + direction * start);
+        // Rays leaving the surface (e.g. scattered or refracted from it) start
+        // within epsilon of it, the gradient tells which side they go to.
+        let outside = if start_distance.abs() < self.epsilon {
+            self.normal_at(ray.origin + direction * start)
+                .dot(direction)
+                >= 0f64
+        } else {
+            start_distance > 0f64
+        };
+        let sign = if outside { 1f64 } else { -1f64 };
+
+        let mut s = start;
+        for step in 0..self.max_steps {
+            let point = ray.origin + direction * s;
+            let distance = sign * self.sdf.distance(point);
+            if distance < self.epsilon && step > 0 {
+                let t = s / length;
+                return Some(IntersectRecord::new(
+                    ray,
+                    self.normal_at(point),
+                    t,
+                    self.material.clone(),
+                ));
+            }
+            s += (distance * self.step_scale).max(self.epsilon);
+            if s > end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // hits are up to epsilon away from the surface
+        let bounds = self.sdf.bounds()?;
+        let margin = DVec3::splat(self.epsilon * 2f64);
+        Some(Aabb::new(bounds.min - margin, bounds.max + margin))
+    }
+}
+
+pub struct Sphere {
+    pub center: DVec3,
+    pub radius: f64,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: DVec3) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = DVec3::splat(self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+// Axis-aligned, with edges rounded by `rounding`
+pub struct Cuboid {
+    pub center: DVec3,
+    pub half_size: DVec3,
+    pub rounding: f64,
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, p: DVec3) -> f64 {
+        let q = (p - self.center).abs() - self.half_size + self.rounding;
+        q.max(DVec3::ZERO).length() + q.max_element().min(0f64) - self.rounding
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            self.center - self.half_size,
+            self.center + self.half_size,
+        ))
+    }
+}
+
+// A ring around the Y axis
+pub struct Torus {
+    pub center: DVec3,
+    // from the center to the middle of the tube
+    pub major_radius: f64,
+    // of the tube
+    pub minor_radius: f64,
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: DVec3) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = DVec3::new(outer, self.minor_radius, outer);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+// Segment from `a` to `b` thickened by `radius`
+pub struct Capsule {
+    pub a: DVec3,
+    pub b: DVec3,
+    pub radius: f64,
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: DVec3) -> f64 {
+        let (pa, ba) = (p - self.a, self.b - self.a);
+        let h = (pa.dot(ba) / ba.length_squared()).clamp(0f64, 1f64);
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = DVec3::splat(self.radius);
+        Some(Aabb::new(self.a.min(self.b) - r, self.a.max(self.b) + r))
+    }
+}
+
+// The Mandelbulb fractal, power 8 unless set otherwise. Its radius depends on
+// the power, about 1.1 times `scale` at 8 and 1.3 times at 2, but never more
+// than twice `scale` where points escape.
+pub struct Mandelbulb {
+    pub center: DVec3,
+    pub scale: f64,
+    pub power: f64,
+    // more of them give more detail
+    pub iterations: usize,
+}
+
+impl Mandelbulb {
+    pub fn new(center: DVec3, scale: f64) -> Self {
+        Self {
+            center,
+            scale,
+            power: 8f64,
+            iterations: 10,
+        }
+    }
+
+    // Distance estimator of the unit sized fractal
+    fn estimate(&self, c: DVec3) -> f64 {
+        let mut z = c;
+        let mut dr = 1f64;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r < 1e-12 {
+                // at the origin, well inside
+                return 0f64;
+            }
+            if r > 2f64 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1f64) * self.power * dr + 1f64;
+            z = r.powf(self.power)
+                * DVec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + c;
+            r = z.length();
+        }
+        0.5 * r.ln() * r / dr
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: DVec3) -> f64 {
+        self.estimate((p - self.center) / self.scale) * self.scale
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // the escape radius, whatever the power
+        let r = DVec3::splat(2f64 * self.scale);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
+
+// Blends two shapes together where they are closer than `smoothness`, plain
+// union when it is zero
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    pub a: A,
+    pub b: B,
+    pub smoothness: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: DVec3) -> f64 {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        let k = self.smoothness;
+        if k <= 0f64 {
+            return a.min(b);
+        }
+        // polynomial smooth minimum
+        let h = (k - (a - b).abs()).max(0f64) / k;
+        a.min(b) - h * h * k * 0.25
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // the blend bulges by up to a quarter of the smoothness
+        let bounds = self.a.bounds()?.union(&self.b.bounds()?);
+        let margin = DVec3::splat(self.smoothness.max(0f64) * 0.25);
+        Some(Aabb::new(bounds.min - margin, bounds.max + margin))
+    }
+}
+
+// Copies of a shape every `spacing` along each axis, zero for the axes not to
+// repeat along. Without a `count` the copies go on forever, otherwise there
+// are `count` on each side of the original. Each copy only shows within its
+// own cell, so the shape should fit in one.
+pub struct Repeat<S: Sdf> {
+    sdf: S,
+    spacing: DVec3,
+    count: Option<IVec3>,
+    // cells are centered on the shape, or on the origin when it is unbounded
+    origin: DVec3,
+}
+
+impl<S: Sdf> Repeat<S> {
+    pub fn new(sdf: S, spacing: DVec3, count: Option<IVec3>) -> Self {
+        let origin = sdf.bounds().map_or(DVec3::ZERO, |b| b.centroid());
+        Self {
+            sdf,
+            spacing,
+            count,
+            origin,
+        }
+    }
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: DVec3) -> f64 {
+        let mut q = p;
+        for axis in 0..3 {
+            let spacing = self.spacing[axis];
+            if spacing <= 0f64 {
+                continue;
+            }
+            let mut cell = ((p[axis] - self.origin[axis]) / spacing).round();
+            if let Some(count) = self.count {
+                let count = count[axis] as f64;
+                cell = cell.clamp(-count, count);
+            }
+            q[axis] = p[axis] - spacing * cell;
+        }
+        self.sdf.distance(q)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let count = self.count?;
+        let bounds = self.sdf.bounds()?;
+        let reach = self.spacing.max(DVec3::ZERO) * count.as_dvec3();
+        Some(Aabb::new(bounds.min - reach, bounds.max + reach))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn range() -> Interval {
+        Interval::greater_than(0f64)
+    }
+
+    #[test]
+    fn test_sphere_matches_analytic() {
+        let material = DummyMaterial::new_shared();
+        let traced = SdfShape::new(
+            Sphere {
+                center: DVec3::ZERO,
+                radius: 1f64,
+            },
+            &material,
+        );
+        let analytic = crate::world::Sphere::new(DVec3::ZERO, 1f64, &material);
+
+        let origin = DVec3::new(0.3, 0.4, 5f64);
+        let ray = Ray::new(origin, DVec3::new(-0.1, -0.2, -1f64));
+        let expected = analytic.hit(&ray, &range()).unwrap();
+        let rec = traced.hit(&ray, &range()).unwrap();
+        assert!((rec.t - expected.t).abs() < 1e-3);
+        assert!((rec.normal - expected.normal).length() < 1e-3);
+        assert!(rec.is_front);
+
+        let ray = Ray::new(origin, DVec3::Z);
+        assert!(traced.hit(&ray, &range()).is_none());
+    }
+
+    #[test]
+    fn test_from_inside() {
+        let material = DummyMaterial::new_shared();
+        let traced = SdfShape::new(
+            Sphere {
+                center: DVec3::ZERO,
+                radius: 1f64,
+            },
+            &material,
+        );
+        let ray = Ray::new(DVec3::ZERO, DVec3::X * 2f64);
+        let rec = traced.hit(&ray, &range()).unwrap();
+        // in units of the unnormalized direction
+        assert!((rec.t - 0.5).abs() < 1e-3);
+        assert!(!rec.is_front);
+        assert!((rec.normal - DVec3::NEG_X).length() < 1e-3);
+
+        // leaving the surface it just hit, it goes out instead of hitting
+        // the surface again right away
+        let ray = Ray::new(rec.point, DVec3::X);
+        assert!(traced.hit(&ray, &Interval::greater_than(1e-3)).is_none());
+    }
+
+    #[test]
+    fn test_from_fn() {
+        // a plane at y = 0, only traced within its bounds
+        let material = DummyMaterial::new_shared();
+        let bounds = Aabb::new(DVec3::new(-1f64, -1f64, -1f64), DVec3::ONE);
+        let traced = SdfShape::new(SdfFn::new(|p: DVec3| p.y, Some(bounds)), &material);
+        let rec = traced
+            .hit(
+                &Ray::new(DVec3::new(0f64, 5f64, 0f64), DVec3::NEG_Y),
+                &range(),
+            )
+            .unwrap();
+        assert!((rec.t - 5f64).abs() < 1e-3);
+        assert!((rec.normal - DVec3::Y).length() < 1e-9);
+        let ray = Ray::new(DVec3::new(2f64, 5f64, 0f64), DVec3::NEG_Y);
+        assert!(traced.hit(&ray, &range()).is_none());
+    }
+
+    #[test]
+    fn test_primitives() {
+        let cuboid = Cuboid {
+            center: DVec3::ZERO,
+            half_size: DVec3::ONE,
+            rounding: 0.25,
+        };
+        assert!((cuboid.distance(DVec3::new(2f64, 0f64, 0f64)) - 1f64).abs() < 1e-9);
+        assert!((cuboid.distance(DVec3::ZERO) + 1f64).abs() < 1e-9);
+        // the rounded corner is further than the sharp one would be
+        let corner = cuboid.distance(DVec3::ONE);
+        assert!(corner > 0f64 && corner < 0.25);
+
+        let torus = Torus {
+            center: DVec3::ZERO,
+            major_radius: 1f64,
+            minor_radius: 0.25,
+        };
+        assert!(torus.distance(DVec3::new(1f64, 0f64, 0f64)) < 0f64);
+        assert!((torus.distance(DVec3::ZERO) - 0.75).abs() < 1e-9);
+
+        let capsule = Capsule {
+            a: DVec3::ZERO,
+            b: DVec3::Y,
+            radius: 0.5,
+        };
+        assert!((capsule.distance(DVec3::new(1f64, 0.5, 0f64)) - 0.5).abs() < 1e-9);
+        assert!((capsule.distance(DVec3::new(0f64, 2f64, 0f64)) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_smooth_union() {
+        let sphere = |x: f64| Sphere {
+            center: DVec3::new(x, 0f64, 0f64),
+            radius: 0.5,
+        };
+        let blend = SmoothUnion {
+            a: sphere(-0.6),
+            b: sphere(0.6),
+            smoothness: 0.5,
+        };
+        let union = SmoothUnion {
+            a: sphere(-0.6),
+            b: sphere(0.6),
+            smoothness: 0f64,
+        };
+        // the gap between the spheres is filled in
+        assert!(union.distance(DVec3::ZERO) > 0f64);
+        assert!(blend.distance(DVec3::ZERO) < 0f64);
+        // far from the gap both agree
+        let p = DVec3::new(3f64, 0f64, 0f64);
+        assert!((blend.distance(p) - union.distance(p)).abs() < 1e-9);
+        assert!(blend.bounds().unwrap().max.x > union.bounds().unwrap().max.x);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let ball = || Sphere {
+            center: DVec3::ZERO,
+            radius: 0.25,
+        };
+        let endless = Repeat::new(ball(), DVec3::new(1f64, 0f64, 1f64), None);
+        let p = DVec3::new(0.1, 0.3, 0.2);
+        assert!(
+            (endless.distance(p + DVec3::new(7f64, 0f64, -3f64)) - endless.distance(p)).abs()
+                < 1e-9
+        );
+        // not along y
+        assert!(endless.distance(p + DVec3::Y) > 1f64);
+        assert!(endless.bounds().is_none());
+
+        let limited = Repeat::new(
+            ball(),
+            DVec3::new(1f64, 0f64, 0f64),
+            Some(IVec3::new(2, 0, 0)),
+        );
+        assert!(limited.distance(DVec3::new(2f64, 0f64, 0f64)) < 0f64);
+        assert!((limited.distance(DVec3::new(4f64, 0f64, 0f64)) - 1.75).abs() < 1e-9);
+        let bounds = limited.bounds().unwrap();
+        assert_eq!(bounds.max, DVec3::new(2.25, 0.25, 0.25));
+
+        // traced through the copies
+        let material = DummyMaterial::new_shared();
+        let traced = SdfShape::new(limited, &material);
+        let ray = Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X);
+        let rec = traced.hit(&ray, &range()).unwrap();
+        assert!((rec.point.x + 2.25).abs() < 1e-3);
+
+        // repeated around the shape rather than the origin
+        let offset = Repeat::new(
+            Sphere {
+                center: DVec3::new(0.3, 5f64, 0f64),
+                radius: 0.25,
+            },
+            DVec3::new(1f64, 0f64, 0f64),
+            Some(IVec3::new(1, 0, 0)),
+        );
+        for x in [-0.7, 0.3, 1.3] {
+            assert!(offset.distance(DVec3::new(x, 5f64, 0f64)) < 0f64);
+        }
+        assert!(offset.distance(DVec3::new(2.3, 5f64, 0f64)) > 0f64);
+    }
+
+    #[test]
+    fn test_mandelbulb() {
+        let material = DummyMaterial::new_shared();
+        let bulb = Mandelbulb::new(DVec3::ZERO, 1f64);
+        assert!(bulb.distance(DVec3::new(3f64, 0f64, 0f64)) > 0f64);
+        assert!(bulb.distance(DVec3::ZERO) <= 0f64 + 1e-9);
+
+        let traced = SdfShape::new(bulb, &material).with_step_scale(0.8);
+        let ray = Ray::new(DVec3::new(0f64, 0f64, 3f64), DVec3::NEG_Z);
+        let rec = traced.hit(&ray, &range()).unwrap();
+        assert!(rec.point.length() < 1.2 && rec.point.length() > 0.5);
+        assert!(rec.normal.dot(DVec3::Z) > 0f64);
+    }
+
+    #[test]
+    fn test_mandelbulb_bounds() {
+        // lower powers grow larger, every point inside is still bounded
+        for power in [2f64, 3f64, 8f64] {
+            let bulb = Mandelbulb {
+                power,
+                ..Mandelbulb::new(DVec3::new(1f64, 0f64, 0f64), 0.5)
+            };
+            let bounds = bulb.bounds().unwrap();
+            let n = 61;
+            for i in 0..n {
+                for j in 0..n {
+                    for k in 0..n {
+                        let offset = DVec3::new(i as f64, j as f64, k as f64) / (n - 1) as f64;
+                        let p = DVec3::new(1f64, 0f64, 0f64) + (offset - 0.5) * 2f64;
+                        if bulb.distance(p) < 0f64 {
+                            let inside = p.cmpge(bounds.min).all() && p.cmple(bounds.max).all();
+                            assert!(inside, "power {power}: {p}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
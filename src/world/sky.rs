@@ -0,0 +1,329 @@
+use std::f64::consts::PI;
+
+use glam::DVec3;
+use rand::random;
+
+use super::{EnvironmentLight, LightSample};
+use crate::color::LinearRgbColor;
+use crate::utils::{random_in_cone, random_unit_vector};
+
+// Angular radius of the sun seen from the ground, in radians
+const SUN_ANGULAR_RADIUS: f64 = 0.00465;
+// Luminance of the sun above the atmosphere, in kcd/m^2 like the sky model
+const SUN_LUMINANCE: f64 = 1.6e6;
+// Brings the kcd/m^2 of the model to the scale of the rest of the renderer,
+// a clear zenith ends up around 0.5
+const LUMINANCE_SCALE: f64 = 0.05;
+// Chance of aiming a light sample at the sun rather than at the whole sky
+const SUN_SAMPLE_PROBABILITY: f64 = 0.5;
+// Wavelengths standing for the red, green and blue channels, in micrometers
+const WAVELENGTHS: [f64; 3] = [0.680, 0.550, 0.440];
+
+// Perez et al. luminance distribution, the ratio of the radiance of a sky
+// element to the zenith follows F(theta, gamma) / F(0, theta_sun).
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    // Coefficients are linear in the turbidity, given as [slope, intercept]
+    fn new(coefficients: [[f64; 2]; 5], turbidity: f64) -> Self {
+        let [a, b, c, d, e] = coefficients.map(|[slope, intercept]| slope * turbidity + intercept);
+        Self { a, b, c, d, e }
+    }
+
+    fn eval(&self, cos_theta: f64, gamma: f64) -> f64 {
+        (1f64 + self.a * (self.b / cos_theta).exp())
+            * (1f64 + self.c * (self.d * gamma).exp() + self.e * gamma.cos().powi(2))
+    }
+}
+
+// Clear daylight sky after Preetham, Shirley and Smits (1999): "A Practical
+// Analytic Model for Daylight". The ground below the horizon is a diffuse
+// plane lit by the sun and the sky.
+pub struct PhysicalSky {
+    sun_direction: DVec3,
+    turbidity: f64,
+    ground_albedo: DVec3,
+    intensity: f64,
+    // Perez distributions and zenith values of the luminance Y and the
+    // chromaticities x and y
+    perez: [Perez; 3],
+    zenith: [f64; 3],
+    cos_sun_theta: f64,
+    sun_radiance: DVec3,
+    ground_radiance: DVec3,
+}
+
+impl PhysicalSky {
+    // `turbidity` goes from 2 (very clear) to 10 (hazy)
+    pub fn new(sun_direction: DVec3, turbidity: f64) -> Self {
+        Self::build(sun_direction, turbidity, DVec3::splat(0.3), 1f64)
+    }
+
+    pub fn with_ground_albedo(self, ground_albedo: DVec3) -> Self {
+        Self::build(
+            self.sun_direction,
+            self.turbidity,
+            ground_albedo,
+            self.intensity,
+        )
+    }
+
+    // Scales the radiance of the whole sky, sun and ground
+    pub fn with_intensity(self, intensity: f64) -> Self {
+        Self::build(
+            self.sun_direction,
+            self.turbidity,
+            self.ground_albedo,
+            intensity,
+        )
+    }
+
+    pub fn sun_direction(&self) -> DVec3 {
+        self.sun_direction
+    }
+
+    fn build(sun_direction: DVec3, turbidity: f64, ground_albedo: DVec3, intensity: f64) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity;
+        // the model does not hold for a sun below the horizon, keep it there
+        let theta = sun_direction.y.clamp(0f64, 1f64).acos();
+        let perez = [
+            Perez::new(
+                [
+                    [0.1787, -1.4630],
+                    [-0.3554, 0.4275],
+                    [-0.0227, 5.3251],
+                    [0.1206, -2.5771],
+                    [-0.0670, 0.3703],
+                ],
+                t,
+            ),
+            Perez::new(
+                [
+                    [-0.0193, -0.2592],
+                    [-0.0665, 0.0008],
+                    [-0.0004, 0.2125],
+                    [-0.0641, -0.8989],
+                    [-0.0033, 0.0452],
+                ],
+                t,
+            ),
+            Perez::new(
+                [
+                    [-0.0167, -0.2608],
+                    [-0.0950, 0.0092],
+                    [-0.0079, 0.2102],
+                    [-0.0441, -1.6537],
+                    [-0.0109, 0.0529],
+                ],
+                t,
+            ),
+        ];
+        let chi = (4f64 / 9f64 - t / 120f64) * (PI - 2f64 * theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let cubic = |c: [f64; 4]| ((c[0] * theta + c[1]) * theta + c[2]) * theta + c[3];
+            t * t * cubic(m[0]) + t * cubic(m[1]) + cubic(m[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0f64],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0f64],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let mut sky = Self {
+            sun_direction,
+            turbidity,
+            ground_albedo,
+            intensity,
+            perez,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            cos_sun_theta: theta.cos(),
+            sun_radiance: sun_radiance(sun_direction, turbidity) * intensity,
+            ground_radiance: DVec3::ZERO,
+        };
+        sky.ground_radiance = ground_albedo * sky.horizontal_irradiance() / PI;
+        sky
+    }
+
+    // Radiance of the sky alone, for a direction above the horizon
+    fn sky_radiance(&self, direction: DVec3) -> DVec3 {
+        // directions grazing the horizon stay finite
+        let cos_theta = direction.y.max(1e-4);
+        let gamma = direction.dot(self.sun_direction).clamp(-1f64, 1f64).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            let perez = &self.perez[i];
+            self.zenith[i] * perez.eval(cos_theta, gamma)
+                / perez.eval(1f64, self.cos_sun_theta.acos())
+        });
+        xyy_to_rgb(x, y, luminance * LUMINANCE_SCALE) * self.intensity
+    }
+
+    fn sees_sun(&self, direction: DVec3) -> bool {
+        direction.dot(self.sun_direction) >= SUN_ANGULAR_RADIUS.cos()
+    }
+
+    // Light falling onto the ground from the sun and the whole sky
+    fn horizontal_irradiance(&self) -> DVec3 {
+        let (steps_theta, steps_phi) = (32, 64);
+        let d_theta = PI / 2f64 / steps_theta as f64;
+        let d_phi = 2f64 * PI / steps_phi as f64;
+        let mut sky = DVec3::ZERO;
+        for i in 0..steps_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let direction = DVec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                sky += self.sky_radiance(direction) * theta.cos() * theta.sin() * d_theta * d_phi;
+            }
+        }
+        let sun = self.sun_radiance * sun_solid_angle() * self.sun_direction.y.max(0f64);
+        sky + sun
+    }
+
+    fn sun_is_up(&self) -> bool {
+        self.sun_radiance != DVec3::ZERO
+    }
+}
+
+impl EnvironmentLight for PhysicalSky {
+    fn radiance(&self, direction: DVec3) -> LinearRgbColor {
+        let direction = direction.normalize();
+        if direction.y <= 0f64 {
+            return LinearRgbColor::from_vec(&self.ground_radiance);
+        }
+        let mut radiance = self.sky_radiance(direction);
+        if self.sees_sun(direction) {
+            radiance += self.sun_radiance;
+        }
+        LinearRgbColor::from_vec(&radiance)
+    }
+
+    fn sample(&self) -> Option<LightSample> {
+        let direction = if self.sun_is_up() && random::<f64>() < SUN_SAMPLE_PROBABILITY {
+            random_in_cone(self.sun_direction, SUN_ANGULAR_RADIUS.cos())
+        } else {
+            random_unit_vector()
+        };
+        Some(LightSample {
+            direction,
+            pdf: self.pdf(direction),
+        })
+    }
+
+    // Mix of the densities of the sun disk and of the uniform sphere
+    fn pdf(&self, direction: DVec3) -> f64 {
+        let uniform = 1f64 / (4f64 * PI);
+        if !self.sun_is_up() {
+            return uniform;
+        }
+        let sun = if self.sees_sun(direction.normalize()) {
+            1f64 / sun_solid_angle()
+        } else {
+            0f64
+        };
+        SUN_SAMPLE_PROBABILITY * sun + (1f64 - SUN_SAMPLE_PROBABILITY) * uniform
+    }
+}
+
+fn sun_solid_angle() -> f64 {
+    2f64 * PI * (1f64 - SUN_ANGULAR_RADIUS.cos())
+}
+
+// Sunlight left after Rayleigh and aerosol (Angstrom) scattering along the
+// path through the atmosphere, black once the sun has set
+fn sun_radiance(sun_direction: DVec3, turbidity: f64) -> DVec3 {
+    if sun_direction.y <= 0f64 {
+        return DVec3::ZERO;
+    }
+    let theta = sun_direction.y.acos();
+    // relative optical air mass, Kasten and Young (1989)
+    let air_mass = 1f64 / (theta.cos() + 0.50572 * (96.07995 - theta.to_degrees()).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = WAVELENGTHS.map(|lambda| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    });
+    DVec3::from_array(transmittance) * SUN_LUMINANCE * LUMINANCE_SCALE
+}
+
+// CIE xyY to linear sRGB, negative components clamped away
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> DVec3 {
+    if y <= 0f64 {
+        return DVec3::ZERO;
+    }
+    let xyz = DVec3::new(x * luminance / y, luminance, (1f64 - x - y) * luminance / y);
+    DVec3::new(
+        DVec3::new(3.2406, -1.5372, -0.4986).dot(xyz),
+        DVec3::new(-0.9689, 1.8758, 0.0415).dot(xyz),
+        DVec3::new(0.0557, -0.2040, 1.0570).dot(xyz),
+    )
+    .max(DVec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn afternoon() -> PhysicalSky {
+        PhysicalSky::new(DVec3::new(1f64, 1f64, 0f64), 3f64)
+    }
+
+    #[test]
+    fn test_daylight_colors() {
+        let sky = afternoon();
+        let zenith = sky.radiance(DVec3::Y).to_vec();
+        // a clear zenith is blue
+        assert!(zenith.z > zenith.x, "{zenith}");
+        // brighter around the sun than away from it
+        let near_sun = sky.radiance(DVec3::new(1f64, 0.8, 0.1)).luminance();
+        let away = sky.radiance(DVec3::new(-1f64, 0.8, 0.1)).luminance();
+        assert!(near_sun > away, "{near_sun} {away}");
+        // the sun outshines everything, and is yellowish
+        let sun = sky.radiance(sky.sun_direction()).to_vec();
+        assert!(sun.x > 100f64 * zenith.x && sun.x > sun.z, "{sun}");
+        // the ground reflects some of it
+        let ground = sky.radiance(DVec3::NEG_Y).luminance();
+        assert!(ground > 0f64 && ground < sky.radiance(DVec3::Y).luminance() * 10f64);
+    }
+
+    #[test]
+    fn test_sampling() {
+        let sky = afternoon();
+        let mut hits_sun = 0;
+        for _ in 0..1000 {
+            let sample = sky.sample().unwrap();
+            assert_eq!(sample.pdf, sky.pdf(sample.direction));
+            if sky.sees_sun(sample.direction) {
+                hits_sun += 1;
+            }
+        }
+        assert!((400..600).contains(&hits_sun), "{hits_sun}");
+    }
+
+    #[test]
+    fn test_sun_below_horizon() {
+        let sky = PhysicalSky::new(DVec3::new(1f64, -0.1, 0f64), 3f64);
+        assert_eq!(sky.pdf(sky.sun_direction()), 1f64 / (4f64 * PI));
+        assert_eq!(
+            sky.radiance(sky.sun_direction()).to_vec(),
+            sky.ground_radiance
+        );
+    }
+}
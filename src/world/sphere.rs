@@ -1,4 +1,10 @@
+use std::f64::consts::PI;
+
+use super::aabb::Aabb;
+use super::csg::{Solid, Span};
 use super::intersectable::{IntersectRecord, Intersectable};
+use super::light::{Light, LightSample};
+use crate::utils::{random_in_cone, random_unit_vector};
 use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
 use glam::DVec3;
 
@@ -6,6 +12,15 @@ pub struct Sphere {
     center: DVec3,
     radius: f64,
     material: SharedMaterial,
+    motion: Option<Motion>,
+}
+
+// A moving sphere goes at a constant speed from `center` at `start_time` to
+// `end_center` at `end_time`, and rests there before and after
+struct Motion {
+    end_center: DVec3,
+    start_time: f64,
+    end_time: f64,
 }
 
 impl Sphere {
@@ -14,15 +29,58 @@ impl Sphere {
             center,
             radius,
             material: material.clone(),
+            motion: None,
         }
     }
+
+    pub fn with_motion(mut self, end_center: DVec3, start_time: f64, end_time: f64) -> Self {
+        self.motion = (end_time > start_time).then_some(Motion {
+            end_center,
+            start_time,
+            end_time,
+        });
+        self
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    pub fn center_at(&self, time: f64) -> DVec3 {
+        match &self.motion {
+            Some(motion) => {
+                let t = ((time - motion.start_time) / (motion.end_time - motion.start_time))
+                    .clamp(0f64, 1f64);
+                self.center.lerp(motion.end_center, t)
+            }
+            None => self.center,
+        }
+    }
+
+    // Cosine of the half angle of the cone the sphere covers as seen from
+    // `origin`, together with `1 - cos` computed without cancellation. None if
+    // `origin` is inside the sphere.
+    fn visible_cone(&self, origin: DVec3) -> Option<(f64, f64)> {
+        let dist_squared = (self.center - origin).length_squared();
+        let sin_squared = self.radius * self.radius / dist_squared;
+        if sin_squared >= 1f64 {
+            return None;
+        }
+        let cos_max = (1f64 - sin_squared).sqrt();
+        Some((cos_max, sin_squared / (1f64 + cos_max)))
+    }
+
+    fn area(&self) -> f64 {
+        4f64 * PI * self.radius * self.radius
+    }
 }
 
 impl Intersectable for Sphere {
     fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
         // Solve the quadratic equation based on vector math.
         // Find the nearer intersection
-        let oc = ray.origin - self.center;
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
 
         let longest_reach = self.radius + avaliable_range.upper * ray.direction.length();
 
@@ -51,7 +109,7 @@ impl Intersectable for Sphere {
             return None;
         }
 
-        let normal_vec = (ray.at(root) - self.center) / self.radius;
+        let normal_vec = (ray.at(root) - center) / self.radius;
 
         Some(IntersectRecord::new(
             ray,
@@ -60,6 +118,77 @@ impl Intersectable for Sphere {
             self.material.clone(),
         ))
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // everywhere the sphere goes
+        let r = DVec3::splat(self.radius);
+        let start = Aabb::new(self.center - r, self.center + r);
+        Some(match &self.motion {
+            Some(motion) => start.union(&Aabb::new(motion.end_center - r, motion.end_center + r)),
+            None => start,
+        })
+    }
+}
+
+impl Solid for Sphere {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
+        let a = ray.direction.length_squared();
+        let b_half = oc.dot(ray.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+        let delta = b_half * b_half - a * c;
+        if delta <= 0f64 {
+            return vec![];
+        }
+        let record = |t: f64| {
+            let normal = (ray.at(t) - center) / self.radius;
+            IntersectRecord::new(ray, normal, t, self.material.clone())
+        };
+        vec![Span {
+            enter: record((-b_half - delta.sqrt()) / a),
+            exit: record((-b_half + delta.sqrt()) / a),
+        }]
+    }
+}
+
+// Sampled where it rests at time zero, a moving sphere should not be used as
+// a light
+impl Light for Sphere {
+    fn sample(&self, origin: DVec3) -> Option<LightSample> {
+        if let Some((cos_max, one_minus_cos)) = self.visible_cone(origin) {
+            // sample the cone of directions hitting the sphere uniformly
+            let axis = (self.center - origin).normalize();
+            return Some(LightSample {
+                direction: random_in_cone(axis, cos_max),
+                pdf: 1f64 / (2f64 * PI * one_minus_cos),
+            });
+        }
+        // from the inside, every direction hits, sample the area instead
+        let normal = random_unit_vector();
+        let to_point = self.center + self.radius * normal - origin;
+        let dist_squared = to_point.length_squared();
+        let direction = to_point.normalize_or_zero();
+        let cos = normal.dot(direction).abs();
+        if dist_squared == 0f64 || cos == 0f64 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            pdf: dist_squared / (cos * self.area()),
+        })
+    }
+
+    fn pdf(&self, origin: DVec3, direction: DVec3) -> f64 {
+        let ray = Ray::new(origin, direction.normalize());
+        let Some(rec) = self.hit(&ray, &Interval::greater_than(0f64)) else {
+            return 0f64;
+        };
+        match self.visible_cone(origin) {
+            Some((_, one_minus_cos)) => 1f64 / (2f64 * PI * one_minus_cos),
+            None => rec.t * rec.t / (rec.normal.dot(ray.direction).abs() * self.area()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +245,33 @@ mod tests {
         assert!(sphere.hit(&ray, &available_range).is_some());
     }
 
+    #[test]
+    fn test_light_sample_from_outside() {
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::new(0.0, 0.0, -4.0), 1.0, &material);
+        for _ in 0..100 {
+            let sample = sphere.sample(DVec3::ZERO).unwrap();
+            let ray = Ray::new(DVec3::ZERO, sample.direction);
+            assert!(sphere.hit(&ray, &Interval::greater_than(0.0)).is_some());
+            assert!((sphere.pdf(DVec3::ZERO, sample.direction) - sample.pdf).abs() < 1e-9);
+        }
+        assert_eq!(sphere.pdf(DVec3::ZERO, DVec3::Z), 0.0);
+    }
+
+    #[test]
+    fn test_light_sample_from_inside() {
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::ZERO, 2.0, &material);
+        let origin = DVec3::new(0.5, 0.0, 0.0);
+        // the estimated solid angle is the whole sphere of directions
+        let n = 20000;
+        let solid_angle: f64 = (0..n)
+            .map(|_| 1.0 / sphere.sample(origin).unwrap().pdf)
+            .sum::<f64>()
+            / n as f64;
+        assert!((solid_angle - 4.0 * PI).abs() < 0.2, "{}", solid_angle);
+    }
+
     #[test]
     fn test_hit_with_limited_range() {
         let sphere_center = DVec3::ZERO;
@@ -131,4 +287,33 @@ mod tests {
 
         assert!(sphere.hit(&ray, &available_range).is_none());
     }
+
+    #[test]
+    fn test_moving_sphere() {
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::ZERO, 0.5, &material).with_motion(
+            DVec3::new(2.0, 0.0, 0.0),
+            1.0,
+            2.0,
+        );
+        assert!(sphere.is_moving());
+        let bbox = sphere.bounding_box().unwrap();
+        assert_eq!(bbox.min, DVec3::new(-0.5, -0.5, -0.5));
+        assert_eq!(bbox.max, DVec3::new(2.5, 0.5, 0.5));
+
+        // a ray through where the sphere is halfway through its motion
+        let ray = Ray::new(DVec3::new(1.0, 0.0, 5.0), DVec3::NEG_Z);
+        let range = Interval::greater_than(0.0);
+        assert!(sphere.hit(&ray, &range).is_none());
+        let rec = sphere.hit(&ray.with_time(1.5), &range).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!((rec.normal - DVec3::Z).length() < 1e-9);
+        assert!(sphere
+            .hit(
+                &Ray::new(DVec3::new(1.0, 0.0, 5.0), DVec3::NEG_Z).with_time(3.0),
+                &range
+            )
+            .is_none());
+        assert_eq!(sphere.center_at(3.0), DVec3::new(2.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,217 @@
+use super::aabb::Aabb;
+use super::csg::{Solid, Span};
+use super::intersectable::{IntersectRecord, Intersectable};
+use super::plane::disk_extent;
+use super::polynomial::Polynomial;
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+use glam::DVec3;
+
+// Ring swept by a circle of `minor_radius` around `axis`, at `major_radius`
+// from the center to the middle of its tube
+pub struct Torus {
+    center: DVec3,
+    // unit vector
+    axis: DVec3,
+    major_radius: f64,
+    minor_radius: f64,
+    material: SharedMaterial,
+}
+
+impl Torus {
+    pub fn new(
+        center: DVec3,
+        axis: DVec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: &SharedMaterial,
+    ) -> Self {
+        Self {
+            center,
+            axis: axis.normalize(),
+            major_radius,
+            minor_radius,
+            material: material.clone(),
+        }
+    }
+
+    // Every parameter where the line of `ray` crosses the surface, sorted
+    fn crossings(&self, ray: &Ray) -> Vec<f64> {
+        let length = ray.direction.length();
+        let direction = ray.direction / length;
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        // Only the part of the line within the bounding sphere can cross the
+        // torus. Solving from where it enters keeps the coefficients small
+        // for far away rays, which would otherwise drown the roots.
+        let origin = ray.origin - self.center;
+        let outer = major + minor;
+        let b_half = origin.dot(direction);
+        let delta = b_half * b_half - (origin.length_squared() - outer * outer);
+        if delta <= 0f64 {
+            return vec![];
+        }
+        let enter = -b_half - delta.sqrt();
+        let origin = origin + direction * enter;
+
+        // With p = o + s d and h its height along the axis, the surface is
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (|p|^2 - h^2)
+        let b = 2f64 * origin.dot(direction);
+        let g = origin.length_squared();
+        let c = g + major * major - minor * minor;
+        let (h0, h1) = (origin.dot(self.axis), direction.dot(self.axis));
+        let k = 4f64 * major * major;
+        let quartic = Polynomial::new(vec![
+            c * c - k * (g - h0 * h0),
+            2f64 * b * c - k * (b - 2f64 * h0 * h1),
+            b * b + 2f64 * c - k * (1f64 - h1 * h1),
+            2f64 * b,
+            1f64,
+        ]);
+        quartic
+            .roots_in(&Interval::new(0f64, 2f64 * delta.sqrt()))
+            .into_iter()
+            .map(|s| (enter + s) / length)
+            .collect()
+    }
+
+    // Away from the middle of the tube
+    fn outward_normal(&self, point: DVec3) -> DVec3 {
+        let p = point - self.center;
+        let radial = p - self.axis * p.dot(self.axis);
+        (p - radial.normalize_or_zero() * self.major_radius).normalize()
+    }
+
+    fn record(&self, ray: &Ray, t: f64) -> IntersectRecord {
+        let normal = self.outward_normal(ray.at(t));
+        IntersectRecord::new(ray, normal, t, self.material.clone())
+    }
+
+    // The line starts and ends outside, so the crossings alternate between
+    // entries and exits, unless rounding lost one of a grazing pair. Each is
+    // told apart by the side of the surface the ray comes from, and those
+    // left without a partner or only touching the surface are dropped.
+    fn pair_crossings(&self, ray: &Ray, crossings: Vec<f64>) -> Vec<Span> {
+        let mut spans = vec![];
+        let mut enter: Option<f64> = None;
+        for t in crossings {
+            let facing = self.outward_normal(ray.at(t)).dot(ray.direction);
+            if facing < 0f64 {
+                // an entry lost its exit, the ray went out before this one
+                enter = Some(t);
+            } else if facing > 0f64 {
+                if let Some(enter) = enter.take() {
+                    spans.push(Span {
+                        enter: self.record(ray, enter),
+                        exit: self.record(ray, t),
+                    });
+                }
+            }
+        }
+        spans
+    }
+}
+
+impl Intersectable for Torus {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let t = self
+            .crossings(ray)
+            .into_iter()
+            .find(|t| avaliable_range.contains(*t))?;
+        Some(self.record(ray, t))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = disk_extent(self.axis, self.major_radius) + DVec3::splat(self.minor_radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl Solid for Torus {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.pair_crossings(ray, self.crossings(ray))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+
+    fn ring() -> Torus {
+        let material = DummyMaterial::new_shared();
+        Torus::new(DVec3::ZERO, DVec3::Y, 1f64, 0.25, &material)
+    }
+
+    #[test]
+    fn test_through_the_hole() {
+        let torus = ring();
+        let ray = Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X);
+        let crossings = torus.crossings(&ray);
+        let expected = [3.75, 4.25, 5.75, 6.25];
+        assert_eq!(crossings.len(), 4);
+        for (t, e) in crossings.iter().zip(expected) {
+            assert!((t - e).abs() < 1e-9, "{:?}", crossings);
+        }
+
+        let rec = torus.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.normal - DVec3::NEG_X).length() < 1e-9);
+        assert!(rec.is_front);
+        // from inside the tube
+        let rec = torus.hit(&ray, &Interval::greater_than(4f64)).unwrap();
+        assert!((rec.t - 4.25).abs() < 1e-9);
+        assert!(!rec.is_front);
+
+        // straight down the hole
+        let ray = Ray::new(DVec3::new(0f64, 5f64, 0f64), DVec3::NEG_Y);
+        assert!(torus.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_far_away_ray() {
+        // coefficients of a quartic solved from the ray origin would be far
+        // too large to find these precisely
+        let torus = ring();
+        let ray = Ray::new(DVec3::new(0f64, 0.1, 1e5), DVec3::new(0f64, 0f64, -2f64));
+        let rec = torus.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        let expected = 1e5 - 1f64 - (0.25f64 * 0.25 - 0.1 * 0.1).sqrt();
+        assert!((rec.t * 2f64 - expected).abs() < 1e-6);
+        assert_eq!(torus.spans(&ray).len(), 2);
+    }
+
+    #[test]
+    fn test_spans() {
+        let torus = ring();
+        let ray = Ray::new(DVec3::new(-5f64, 0f64, 0f64), DVec3::X);
+        let spans = torus.spans(&ray);
+        assert_eq!(spans.len(), 2);
+        assert!((spans[0].enter.t - 3.75).abs() < 1e-9);
+        assert!((spans[0].exit.t - 4.25).abs() < 1e-9);
+        assert!((spans[1].enter.t - 5.75).abs() < 1e-9);
+        assert!((spans[1].exit.t - 6.25).abs() < 1e-9);
+
+        // the exit from the first side of the ring lost, which must not join
+        // the entry there with the exit from the other side
+        let spans = torus.pair_crossings(&ray, vec![3.75, 5.75, 6.25]);
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].enter.t, spans[0].exit.t), (5.75, 6.25));
+        // and a lost entry leaves its exit alone
+        let spans = torus.pair_crossings(&ray, vec![4.25, 5.75, 6.25]);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].enter.t, 5.75);
+    }
+
+    #[test]
+    fn test_tilted() {
+        let material = DummyMaterial::new_shared();
+        let torus = Torus::new(DVec3::new(0f64, 1f64, 0f64), DVec3::X, 2f64, 0.5, &material);
+        // the tube passes above the center, through y = 3
+        let ray = Ray::new(DVec3::new(0f64, 10f64, 0f64), DVec3::NEG_Y);
+        let rec = torus.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 6.5).abs() < 1e-9);
+        assert!((rec.normal - DVec3::Y).length() < 1e-9);
+
+        let bbox = torus.bounding_box().unwrap();
+        assert!((bbox.min - DVec3::new(-0.5, -1.5, -2.5)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(0.5, 3.5, 2.5)).length() < 1e-9);
+    }
+}
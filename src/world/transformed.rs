@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use glam::{DAffine3, DMat3, DQuat, DVec3};
+
+use super::aabb::Aabb;
+use super::csg::{Solid, Span};
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::ray::Ray;
+use crate::utils::Interval;
+
+// Geometry that many instances can refer to without copying it
+pub type SharedIntersectable = Arc<dyn Intersectable>;
+
+// An object placed in the world by an affine transform. Rays are moved into
+// the space of the object instead of the object into the world, so `object`
+// can be shared (e.g. a `SharedIntersectable` mesh) by any number of
+// differently placed instances.
+pub struct Transformed<I: Intersectable> {
+    object: I,
+    to_world: DAffine3,
+    to_object: DAffine3,
+    // normals are transformed by the inverse transpose, so that they stay
+    // perpendicular to the surface under non-uniform scaling
+    normal_to_world: DMat3,
+}
+
+impl<I: Intersectable> Transformed<I> {
+    pub fn new(object: I, to_world: DAffine3) -> Self {
+        let to_object = to_world.inverse();
+        Self {
+            object,
+            to_world,
+            to_object,
+            normal_to_world: to_object.matrix3.transpose(),
+        }
+    }
+
+    // Scales, then rotates, then translates `object`
+    pub fn from_parts(object: I, scale: DVec3, rotation: DQuat, translation: DVec3) -> Self {
+        Self::new(
+            object,
+            DAffine3::from_scale_rotation_translation(scale, rotation, translation),
+        )
+    }
+
+    pub fn object(&self) -> &I {
+        &self.object
+    }
+
+    pub fn transform(&self) -> DAffine3 {
+        self.to_world
+    }
+
+    // The direction is not normalized, so a point at `t` along the object
+    // space ray is the same point at `t` along the world ray, and ranges and
+    // hit distances need no conversion.
+    fn to_object_ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.to_object.transform_point3(ray.origin),
+            direction: self.to_object.transform_vector3(ray.direction),
+            time: ray.time,
+        }
+    }
+
+    fn to_world_record(&self, ray: &Ray, mut rec: IntersectRecord) -> IntersectRecord {
+        rec.point = ray.at(rec.t);
+        // the normal already faces against the ray, which the transform keeps
+        rec.normal = (self.normal_to_world * rec.normal).normalize();
+        rec
+    }
+}
+
+impl<I: Intersectable> Intersectable for Transformed<I> {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let rec = self.object.hit(&self.to_object_ray(ray), avaliable_range)?;
+        Some(self.to_world_record(ray, rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local = self.object.bounding_box()?;
+        if local.is_empty() {
+            return Some(local);
+        }
+        let corners = (0..8).map(|i| {
+            let corner = DVec3::new(
+                if i & 1 == 0 { local.min.x } else { local.max.x },
+                if i & 2 == 0 { local.min.y } else { local.max.y },
+                if i & 4 == 0 { local.min.z } else { local.max.z },
+            );
+            self.to_world.transform_point3(corner)
+        });
+        Some(Aabb::from_points(corners))
+    }
+}
+
+impl<I: Solid> Solid for Transformed<I> {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.object
+            .spans(&self.to_object_ray(ray))
+            .into_iter()
+            .map(|span| Span {
+                enter: self.to_world_record(ray, span.enter),
+                exit: self.to_world_record(ray, span.exit),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use crate::world::{BvhContainer, Sphere};
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn test_translated_and_scaled() {
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::ZERO, 1.0, &material);
+        let moved = Transformed::from_parts(
+            sphere,
+            DVec3::splat(2.0),
+            DQuat::IDENTITY,
+            DVec3::new(0.0, 0.0, -5.0),
+        );
+
+        let ray = Ray::new(DVec3::ZERO, DVec3::NEG_Z);
+        let rec = moved.hit(&ray, &Interval::greater_than(0.0)).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.point - DVec3::new(0.0, 0.0, -3.0)).length() < 1e-9);
+        assert!((rec.normal - DVec3::Z).length() < 1e-9);
+        assert!(rec.is_front);
+
+        // the range is in world units
+        assert!(moved.hit(&ray, &Interval::new(0.0, 2.5)).is_none());
+
+        let bbox = moved.bounding_box().unwrap();
+        assert!((bbox.min - DVec3::new(-2.0, -2.0, -7.0)).length() < 1e-9);
+        assert!((bbox.max - DVec3::new(2.0, 2.0, -3.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_non_uniform_scale_normal() {
+        // an ellipsoid twice as wide as it is high
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::ZERO, 1.0, &material);
+        let ellipsoid = Transformed::from_parts(
+            sphere,
+            DVec3::new(2.0, 1.0, 1.0),
+            DQuat::IDENTITY,
+            DVec3::ZERO,
+        );
+        let point = DVec3::new(2f64.sqrt(), 0.5f64.sqrt(), 0.0);
+        let ray = Ray::new(point * 2.0, -point);
+        let rec = ellipsoid.hit(&ray, &Interval::greater_than(0.0)).unwrap();
+        assert!((rec.point - point).length() < 1e-9);
+        // gradient of x²/4 + y² + z²
+        let expected = DVec3::new(point.x / 2.0, 2.0 * point.y, 0.0).normalize();
+        assert!((rec.normal - expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_rotated_bounding_box() {
+        let material = DummyMaterial::new_shared();
+        let sphere = Sphere::new(DVec3::new(1.0, 0.0, 0.0), 0.5, &material);
+        let rotated = Transformed::new(sphere, DAffine3::from_rotation_z(FRAC_PI_2));
+        let bbox = rotated.bounding_box().unwrap();
+        assert!((bbox.centroid() - DVec3::new(0.0, 1.0, 0.0)).length() < 1e-9);
+        let ray = Ray::new(DVec3::new(0.0, 1.0, 5.0), DVec3::NEG_Z);
+        assert!(rotated.hit(&ray, &Interval::greater_than(0.0)).is_some());
+    }
+
+    #[test]
+    fn test_shared_instances() {
+        let material = DummyMaterial::new_shared();
+        let shared: SharedIntersectable = Arc::new(Sphere::new(DVec3::ZERO, 0.5, &material));
+        let instances: Vec<Box<dyn Intersectable>> = (0..100)
+            .map(|i| {
+                Transformed::new(
+                    shared.clone(),
+                    DAffine3::from_translation(DVec3::new(i as f64, 0.0, 0.0)),
+                )
+                .into_box()
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&shared), 101);
+
+        let world = BvhContainer::build(instances);
+        let ray = Ray::new(DVec3::new(42.0, 0.0, 5.0), DVec3::NEG_Z);
+        let rec = world.hit(&ray, &Interval::greater_than(0.0)).unwrap();
+        assert!((rec.point - DVec3::new(42.0, 0.0, 0.5)).length() < 1e-9);
+    }
+}
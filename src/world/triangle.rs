@@ -0,0 +1,410 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::intersectable::{IntersectRecord, Intersectable};
+use crate::{materials::SharedMaterial, ray::Ray, utils::Interval};
+use glam::{DVec2, DVec3};
+
+// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013).
+// The triangle is transformed into a ray-aligned space where the ray starts at
+// the origin and points along +z, so the edge tests of neighboring triangles
+// are evaluated with exactly the same arithmetic and no ray can slip through a
+// shared edge. Returns `t` and the barycentric weights of the three vertices.
+fn intersect_triangle(
+    ray: &Ray,
+    p0: DVec3,
+    p1: DVec3,
+    p2: DVec3,
+    avaliable_range: &Interval,
+) -> Option<(f64, DVec3)> {
+    let dir = ray.direction;
+    let abs_dir = dir.abs();
+    let kz = if abs_dir.x > abs_dir.y && abs_dir.x > abs_dir.z {
+        0
+    } else if abs_dir.y > abs_dir.z {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if dir[kz] < 0f64 {
+        // preserve the winding of the triangle
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1f64 / dir[kz];
+
+    let a = p0 - ray.origin;
+    let b = p1 - ray.origin;
+    let c = p2 - ray.origin;
+
+    let ax = a[kx] - sx * a[kz];
+    let ay = a[ky] - sy * a[kz];
+    let bx = b[kx] - sx * b[kz];
+    let by = b[ky] - sy * b[kz];
+    let cx = c[kx] - sx * c[kz];
+    let cy = c[ky] - sy * c[kz];
+
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0f64 || v < 0f64 || w < 0f64) && (u > 0f64 || v > 0f64 || w > 0f64) {
+        // the edge functions disagree, the ray passes outside of the triangle
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0f64 {
+        // the ray is parallel to the triangle
+        return None;
+    }
+
+    let t_scaled = u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz];
+    let t = t_scaled / det;
+    if !avaliable_range.contains(t) {
+        return None;
+    }
+
+    Some((t, DVec3::new(u, v, w) / det))
+}
+
+fn triangle_record(
+    ray: &Ray,
+    p0: DVec3,
+    p1: DVec3,
+    p2: DVec3,
+    t: f64,
+    barycentric: DVec3,
+    material: &SharedMaterial,
+) -> IntersectRecord {
+    // counter-clockwise vertices face the viewer
+    let outward_normal = (p1 - p0).cross(p2 - p0).normalize();
+    IntersectRecord::new(ray, outward_normal, t, material.clone())
+        .with_uv(DVec2::new(barycentric.y, barycentric.z))
+}
+
+pub struct Triangle {
+    vertices: [DVec3; 3],
+    material: SharedMaterial,
+}
+
+impl Triangle {
+    pub fn new(p0: DVec3, p1: DVec3, p2: DVec3, material: &SharedMaterial) -> Self {
+        Self {
+            vertices: [p0, p1, p2],
+            material: material.clone(),
+        }
+    }
+}
+
+impl Intersectable for Triangle {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let [p0, p1, p2] = self.vertices;
+        let (t, barycentric) = intersect_triangle(ray, p0, p1, p2, avaliable_range)?;
+        Some(triangle_record(
+            ray,
+            p0,
+            p1,
+            p2,
+            t,
+            barycentric,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices))
+    }
+}
+
+// Vertex and index buffers shared by every triangle of a mesh, so that meshes
+// and their individual triangles can be handed out without copying geometry.
+pub struct MeshBuffers {
+    pub vertices: Vec<DVec3>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+pub struct TriangleMesh {
+    buffers: Arc<MeshBuffers>,
+    materials: Vec<SharedMaterial>,
+    // One index into `materials` per face. Empty when the whole mesh uses the
+    // first material.
+    face_materials: Vec<usize>,
+}
+
+impl TriangleMesh {
+    pub fn new(vertices: Vec<DVec3>, indices: Vec<[usize; 3]>, material: &SharedMaterial) -> Self {
+        Self::from_buffers(Arc::new(MeshBuffers { vertices, indices }), material)
+    }
+
+    pub fn from_buffers(buffers: Arc<MeshBuffers>, material: &SharedMaterial) -> Self {
+        Self::with_face_materials(buffers, vec![material.clone()], vec![])
+    }
+
+    pub fn with_face_materials(
+        buffers: Arc<MeshBuffers>,
+        materials: Vec<SharedMaterial>,
+        face_materials: Vec<usize>,
+    ) -> Self {
+        assert!(
+            buffers
+                .indices
+                .iter()
+                .flatten()
+                .all(|&i| i < buffers.vertices.len()),
+            "triangle index out of the vertex buffer"
+        );
+        assert!(!materials.is_empty(), "a mesh needs at least one material");
+        assert!(
+            face_materials.is_empty() || face_materials.len() == buffers.indices.len(),
+            "face materials do not match the number of faces"
+        );
+        assert!(
+            face_materials.iter().all(|&m| m < materials.len()),
+            "face material index out of the material list"
+        );
+        Self {
+            buffers,
+            materials,
+            face_materials,
+        }
+    }
+
+    // Same geometry, with `material` for the faces that used the first one
+    pub fn with_default_material(&self, material: &SharedMaterial) -> Self {
+        let mut materials = self.materials.clone();
+        materials[0] = material.clone();
+        Self {
+            buffers: self.buffers.clone(),
+            materials,
+            face_materials: self.face_materials.clone(),
+        }
+    }
+
+    pub fn buffers(&self) -> &Arc<MeshBuffers> {
+        &self.buffers
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.indices.is_empty()
+    }
+
+    // Splits the mesh into individually intersectable triangles that still
+    // share the buffers of the mesh.
+    pub fn triangles(&self) -> impl Iterator<Item = MeshTriangle> + '_ {
+        (0..self.len()).map(|index| MeshTriangle {
+            buffers: self.buffers.clone(),
+            index,
+            material: self.material_of(index).clone(),
+        })
+    }
+
+    pub fn material_of(&self, face: usize) -> &SharedMaterial {
+        match self.face_materials.get(face) {
+            Some(&m) => &self.materials[m],
+            None => &self.materials[0],
+        }
+    }
+}
+
+fn face_of(buffers: &MeshBuffers, index: usize) -> (DVec3, DVec3, DVec3) {
+    let [i0, i1, i2] = buffers.indices[index];
+    (
+        buffers.vertices[i0],
+        buffers.vertices[i1],
+        buffers.vertices[i2],
+    )
+}
+
+impl Intersectable for TriangleMesh {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let mut current_range = *avaliable_range;
+        let mut nearest = None;
+        for index in 0..self.len() {
+            let (p0, p1, p2) = face_of(&self.buffers, index);
+            if let Some((t, barycentric)) = intersect_triangle(ray, p0, p1, p2, &current_range) {
+                current_range.upper = t;
+                nearest = Some((index, t, barycentric));
+            }
+        }
+        let (index, t, barycentric) = nearest?;
+        let (p0, p1, p2) = face_of(&self.buffers, index);
+        Some(triangle_record(
+            ray,
+            p0,
+            p1,
+            p2,
+            t,
+            barycentric,
+            self.material_of(index),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.buffers.vertices.iter().copied()))
+    }
+}
+
+pub struct MeshTriangle {
+    buffers: Arc<MeshBuffers>,
+    index: usize,
+    material: SharedMaterial,
+}
+
+impl MeshTriangle {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl Intersectable for MeshTriangle {
+    fn hit(&self, ray: &Ray, avaliable_range: &Interval) -> Option<IntersectRecord> {
+        let (p0, p1, p2) = face_of(&self.buffers, self.index);
+        let (t, barycentric) = intersect_triangle(ray, p0, p1, p2, avaliable_range)?;
+        Some(triangle_record(
+            ray,
+            p0,
+            p1,
+            p2,
+            t,
+            barycentric,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (p0, p1, p2) = face_of(&self.buffers, self.index);
+        Some(Aabb::from_points([p0, p1, p2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::material::DummyMaterial;
+    use rand::{
+        distributions::{Distribution, Uniform},
+        rngs::StdRng,
+        SeedableRng,
+    };
+
+    fn unit_triangle() -> Triangle {
+        let material = DummyMaterial::new_shared();
+        Triangle::new(DVec3::ZERO, DVec3::X, DVec3::Y, &material)
+    }
+
+    #[test]
+    fn test_hit_front() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(0.25, 0.25, 1f64), DVec3::NEG_Z);
+        let rec = triangle.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+        assert!(rec.is_front);
+        assert_eq!(rec.normal, DVec3::Z);
+    }
+
+    #[test]
+    fn test_hit_back() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(0.25, 0.25, -1f64), DVec3::Z);
+        let rec = triangle.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!(!rec.is_front);
+        assert_eq!(rec.normal, DVec3::NEG_Z);
+    }
+
+    #[test]
+    fn test_miss_outside() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(0.6, 0.6, 1f64), DVec3::NEG_Z);
+        assert!(triangle.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_parallel_misses() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(-1f64, 0.25, 0f64), DVec3::X);
+        assert!(triangle.hit(&ray, &Interval::greater_than(0f64)).is_none());
+    }
+
+    #[test]
+    fn test_hit_with_limited_range() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(0.25, 0.25, 1f64), DVec3::NEG_Z);
+        assert!(triangle.hit(&ray, &Interval::new(0f64, 0.5)).is_none());
+    }
+
+    #[test]
+    fn test_barycentric() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(DVec3::new(0.2, 0.3, 1f64), DVec3::NEG_Z);
+        let rec = triangle.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        // weights of the second (X) and third (Y) vertex
+        assert!((rec.uv - DVec2::new(0.2, 0.3)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_shared_edge_is_watertight() {
+        // a unit square split along its diagonal
+        let material = DummyMaterial::new_shared();
+        let mesh = TriangleMesh::new(
+            vec![
+                DVec3::ZERO,
+                DVec3::X,
+                DVec3::new(1f64, 1f64, 0f64),
+                DVec3::Y,
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+            &material,
+        );
+        let triangles: Vec<MeshTriangle> = mesh.triangles().collect();
+
+        let mut rng = StdRng::from_seed([42; 32]);
+        let uniform = Uniform::new_inclusive(0.01, 0.99);
+        for _ in 0..1000 {
+            // rays aimed at the shared diagonal from random origins
+            let s = uniform.sample(&mut rng);
+            let target = DVec3::new(s, s, 0f64);
+            let origin = DVec3::new(
+                uniform.sample(&mut rng) * 4f64 - 2f64,
+                uniform.sample(&mut rng) * 4f64 - 2f64,
+                1f64,
+            );
+            let ray = Ray::new(origin, target - origin);
+            let range = Interval::greater_than(0f64);
+            assert!(mesh.hit(&ray, &range).is_some(), "target {}", target);
+            assert!(
+                triangles.iter().any(|t| t.hit(&ray, &range).is_some()),
+                "target {}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_mesh_nearest_hit() {
+        let material = DummyMaterial::new_shared();
+        let mesh = TriangleMesh::new(
+            vec![
+                DVec3::new(0f64, 0f64, -2f64),
+                DVec3::new(1f64, 0f64, -2f64),
+                DVec3::new(0f64, 1f64, -2f64),
+                DVec3::new(0f64, 0f64, -1f64),
+                DVec3::new(1f64, 0f64, -1f64),
+                DVec3::new(0f64, 1f64, -1f64),
+            ],
+            vec![[0, 1, 2], [3, 4, 5]],
+            &material,
+        );
+        let ray = Ray::new(DVec3::new(0.25, 0.25, 0f64), DVec3::NEG_Z);
+        let rec = mesh.hit(&ray, &Interval::greater_than(0f64)).unwrap();
+        assert!((rec.t - 1f64).abs() < 1e-9);
+    }
+}